@@ -0,0 +1,30 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use toy_payments_engine::models::{Transaction, ValidationPolicy};
+
+// Deserialize every record of an arbitrary CSV file the same way `process_csv` does
+fuzz_target!(|data: &[u8]| {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data);
+
+    let Ok(headers) = reader.headers().cloned() else {
+        return;
+    };
+
+    for record in reader.records().flatten() {
+        let Ok(transaction) = record.deserialize::<Transaction>(Some(&headers)) else {
+            continue;
+        };
+
+        // Whatever the partner sends, nothing unusable may pass validation
+        let policy = ValidationPolicy::default();
+        if transaction.validate(&policy).is_ok() {
+            if let Some(amount) = transaction.amount {
+                assert!(amount.is_finite(), "non-finite amount accepted: {:?}", transaction);
+                assert!(amount <= policy.max_amount(), "oversized amount accepted: {:?}", transaction);
+            }
+        }
+    }
+});
@@ -0,0 +1,30 @@
+#![no_main]
+
+use std::sync::OnceLock;
+
+use indicatif::ProgressBar;
+use libfuzzer_sys::fuzz_target;
+use tokio::runtime::Runtime;
+use toy_payments_engine::engine::TransactionEngine;
+use toy_payments_engine::utils::process_reader;
+use toy_payments_engine_fuzz::assert_invariants;
+
+fn runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("failed to build runtime")
+    })
+}
+
+// Run a whole arbitrary file through the same path as `process_csv`
+fuzz_target!(|data: &[u8]| {
+    let mut engine = TransactionEngine::new();
+    let progress_bar = ProgressBar::hidden();
+
+    // Errors are fine (e.g. an unknown transaction type), panics are not
+    let _ = runtime().block_on(process_reader(data, &mut engine, &progress_bar));
+
+    assert_invariants(&engine);
+});
@@ -0,0 +1,71 @@
+#![no_main]
+
+use arbitrary::{Arbitrary, Unstructured};
+use chrono::DateTime;
+use libfuzzer_sys::fuzz_target;
+use toy_payments_engine::engine::TransactionEngine;
+use toy_payments_engine::models::{Transaction, TransactionType};
+use toy_payments_engine::utils::validate_transaction;
+use toy_payments_engine_fuzz::assert_invariants;
+
+fn arbitrary_transaction(u: &mut Unstructured) -> arbitrary::Result<Transaction> {
+    let tx_type = match u.int_in_range(0..=10)? {
+        0 => TransactionType::Deposit,
+        1 => TransactionType::Withdrawal,
+        2 => TransactionType::Dispute,
+        3 => TransactionType::Resolve,
+        4 => TransactionType::Chargeback,
+        5 => TransactionType::Convert,
+        6 => TransactionType::Transfer,
+        7 => TransactionType::Authorize,
+        8 => TransactionType::Capture,
+        9 => TransactionType::Void,
+        _ => TransactionType::Refund,
+    };
+
+    // Small id ranges so disputes actually hit earlier transactions
+    let mut transaction = Transaction::new(
+        tx_type,
+        u.int_in_range(0..=4)?,
+        u.int_in_range(0..=16)?,
+        Option::<f64>::arbitrary(u)?,
+    );
+
+    // A few currencies with a rate between them, and one validation refuses
+    let currency = |u: &mut Unstructured| -> arbitrary::Result<Option<String>> {
+        Ok(match u.int_in_range(0..=3)? {
+            0 => None,
+            1 => Some("EUR".to_string()),
+            2 => Some("USD".to_string()),
+            _ => Some("eur".to_string()),
+        })
+    };
+    transaction.currency = currency(u)?;
+    transaction.to_currency = currency(u)?;
+    transaction.to_client = Option::<u8>::arbitrary(u)?.map(|client| u16::from(client % 5));
+    transaction.ref_tx = Option::<u8>::arbitrary(u)?.map(|tx| u32::from(tx % 17));
+
+    // Timestamps within a few days, mostly but not always in order
+    transaction.timestamp = Option::<u32>::arbitrary(u)?
+        .and_then(|seconds| DateTime::from_timestamp(1_700_000_000 + i64::from(seconds % 300_000), 0));
+
+    Ok(transaction)
+}
+
+// Only transactions accepted by `validate_transaction` reach the engine, as in `process_csv`
+fuzz_target!(|data: &[u8]| {
+    let mut u = Unstructured::new(data);
+    let mut engine = TransactionEngine::new();
+    engine.rates.insert("EUR", "USD", 1.1).unwrap();
+
+    // Unstructured keeps yielding defaults once exhausted, so stop on empty input
+    while !u.is_empty() {
+        let Ok(transaction) = arbitrary_transaction(&mut u) else {
+            break;
+        };
+        if validate_transaction(&transaction).is_ok() {
+            let _ = engine.handle_transaction(transaction);
+            assert_invariants(&engine);
+        }
+    }
+});
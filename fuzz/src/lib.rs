@@ -0,0 +1,21 @@
+use toy_payments_engine::engine::TransactionEngine;
+
+/// Panics if any account breaks the ledger invariants.
+pub fn assert_invariants(engine: &TransactionEngine) {
+    for (_, account) in engine.balances() {
+        assert!(
+            account.available.is_finite() && account.held.is_finite() && account.total.is_finite(),
+            "non-finite balance: {:?}",
+            account
+        );
+        assert!(account.held >= -1e-9, "negative held funds: {:?}", account);
+
+        // Relative tolerance, amounts can be large
+        let tolerance = 1e-9 * account.total.abs().max(1.0);
+        assert!(
+            (account.total - (account.available + account.held)).abs() <= tolerance,
+            "total != available + held: {:?}",
+            account
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use std::env;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use toy_payments_engine::generator::{generate, GeneratorConfig};
+
+const USAGE: &str = "Usage: generate <output_file> [--seed <n>] [--clients <n>] [--transactions <n>] \
+[--mix <type>=<weight>,...] [--amounts fixed:<a>|uniform:<min>:<max>|exponential:<mean>] \
+[--invalid-ratio <0..1>] [--malformed-ratio <0..1>] [--max-amount <amount>] [--expected <file>]";
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    if let Err(e) = run(&args) {
+        eprintln!("Error: {}", e);
+        eprintln!("{}", USAGE);
+        process::exit(1);
+    }
+}
+
+fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = GeneratorConfig::default();
+    let mut output_file: Option<PathBuf> = None;
+    let mut expected_file: Option<PathBuf> = None;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        if !arg.starts_with("--") {
+            if output_file.replace(PathBuf::from(arg)).is_some() {
+                return Err("Only one output file can be given".into());
+            }
+            continue;
+        }
+
+        let value = iter
+            .next()
+            .ok_or_else(|| format!("Missing value for {}", arg))?;
+
+        match arg.as_str() {
+            "--seed" => config.seed = value.parse()?,
+            "--clients" => config.clients = value.parse()?,
+            "--transactions" => config.transactions = value.parse()?,
+            "--mix" => config.mix = value.parse()?,
+            "--amounts" => config.amounts = value.parse()?,
+            "--invalid-ratio" => config.invalid_ratio = parse_ratio(value)?,
+            "--malformed-ratio" => config.malformed_ratio = parse_ratio(value)?,
+            "--max-amount" => {
+                let max_amount: f64 = value.parse()?;
+                if !max_amount.is_finite() || max_amount <= 0.0 {
+                    return Err(format!("Invalid maximum amount: {}", value).into());
+                }
+                config.max_amount = max_amount;
+            }
+            "--expected" => expected_file = Some(PathBuf::from(value)),
+            _ => return Err(format!("Unknown option {}", arg).into()),
+        }
+    }
+
+    let output_file = output_file.ok_or("Missing output file")?;
+    let expected_file = expected_file.unwrap_or_else(|| expected_path_for(&output_file));
+
+    let transactions = BufWriter::new(File::create(&output_file)?);
+    let expected = BufWriter::new(File::create(&expected_file)?);
+    let summary = generate(&config, transactions, expected)?;
+
+    println!(
+        "Dataset generated successfully: {} ({} rows, {} deliberately invalid, {} malformed)",
+        output_file.display(),
+        summary.rows,
+        summary.deliberately_invalid,
+        summary.malformed
+    );
+    println!(
+        "Expected accounts written to: {} ({} accounts)",
+        expected_file.display(),
+        summary.accounts
+    );
+
+    Ok(())
+}
+
+fn parse_ratio(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(ratio) if (0.0..=1.0).contains(&ratio) => Ok(ratio),
+        _ => Err(format!("Ratio must be between 0 and 1, got {}", value)),
+    }
+}
+
+/// `data/large/run.csv` -> `data/large/run_expected.csv`
+fn expected_path_for(output_file: &Path) -> PathBuf {
+    let stem = output_file
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "transactions".into());
+    output_file.with_file_name(format!("{}_expected.csv", stem))
+}
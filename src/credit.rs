@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+
+use serde::Deserialize;
+
+use crate::models::{is_valid_currency, Currency};
+
+/// A row of the credit limits file; an empty currency applies to the default balance.
+#[derive(Debug, Deserialize)]
+struct CreditRow {
+    client: u16,
+    #[serde(default)]
+    currency: Option<Currency>,
+    credit_limit: f64,
+}
+
+/// Why a credit limits file could not be loaded.
+#[derive(Debug)]
+pub enum CreditError {
+    Csv(csv::Error),
+    InvalidCurrency(Currency),
+    InvalidLimit { client: u16, limit: f64 },
+    DuplicateLimit { client: u16, currency: Option<Currency> },
+}
+
+impl fmt::Display for CreditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CreditError::Csv(e) => write!(f, "Error reading credit limits: {}", e),
+            CreditError::InvalidCurrency(code) => {
+                write!(f, "Invalid currency code '{}' in credit limits", code)
+            }
+            CreditError::InvalidLimit { client, limit } => {
+                write!(f, "Credit limit {} of client {} must be a non-negative number", limit, client)
+            }
+            CreditError::DuplicateLimit { client, currency } => write!(
+                f,
+                "Credit limit of client {} in {} is listed more than once",
+                client,
+                currency.as_deref().unwrap_or("the default currency")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CreditError {}
+
+impl From<csv::Error> for CreditError {
+    fn from(e: csv::Error) -> Self {
+        CreditError::Csv(e)
+    }
+}
+
+/// Agreed overdrafts, loaded from a `client,currency,credit_limit` CSV file. A balance
+/// with a credit limit may go down to `-credit_limit` available; every other balance
+/// stays at or above zero.
+#[derive(Debug, Clone, Default)]
+pub struct CreditLimits {
+    limits: HashMap<(u16, Option<Currency>), f64>,
+}
+
+impl CreditLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads credit limits from a CSV file.
+    pub fn from_path(path: &str) -> Result<Self, CreditError> {
+        let file = std::fs::File::open(path).map_err(csv::Error::from)?;
+        Self::from_reader(file)
+    }
+
+    /// Loads credit limits from any CSV source.
+    pub fn from_reader<R: io::Read>(reader: R) -> Result<Self, CreditError> {
+        let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(reader);
+        let mut limits = Self::new();
+
+        for row in reader.deserialize() {
+            let row: CreditRow = row?;
+            limits.insert(row.client, row.currency.as_deref(), row.credit_limit)?;
+        }
+
+        Ok(limits)
+    }
+
+    /// Grants `client_id` an overdraft of `limit` on its balance in `currency`.
+    pub fn insert(&mut self, client_id: u16, currency: Option<&str>, limit: f64) -> Result<(), CreditError> {
+        if let Some(code) = currency.filter(|code| !is_valid_currency(code)) {
+            return Err(CreditError::InvalidCurrency(code.to_string()));
+        }
+        if !limit.is_finite() || limit < 0.0 {
+            return Err(CreditError::InvalidLimit { client: client_id, limit });
+        }
+
+        let key = (client_id, currency.map(str::to_string));
+        if self.limits.contains_key(&key) {
+            return Err(CreditError::DuplicateLimit { client: key.0, currency: key.1 });
+        }
+        self.limits.insert(key, limit);
+
+        Ok(())
+    }
+
+    /// How far below zero the balance may go; zero without an agreed overdraft.
+    pub fn limit(&self, client_id: u16, currency: Option<&str>) -> f64 {
+        self.limits
+            .get(&(client_id, currency.map(str::to_string)))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Whether any credit limit is configured at all.
+    pub fn is_active(&self) -> bool {
+        !self.limits.is_empty()
+    }
+}
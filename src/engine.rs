@@ -1,221 +1,1392 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
 
-use crate::models::{ClientAccount, TransactionType, Transaction};
+use crate::models::{
+    ClientAccount, Currency, TimePolicy, Timestamp, TransactionType, Transaction, ValidationError, ValidationPolicy,
+};
+use crate::credit::CreditLimits;
+use crate::fees::FeeSchedule;
+use crate::journal::{self, Event, EventKind, Journal};
+use crate::ledger::{Ledger, LedgerAccount, Posting, SystemBook};
+use crate::limits::{BreachAction, LimitBreach, LimitPolicy, LimitWindow};
+use crate::rates::{convert_amount, RateTable};
+
+/// Why the engine refused a transaction.
+///
+/// `Invalid` means the row itself is malformed for its type and never reached the ledger;
+/// every other variant is a business rule rejecting an otherwise valid transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineError {
+    Invalid(ValidationError),
+    AccountLocked(u16),
+    AccountNotFound { client_id: u16, currency: Option<Currency>, operation: TransactionType },
+    TransactionNotFound { tx_id: u32, operation: TransactionType },
+    ClientMismatch { expected: u16, got: u16, operation: TransactionType },
+    CurrencyMismatch { tx_id: u32, expected: Option<Currency>, got: Currency },
+    MissingAmount(u32),
+    InsufficientFunds { available: f64, amount: f64, operation: TransactionType },
+    InsufficientHeldFunds { held: f64, amount: f64, operation: TransactionType },
+    NoRate { from: Currency, to: Currency },
+    ConversionTooSmall { amount: f64, rate: f64 },
+    NotDisputable { tx_id: u32, kind: TransactionType, operation: TransactionType },
+    PartialConversionDispute(u32),
+    AlreadyDisputed(u32),
+    DisputeExceedsRemaining { tx_id: u32, amount: f64, remaining: f64 },
+    NotDisputed { tx_id: u32, operation: TransactionType },
+    ExceedsDisputedAmount { tx_id: u32, amount: f64, held: f64, operation: TransactionType },
+    NotAnAuthorization { tx_id: u32, operation: TransactionType },
+    AuthorizationClosed { tx_id: u32, state: AuthorizationState, operation: TransactionType },
+    CaptureExceedsAuthorization { tx_id: u32, amount: f64, authorized: f64 },
+    NotRefundable { tx_id: u32, kind: TransactionType },
+    RefundExceedsRemaining { tx_id: u32, amount: f64, remaining: f64 },
+    LimitExceeded { client_id: u16, breach: LimitBreach, locked: bool },
+    OutOfOrder { tx_id: u32, timestamp: Timestamp, latest: Timestamp },
+    DisputeWindowClosed { tx_id: u32, occurred: Timestamp, disputed: Timestamp },
+    DuplicateTransaction { tx_id: u32, existing: TransactionType },
+}
+
+impl EngineError {
+    pub fn is_validation(&self) -> bool {
+        matches!(self, EngineError::Invalid(_))
+    }
+
+    /// Stable name of the rule that refused the transaction, e.g. `insufficient_funds`.
+    pub fn rule(&self) -> &'static str {
+        match self {
+            EngineError::Invalid(e) => e.rule(),
+            EngineError::AccountLocked(_) => "account_locked",
+            EngineError::AccountNotFound { .. } => "account_not_found",
+            EngineError::TransactionNotFound { .. } => "transaction_not_found",
+            EngineError::ClientMismatch { .. } => "client_mismatch",
+            EngineError::CurrencyMismatch { .. } => "currency_mismatch",
+            EngineError::MissingAmount(_) => "missing_amount",
+            EngineError::InsufficientFunds { .. } => "insufficient_funds",
+            EngineError::InsufficientHeldFunds { .. } => "insufficient_held_funds",
+            EngineError::NoRate { .. } => "no_rate",
+            EngineError::ConversionTooSmall { .. } => "conversion_too_small",
+            EngineError::NotDisputable { .. } => "not_disputable",
+            EngineError::PartialConversionDispute(_) => "partial_conversion_dispute",
+            EngineError::AlreadyDisputed(_) => "already_disputed",
+            EngineError::DisputeExceedsRemaining { .. } => "dispute_exceeds_remaining",
+            EngineError::NotDisputed { .. } => "not_disputed",
+            EngineError::ExceedsDisputedAmount { .. } => "exceeds_disputed_amount",
+            EngineError::NotAnAuthorization { .. } => "not_an_authorization",
+            EngineError::AuthorizationClosed { .. } => "authorization_closed",
+            EngineError::CaptureExceedsAuthorization { .. } => "capture_exceeds_authorization",
+            EngineError::NotRefundable { .. } => "not_refundable",
+            EngineError::RefundExceedsRemaining { .. } => "refund_exceeds_remaining",
+            EngineError::LimitExceeded { .. } => "limit_exceeded",
+            EngineError::OutOfOrder { .. } => "out_of_order",
+            EngineError::DisputeWindowClosed { .. } => "dispute_window_closed",
+            EngineError::DuplicateTransaction { .. } => "duplicate_transaction",
+        }
+    }
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::Invalid(e) => write!(f, "{}", e),
+            EngineError::AccountLocked(_) => write!(f, "Account is locked"),
+            EngineError::AccountNotFound { client_id, currency, operation } => write!(
+                f,
+                "Account for Client ID {}{} not found. Cannot process {}.",
+                client_id,
+                currency.as_ref().map(|c| format!(" in {}", c)).unwrap_or_default(),
+                operation
+            ),
+            EngineError::TransactionNotFound { tx_id, operation } => {
+                write!(f, "Transaction with ID {} not found for {}", tx_id, operation)
+            }
+            EngineError::ClientMismatch { expected, got, operation } => write!(
+                f,
+                "{:?} client ID mismatch: expected {}, got {}",
+                operation, expected, got
+            ),
+            EngineError::CurrencyMismatch { tx_id, expected, got } => write!(
+                f,
+                "Currency mismatch for transaction {}: expected {}, got {}",
+                tx_id,
+                expected.as_deref().unwrap_or("the default currency"),
+                got
+            ),
+            EngineError::MissingAmount(tx_id) => {
+                write!(f, "Transaction with ID {} does not have an associated amount", tx_id)
+            }
+            EngineError::InsufficientFunds { available, amount, operation } => write!(
+                f,
+                "Insufficient funds: Available = {}, {:?} amount = {}",
+                available, operation, amount
+            ),
+            EngineError::InsufficientHeldFunds { held, amount, operation } => write!(
+                f,
+                "Insufficient held funds: Held = {}, {:?} amount = {}",
+                held, operation, amount
+            ),
+            EngineError::NoRate { from, to } => write!(f, "No exchange rate from {} to {}", from, to),
+            EngineError::ConversionTooSmall { amount, rate } => write!(
+                f,
+                "Converting {} at rate {} credits less than 0.0001",
+                amount, rate
+            ),
+            EngineError::NotDisputable { tx_id, kind, operation } => {
+                write!(f, "Transaction with ID {} has type {} and cannot be the target of a {}", tx_id, kind, operation)
+            }
+            EngineError::PartialConversionDispute(tx_id) => {
+                write!(f, "Conversion with ID {} can only be disputed as a whole", tx_id)
+            }
+            EngineError::AlreadyDisputed(tx_id) => {
+                write!(f, "Transaction with ID {} has already been disputed in full", tx_id)
+            }
+            EngineError::DisputeExceedsRemaining { tx_id, amount, remaining } => write!(
+                f,
+                "Dispute amount {} exceeds the {} of transaction {} not yet disputed",
+                amount, remaining, tx_id
+            ),
+            EngineError::NotDisputed { tx_id, operation } => {
+                write!(f, "Transaction with ID {} has no disputed funds to {}", tx_id, operation)
+            }
+            EngineError::ExceedsDisputedAmount { tx_id, amount, held, operation } => write!(
+                f,
+                "{:?} amount {} exceeds the {} held for transaction {}",
+                operation, amount, held, tx_id
+            ),
+            EngineError::NotAnAuthorization { tx_id, operation } => {
+                write!(f, "Transaction with ID {} is not an authorization and cannot be the target of a {}", tx_id, operation)
+            }
+            EngineError::AuthorizationClosed { tx_id, state, operation } => {
+                write!(f, "Authorization with ID {} is {:?} and cannot be the target of a {}", tx_id, state, operation)
+            }
+            EngineError::CaptureExceedsAuthorization { tx_id, amount, authorized } => write!(
+                f,
+                "Capture amount {} exceeds the {} authorized by transaction {}",
+                amount, authorized, tx_id
+            ),
+            EngineError::NotRefundable { tx_id, kind } => {
+                write!(f, "Transaction with ID {} has type {} and cannot be refunded", tx_id, kind)
+            }
+            EngineError::RefundExceedsRemaining { tx_id, amount, remaining } => write!(
+                f,
+                "Refund amount {} exceeds the {} of transaction {} neither refunded, held nor charged back",
+                amount, remaining, tx_id
+            ),
+            EngineError::LimitExceeded { client_id, breach, locked } => write!(
+                f,
+                "Limit exceeded for client {}: {}{}",
+                client_id,
+                breach,
+                if *locked { ", account locked" } else { "" }
+            ),
+            EngineError::OutOfOrder { tx_id, timestamp, latest } => write!(
+                f,
+                "Transaction {} at {} is too far behind the latest timestamp {}",
+                tx_id,
+                timestamp.to_rfc3339(),
+                latest.to_rfc3339()
+            ),
+            EngineError::DisputeWindowClosed { tx_id, occurred, disputed } => write!(
+                f,
+                "Transaction {} at {} can no longer be disputed at {}",
+                tx_id,
+                occurred.to_rfc3339(),
+                disputed.to_rfc3339()
+            ),
+            EngineError::DuplicateTransaction { tx_id, existing } => {
+                write!(f, "Transaction with ID {} already exists with type {}", tx_id, existing)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<ValidationError> for EngineError {
+    fn from(e: ValidationError) -> Self {
+        EngineError::Invalid(e)
+    }
+}
+
+/// A transaction the engine accepted, kept so later rows can reference it.
+#[derive(Debug)]
+pub struct StoredTransaction {
+    pub transaction: Transaction,
+    /// Amount a conversion credited in its target currency.
+    pub credited: Option<f64>,
+    /// Sum of every dispute raised against the transaction; never exceeds its amount.
+    pub disputed: f64,
+    /// Part of the disputed amount still held, awaiting a resolve or chargeback.
+    pub held: f64,
+    /// Part of the disputed amount charged back.
+    pub charged_back: f64,
+    /// Sum of every refund given back from the transaction.
+    pub refunded: f64,
+    /// Where an `authorize` transaction is in its lifecycle.
+    pub authorization: Option<AuthorizationState>,
+}
+
+impl StoredTransaction {
+    fn new(transaction: Transaction) -> Self {
+        Self {
+            transaction,
+            credited: None,
+            disputed: 0.0,
+            held: 0.0,
+            charged_back: 0.0,
+            refunded: 0.0,
+            authorization: None,
+        }
+    }
+}
+
+/// Lifecycle of an authorization hold.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuthorizationState {
+    /// Funds are held, awaiting a capture or void.
+    Outstanding,
+    Captured,
+    Voided,
+    /// Released by the engine once `authorization_expiry` rows went by.
+    Expired,
+}
+
+/// A fee the engine charged a client and credited to the house account.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeLine {
+    /// Transaction the fee was charged on.
+    pub tx_id: u32,
+    pub client_id: u16,
+    pub tx_type: TransactionType,
+    pub currency: Option<Currency>,
+    pub amount: f64,
+    /// Client credited with the fee; `None` while the house account is locked and the
+    /// fee stays in the fees book.
+    pub house_account: Option<u16>,
+}
+
+/// Clients whose balances and transactions whose state changed, for persisting the
+/// engine incrementally.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Changes {
+    pub clients: HashSet<u16>,
+    pub transactions: HashSet<u32>,
+}
+
+/// The balance a dispute, resolve or chargeback acts on.
+struct DisputedFunds {
+    client_id: u16,
+    currency: Option<Currency>,
+    amount: f64,
+    disputed: f64,
+    held: f64,
+    charged_back: f64,
+    refunded: f64,
+}
+
+/// A client's rows so far, and its accepted deposits and withdrawals that may still fall
+/// within the limit window, oldest first.
+#[derive(Default)]
+struct Activity {
+    rows: u64,
+    recent: VecDeque<RecentTransaction>,
+}
+
+struct RecentTransaction {
+    /// The client's row it was accepted on.
+    row: u64,
+    timestamp: Option<Timestamp>,
+    tx_type: TransactionType,
+    amount: f64,
+}
 
 pub struct TransactionEngine {
+    /// Balances in the default (unlabelled) currency.
     pub accounts: HashMap<u16, ClientAccount>,
-    pub transactions: HashMap<u32, Transaction>,
+    /// Balances in explicitly named currencies, per client.
+    pub currency_accounts: HashMap<u16, BTreeMap<Currency, ClientAccount>>,
+    pub transactions: HashMap<u32, StoredTransaction>,
+    pub validation: ValidationPolicy,
+    /// Exchange rates for `convert` transactions.
+    pub rates: RateTable,
+    /// Fees charged on withdrawals, transfers, conversions and chargebacks.
+    pub fees: FeeSchedule,
+    /// Every fee charged since `keep_fee_lines`, in order; `None` until it is called.
+    fee_lines: Option<Vec<FeeLine>>,
+    /// Withdrawal and velocity limits.
+    pub limits: LimitPolicy,
+    /// Agreed overdrafts withdrawals and disputes may draw on.
+    pub credit: CreditLimits,
+    /// What the velocity limits look at, per client.
+    activity: HashMap<u16, Activity>,
+    /// Number of rows after which an outstanding authorization is released; `None` never expires.
+    pub authorization_expiry: Option<u64>,
+    /// Rules on transaction timestamps.
+    pub time: TimePolicy,
+    /// Latest timestamp seen so far.
+    latest_timestamp: Option<Timestamp>,
+    /// Rows handled so far, the clock authorizations expire by.
+    rows_handled: u64,
+    /// Authorizations by the row after which they expire, oldest first.
+    expiring_authorizations: VecDeque<(u64, u32)>,
+    /// What changed since the last `take_changes`; `None` until tracking is turned on.
+    changes: Option<Changes>,
+    /// Every balance change since the journal was started; `None` until it is.
+    journal: Option<Journal>,
+    /// Double-entry postings behind every balance change.
+    ledger: Ledger,
+}
+
+impl Default for TransactionEngine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TransactionEngine {
     pub fn new() -> Self {
         Self {
             accounts: HashMap::new(),
+            currency_accounts: HashMap::new(),
             transactions: HashMap::new(),
+            validation: ValidationPolicy::default(),
+            rates: RateTable::new(),
+            fees: FeeSchedule::new(),
+            fee_lines: None,
+            limits: LimitPolicy::new(),
+            credit: CreditLimits::new(),
+            activity: HashMap::new(),
+            authorization_expiry: None,
+            time: TimePolicy::default(),
+            latest_timestamp: None,
+            rows_handled: 0,
+            expiring_authorizations: VecDeque::new(),
+            changes: None,
+            journal: None,
+            ledger: Ledger::new(),
         }
     }
 
-    pub fn handle_transaction(&mut self, transaction: Transaction) -> Result<(), String> {
-        // Skip processing if the account is locked
-        if let Some(account) = self.accounts.get_mut(&transaction.client_id) {
-            if account.locked {
-                return Err("Account is locked".into());
+    pub fn handle_transaction(&mut self, transaction: Transaction) -> Result<(), EngineError> {
+        // Every row moves the row clock authorizations expire by, accepted or not
+        self.rows_handled += 1;
+        self.expire_authorizations();
+
+        // All field validation lives here, so a row is classified the same way
+        // whether it comes from a file or is handed to the engine directly
+        transaction.validate(&self.validation)?;
+
+        // Velocity limits count the client's own rows, accepted or not
+        if self.limits.is_active() {
+            self.activity.entry(transaction.client_id).or_default().rows += 1;
+        }
+
+        // Timestamps may only go back as far as the tolerance allows
+        let timestamp = transaction.timestamp;
+        if let (Some(timestamp), Some(latest)) = (timestamp, self.latest_timestamp) {
+            let earliest = latest.checked_sub_signed(self.time.tolerance).unwrap_or(Timestamp::MIN_UTC);
+            if timestamp < earliest {
+                return Err(EngineError::OutOfOrder { tx_id: transaction.tx_id, timestamp, latest });
             }
         }
 
-        match transaction.tx_type {
+        // Skip processing if the account, or the account a transfer credits, is locked
+        for client_id in std::iter::once(transaction.client_id).chain(transaction.to_client) {
+            if self.is_locked(client_id) {
+                return Err(EngineError::AccountLocked(client_id));
+            }
+        }
+
+        // An id may only be introduced once; a second row would replace the first
+        if transaction.tx_type.introduces_id() {
+            if let Some(stored) = self.transactions.get(&transaction.tx_id) {
+                let existing = stored.transaction.tx_type;
+                return Err(EngineError::DuplicateTransaction { tx_id: transaction.tx_id, existing });
+            }
+        }
+
+        // Everything a row can change belongs to the clients and transactions it names
+        let client_ids = [Some(transaction.client_id), transaction.to_client, self.fees.house_account];
+        let tx_ids = [Some(transaction.tx_id), transaction.ref_tx];
+
+        let result = match transaction.tx_type {
             TransactionType::Deposit => self.handle_deposit(transaction),
             TransactionType::Withdrawal => self.handle_withdrawal(transaction),
             TransactionType::Dispute => self.handle_dispute(transaction),
             TransactionType::Resolve => self.handle_resolve(transaction),
             TransactionType::Chargeback => self.handle_chargeback(transaction),
+            TransactionType::Convert => self.handle_convert(transaction),
+            TransactionType::Transfer => self.handle_transfer(transaction),
+            TransactionType::Authorize => self.handle_authorize(transaction),
+            TransactionType::Capture => self.handle_capture(transaction),
+            TransactionType::Void => self.handle_void(transaction),
+            TransactionType::Refund => self.handle_refund(transaction),
+        };
+
+        if let Some(changes) = self.changes.as_mut() {
+            changes.clients.extend(client_ids.into_iter().flatten());
+            changes.transactions.extend(tx_ids.into_iter().flatten());
         }
+
+        // Only an accepted row moves the time clock; a refused one leaves no trace
+        if let (Ok(()), Some(timestamp)) = (&result, timestamp) {
+            if self.latest_timestamp.is_none_or(|latest| timestamp > latest) {
+                self.latest_timestamp = Some(timestamp);
+            }
+        }
+
+        result
+    }
+
+    /// Starts recording which clients and transactions change.
+    pub fn track_changes(&mut self) {
+        self.changes.get_or_insert_with(Changes::default);
+    }
+
+    /// What changed since tracking started or the previous call.
+    pub fn take_changes(&mut self) -> Changes {
+        self.changes.as_mut().map(std::mem::take).unwrap_or_default()
     }
 
-    fn handle_deposit(&mut self, transaction: Transaction) -> Result<(), String> {
-        // Ensure the transaction has a valid amount
-        let amount = transaction.amount.ok_or("Deposit transaction must have an amount")?;
+    /// Starts recording every balance change as an event.
+    pub fn keep_journal(&mut self) {
+        self.journal.get_or_insert_with(Journal::new);
+    }
 
-        if amount <= 0.0 {
-            return Err("Deposit amount must be positive".into());
+    /// The events recorded since `keep_journal`, if it was called.
+    pub fn journal(&self) -> Option<&Journal> {
+        self.journal.as_ref()
+    }
+
+    /// Starts recording every fee charged.
+    pub fn keep_fee_lines(&mut self) {
+        self.fee_lines.get_or_insert_with(Vec::new);
+    }
+
+    /// The fees charged since `keep_fee_lines`; empty if it was not called.
+    pub fn fee_lines(&self) -> &[FeeLine] {
+        self.fee_lines.as_deref().unwrap_or_default()
+    }
+
+    /// The double-entry ledger behind the balances.
+    pub fn ledger(&self) -> &Ledger {
+        &self.ledger
+    }
+
+    /// Puts back a balance kept by an earlier run. It is posted against the opening
+    /// balances, so the ledger keeps netting to zero.
+    pub fn restore_account(&mut self, currency: Option<&str>, account: ClientAccount) {
+        let client_id = account.client_id;
+        let opening = LedgerAccount::system(SystemBook::OpeningBalances, currency);
+        let places = self.validation.max_decimal_places();
+        let available = Posting::new(LedgerAccount::available(client_id, currency), opening.clone(), account.available);
+        let held = Posting::new(LedgerAccount::held(client_id, currency), opening, account.held);
+        self.ledger.post(&available, places);
+        self.ledger.post(&held, places);
+
+        match currency {
+            None => {
+                self.accounts.insert(client_id, account);
+            }
+            Some(currency) => {
+                self.currency_accounts.entry(client_id).or_default().insert(currency.to_string(), account);
+            }
         }
+    }
 
-        // Get or create the client's account
-        let account = self.accounts.entry(transaction.client_id).or_insert_with(|| ClientAccount {
-            client_id: transaction.client_id,
-            available: 0.0,
-            held: 0.0,
-            total: 0.0,
-            locked: false,
-        });
+    /// Puts back a transaction accepted by an earlier run. An outstanding authorization
+    /// starts its expiry anew.
+    pub fn restore_transaction(&mut self, stored: StoredTransaction) {
+        if let (Some(expiry), Some(AuthorizationState::Outstanding)) = (self.authorization_expiry, stored.authorization) {
+            self.expiring_authorizations.push_back((self.rows_handled + expiry, stored.transaction.tx_id));
+        }
+        self.transactions.insert(stored.transaction.tx_id, stored);
+    }
+
+    /// Latest timestamp of the rows handled so far, if any carried one.
+    pub fn latest_timestamp(&self) -> Option<Timestamp> {
+        self.latest_timestamp
+    }
 
-        // Ensure the account is not locked
-        if account.locked {
-            return Err(format!(
-                "Cannot deposit into a locked account (Client ID: {})",
-                transaction.client_id
-            ));
+    /// Authorizations still holding funds, ordered by transaction ID.
+    pub fn outstanding_authorizations(&self) -> Vec<&Transaction> {
+        let mut outstanding: Vec<&Transaction> = self
+            .transactions
+            .values()
+            .filter(|stored| stored.authorization == Some(AuthorizationState::Outstanding))
+            .map(|stored| &stored.transaction)
+            .collect();
+        outstanding.sort_by_key(|tx| tx.tx_id);
+        outstanding
+    }
+
+    /// Balances of a client in a currency; `None` is the default currency.
+    pub fn account(&self, client_id: u16, currency: Option<&str>) -> Option<&ClientAccount> {
+        match currency {
+            None => self.accounts.get(&client_id),
+            Some(currency) => self.currency_accounts.get(&client_id)?.get(currency),
         }
+    }
 
-        // Update account balances
-        account.available += amount;
-        account.total += amount;
+    /// Every balance held by every client, with its currency.
+    pub fn balances(&self) -> impl Iterator<Item = (Option<&str>, &ClientAccount)> {
+        let default = self.accounts.values().map(|account| (None, account));
+        let labelled = self
+            .currency_accounts
+            .values()
+            .flat_map(|wallets| wallets.iter().map(|(currency, account)| (Some(currency.as_str()), account)));
+        default.chain(labelled)
+    }
+
+    /// Whether any transaction so far named a currency.
+    pub fn is_multi_currency(&self) -> bool {
+        !self.currency_accounts.is_empty()
+    }
+
+    /// A client is locked as soon as any of their balances is.
+    pub fn is_locked(&self, client_id: u16) -> bool {
+        self.accounts.get(&client_id).is_some_and(|a| a.locked)
+            || self
+                .currency_accounts
+                .get(&client_id)
+                .is_some_and(|wallets| wallets.values().any(|a| a.locked))
+    }
+
+    /// Moves `amount` from the `credit` account to the `debit` account for the transaction
+    /// `tx_id`. Client balances follow from the posting, opening them if needed.
+    fn post(&mut self, tx_id: u32, debit: LedgerAccount, credit: LedgerAccount, amount: f64) {
+        let posting = Posting::new(debit, credit, amount);
+        self.ledger.post(&posting, self.validation.max_decimal_places());
+        for event in posting.events(tx_id) {
+            self.apply(event);
+        }
+    }
+
+    /// Locks every balance of a client, opening the one in `currency` if needed.
+    fn lock(&mut self, client_id: u16, currency: Option<&str>, tx_id: u32) {
+        self.apply(Event::new(EventKind::AccountLocked, client_id, currency, tx_id, 0.0));
+    }
+
+    /// Changes a balance and records the change in the journal.
+    fn apply(&mut self, event: Event) {
+        let places = self.validation.max_decimal_places();
+        journal::apply(&mut self.accounts, &mut self.currency_accounts, &event, places);
+        if let Some(journal) = self.journal.as_mut() {
+            journal.events.push(event);
+        }
+    }
+
+    fn account_mut(
+        &mut self,
+        client_id: u16,
+        currency: Option<&str>,
+        operation: TransactionType,
+    ) -> Result<&mut ClientAccount, EngineError> {
+        let account = match currency {
+            None => self.accounts.get_mut(&client_id),
+            Some(currency) => self
+                .currency_accounts
+                .get_mut(&client_id)
+                .and_then(|wallets| wallets.get_mut(currency)),
+        };
+
+        account.ok_or_else(|| EngineError::AccountNotFound {
+            client_id,
+            currency: currency.map(str::to_string),
+            operation,
+        })
+    }
+
+    fn handle_deposit(&mut self, transaction: Transaction) -> Result<(), EngineError> {
+        // Validation guarantees the amount
+        let amount = transaction.amount.ok_or(ValidationError::MissingAmount(transaction.tx_type))?;
+        self.check_limits(&transaction, amount)?;
+
+        // Fund the client's account in the deposit's currency, opening it if needed
+        let currency = transaction.currency.as_deref();
+        self.post(
+            transaction.tx_id,
+            LedgerAccount::available(transaction.client_id, currency),
+            LedgerAccount::system(SystemBook::ExternalFunding, currency),
+            amount,
+        );
+        self.record_activity(&transaction, amount);
 
         // Record the transaction
-        self.transactions.insert(transaction.tx_id, transaction);
+        self.transactions.insert(transaction.tx_id, StoredTransaction::new(transaction));
 
         Ok(())
     }
 
-    fn handle_withdrawal(&mut self, transaction: Transaction) -> Result<(), String> {
-        // Ensure the transaction has a valid amount
-        let amount = transaction.amount.ok_or("Withdrawal transaction must have an amount")?;
+    fn handle_withdrawal(&mut self, transaction: Transaction) -> Result<(), EngineError> {
+        // Validation guarantees the amount
+        let amount = transaction.amount.ok_or(ValidationError::MissingAmount(transaction.tx_type))?;
+        self.check_limits(&transaction, amount)?;
+        let fee = self.fee(&transaction, amount);
+        let credit_limit = self.credit.limit(transaction.client_id, transaction.currency.as_deref());
 
-        if amount <= 0.0 {
-            return Err("Withdrawal amount must be positive".into());
+        // Fetch the client's available funds in the withdrawal's currency; on a credit line
+        // a missing balance starts at zero and the posting opens it, as a deposit's does
+        let available = match self.account_mut(transaction.client_id, transaction.currency.as_deref(), transaction.tx_type) {
+            Ok(account) => account.available,
+            Err(_) if credit_limit > 0.0 => 0.0,
+            Err(e) => return Err(e),
+        };
+
+        // Ensure sufficient available funds, fee included, drawing on any overdraft
+        if available + credit_limit < amount + fee {
+            return Err(EngineError::InsufficientFunds {
+                available: available + credit_limit,
+                amount: amount + fee,
+                operation: transaction.tx_type,
+            });
         }
 
-        // Fetch the client's account
-        let account = self.accounts.get_mut(&transaction.client_id).ok_or_else(|| {
-            format!(
-                "Account for Client ID {} not found. Cannot process withdrawal.",
-                transaction.client_id
-            )
+        // Pay the amount out of the engine
+        let currency = transaction.currency.as_deref();
+        self.post(
+            transaction.tx_id,
+            LedgerAccount::system(SystemBook::ExternalFunding, currency),
+            LedgerAccount::available(transaction.client_id, currency),
+            amount,
+        );
+        self.charge_fee(&transaction, transaction.currency.as_deref(), fee);
+        self.record_activity(&transaction, amount);
+
+        // Record the transaction
+        self.transactions.insert(transaction.tx_id, StoredTransaction::new(transaction));
+
+        Ok(())
+    }
+
+    fn handle_convert(&mut self, transaction: Transaction) -> Result<(), EngineError> {
+        // Validation guarantees the amount and both currencies
+        let amount = transaction.amount.ok_or(ValidationError::MissingAmount(transaction.tx_type))?;
+        let from = transaction.currency.clone().ok_or(ValidationError::MissingCurrency(transaction.tx_type))?;
+        let to = transaction.to_currency.clone().ok_or(ValidationError::MissingTargetCurrency)?;
+
+        // Price the conversion before touching any balance
+        let rate = self.rates.rate(&from, &to).ok_or_else(|| EngineError::NoRate {
+            from: from.clone(),
+            to: to.clone(),
         })?;
+        let credited = convert_amount(amount, rate);
+        if amount > 0.0 && credited == 0.0 {
+            return Err(EngineError::ConversionTooSmall { amount, rate });
+        }
+
+        // The fee is charged in the source currency
+        let fee = self.fee(&transaction, amount);
 
-        // Ensure the account is not locked
-        if account.locked {
-            return Err(format!(
-                "Cannot withdraw from a locked account (Client ID: {})",
-                transaction.client_id
-            ));
+        // Fetch the client's account in the source currency
+        let account = self.account_mut(transaction.client_id, Some(&from), transaction.tx_type)?;
+
+        // Ensure sufficient available funds, fee included
+        if account.available < amount + fee {
+            return Err(EngineError::InsufficientFunds {
+                available: account.available,
+                amount: amount + fee,
+                operation: transaction.tx_type,
+            });
         }
 
-        // Ensure sufficient available funds
-        if account.available < amount {
-            return Err(format!(
-                "Insufficient funds: Available = {}, Withdrawal amount = {}",
-                account.available, amount
-            ));
+        // Debit the source currency and credit the target, opening it if needed
+        let (client_id, tx_id) = (transaction.client_id, transaction.tx_id);
+        self.post(
+            tx_id,
+            LedgerAccount::system(SystemBook::Exchange, Some(&from)),
+            LedgerAccount::available(client_id, Some(&from)),
+            amount,
+        );
+        self.charge_fee(&transaction, Some(&from), fee);
+        self.post(
+            tx_id,
+            LedgerAccount::available(client_id, Some(&to)),
+            LedgerAccount::system(SystemBook::Exchange, Some(&to)),
+            credited,
+        );
+
+        // Record the transaction with what it credited, so it can be disputed as a unit
+        self.transactions.insert(
+            transaction.tx_id,
+            StoredTransaction { credited: Some(credited), ..StoredTransaction::new(transaction) },
+        );
+
+        Ok(())
+    }
+
+    fn handle_transfer(&mut self, transaction: Transaction) -> Result<(), EngineError> {
+        // Validation guarantees the amount and the destination
+        let amount = transaction.amount.ok_or(ValidationError::MissingAmount(transaction.tx_type))?;
+        let to_client = transaction.to_client.ok_or(ValidationError::MissingDestinationClient)?;
+        let fee = self.fee(&transaction, amount);
+        let currency = transaction.currency.as_deref();
+
+        // Fetch the source account in the transfer's currency
+        let source = self.account_mut(transaction.client_id, currency, transaction.tx_type)?;
+
+        // Ensure sufficient available funds, fee included, before either side changes
+        if source.available < amount + fee {
+            return Err(EngineError::InsufficientFunds {
+                available: source.available,
+                amount: amount + fee,
+                operation: transaction.tx_type,
+            });
         }
 
-        // Update account balances
-        account.available -= amount;
-        account.total -= amount;
+        // Debit the source and credit the destination, opening its account if needed
+        self.post(
+            transaction.tx_id,
+            LedgerAccount::available(to_client, currency),
+            LedgerAccount::available(transaction.client_id, currency),
+            amount,
+        );
+        self.charge_fee(&transaction, currency, fee);
 
         // Record the transaction
-        self.transactions.insert(transaction.tx_id, transaction);
+        self.transactions.insert(transaction.tx_id, StoredTransaction::new(transaction));
 
-        Ok(())  
+        Ok(())
     }
 
-    fn handle_dispute(&mut self, transaction: Transaction) -> Result<(), String> {
-        // Fetch the referenced transaction
-        let tx = self.transactions.get(&transaction.tx_id)
-            .ok_or_else(|| format!("Transaction with ID {} not found for dispute", transaction.tx_id))?;
+    fn handle_refund(&mut self, transaction: Transaction) -> Result<(), EngineError> {
+        // Validation guarantees the amount and the reference
+        let amount = transaction.amount.ok_or(ValidationError::MissingAmount(transaction.tx_type))?;
+        let ref_tx = transaction.ref_tx.ok_or(ValidationError::MissingReferencedTransaction)?;
+        let operation = transaction.tx_type;
+
+        // Fetch the refunded transaction; only deposits bring money in that can be given back
+        let stored = self.transactions.get(&ref_tx).ok_or(EngineError::TransactionNotFound { tx_id: ref_tx, operation })?;
+        let original = &stored.transaction;
+        if original.tx_type != TransactionType::Deposit {
+            return Err(EngineError::NotRefundable { tx_id: ref_tx, kind: original.tx_type });
+        }
 
         // Validate client ID
-        if tx.client_id != transaction.client_id {
-            return Err(format!(
-                "Dispute client ID mismatch: expected {}, got {}",
-                tx.client_id, transaction.client_id
-            ));
+        if original.client_id != transaction.client_id {
+            return Err(EngineError::ClientMismatch {
+                expected: original.client_id,
+                got: transaction.client_id,
+                operation,
+            });
         }
 
-        // Fetch the client's account
-        let account = self.accounts.get_mut(&tx.client_id)
-            .ok_or_else(|| format!("Account for client ID {} not found", tx.client_id))?;
+        // The deposit decides the currency; a row may only repeat it
+        if let Some(got) = &transaction.currency {
+            if original.currency.as_ref() != Some(got) {
+                return Err(EngineError::CurrencyMismatch {
+                    tx_id: ref_tx,
+                    expected: original.currency.clone(),
+                    got: got.clone(),
+                });
+            }
+        }
 
-        // Ensure the transaction has an amount
-        let amount = tx.amount.ok_or_else(|| format!(
-            "Transaction with ID {} does not have an associated amount", transaction.tx_id
-        ))?;
+        // Refunds and disputes share the deposit: neither may take money the other holds or
+        // charged back, while resolved money is the client's to have refunded again
+        let deposited = original.amount.ok_or(EngineError::MissingAmount(ref_tx))?;
+        let remaining = self.round(deposited - stored.refunded - stored.held - stored.charged_back);
+        if amount > remaining {
+            return Err(EngineError::RefundExceedsRemaining { tx_id: ref_tx, amount, remaining });
+        }
+        let refunded = self.round(stored.refunded + amount);
+        let (client_id, currency) = (original.client_id, original.currency.clone());
 
-        // Update account balances
+        // Fetch the client's account in the deposit's currency
+        let account = self.account_mut(client_id, currency.as_deref(), operation)?;
+
+        // Ensure sufficient available funds
         if account.available < amount {
-            return Err(format!(
-                "Insufficient funds: Available = {}, Dispute amount = {}",
-                account.available, amount
-            ));
+            return Err(EngineError::InsufficientFunds {
+                available: account.available,
+                amount,
+                operation,
+            });
         }
 
-        account.available -= amount;
-        account.held += amount;
+        // Pay the amount back out of the engine
+        self.post(
+            transaction.tx_id,
+            LedgerAccount::system(SystemBook::ExternalFunding, currency.as_deref()),
+            LedgerAccount::available(client_id, currency.as_deref()),
+            amount,
+        );
+
+        // Track the refund against the deposit and record the refund itself
+        if let Some(stored) = self.transactions.get_mut(&ref_tx) {
+            stored.refunded = refunded;
+        }
+        self.transactions.insert(transaction.tx_id, StoredTransaction::new(transaction));
 
         Ok(())
     }
 
-    fn handle_resolve(&mut self, transaction: Transaction) -> Result<(), String> {
+    /// Refuses a deposit or withdrawal that would breach the client's limits, locking the
+    /// client first when the policy says so.
+    fn check_limits(&mut self, transaction: &Transaction, amount: f64) -> Result<(), EngineError> {
+        if !self.limits.is_active() {
+            return Ok(());
+        }
+
+        let client_id = transaction.client_id;
+        let limits = self.limits.limits(client_id);
+
+        // A row with a timestamp looks back in time when a period is set, any other row
+        // looks back over the client's own rows
+        let window = match (self.limits.period, transaction.timestamp) {
+            (Some(period), Some(_)) => LimitWindow::Period(period),
+            _ => LimitWindow::Rows(self.limits.window),
+        };
+        let activity = self.activity.get(&client_id);
+        let rows = activity.map_or(0, |activity| activity.rows);
+        let since = rows.saturating_sub(self.limits.window);
+        let recent = |tx_type: TransactionType| {
+            activity
+                .into_iter()
+                .flat_map(|activity| &activity.recent)
+                .filter(move |recent| recent.tx_type == tx_type)
+                .filter(move |recent| match (window, transaction.timestamp) {
+                    (LimitWindow::Period(period), Some(now)) => recent.timestamp.is_some_and(|then| now - then < period),
+                    _ => recent.row > since,
+                })
+        };
+
+        let breach = match transaction.tx_type {
+            TransactionType::Withdrawal => {
+                let withdrawals = recent(TransactionType::Withdrawal).count();
+                if let Some(max) = limits.max_withdrawal.filter(|max| amount > *max) {
+                    Some(LimitBreach::SingleWithdrawal { amount, max })
+                } else {
+                    limits
+                        .max_withdrawals
+                        .filter(|max| withdrawals >= *max as usize)
+                        .map(|max| LimitBreach::WithdrawalCount { max, window })
+                }
+            }
+            TransactionType::Deposit => {
+                let volume: f64 = recent(TransactionType::Deposit).map(|recent| recent.amount).sum::<f64>() + amount;
+                limits
+                    .max_deposit_volume
+                    .filter(|max| volume > *max)
+                    .map(|max| LimitBreach::DepositVolume { volume, max, window })
+            }
+            _ => None,
+        };
+
+        let Some(breach) = breach else {
+            return Ok(());
+        };
+
+        // Lock the client the same way a chargeback does, but never open an account to do so
+        let currency = transaction.currency.as_deref();
+        let locked = self.limits.on_breach == BreachAction::Lock && self.account(client_id, currency).is_some();
+        if locked {
+            self.lock(client_id, currency, transaction.tx_id);
+        }
+
+        Err(EngineError::LimitExceeded { client_id, breach, locked })
+    }
+
+    /// Remembers an accepted deposit or withdrawal for the velocity limits.
+    fn record_activity(&mut self, transaction: &Transaction, amount: f64) {
+        if !self.limits.is_active() {
+            return;
+        }
+
+        // Forget what neither the row window nor the period can reach any more
+        let (window, period, latest) = (self.limits.window, self.limits.period, self.latest_timestamp);
+        let activity = self.activity.entry(transaction.client_id).or_default();
+        let since = activity.rows.saturating_sub(window);
+        let expired = |recent: &RecentTransaction| {
+            recent.row <= since
+                && period.is_none_or(|period| match (recent.timestamp, latest) {
+                    (Some(then), Some(latest)) => latest - then >= period,
+                    _ => true,
+                })
+        };
+        while activity.recent.front().is_some_and(expired) {
+            activity.recent.pop_front();
+        }
+        activity.recent.push_back(RecentTransaction {
+            row: activity.rows,
+            timestamp: transaction.timestamp,
+            tx_type: transaction.tx_type,
+            amount,
+        });
+    }
+
+    /// Fee due on a transaction of `amount`, rounded like amounts.
+    fn fee(&self, transaction: &Transaction, amount: f64) -> f64 {
+        self.round(self.fees.fee(transaction.client_id, transaction.tx_type, amount))
+    }
+
+    /// Moves a fee from the client to the house account in the same currency and records it.
+    /// A locked house account takes no more money, so the fee then stays in the fees book.
+    fn charge_fee(&mut self, transaction: &Transaction, currency: Option<&str>, fee: f64) {
+        let Some(house_account) = self.fees.house_account else {
+            return;
+        };
+        if fee <= 0.0 {
+            return;
+        }
+
+        let fees = LedgerAccount::system(SystemBook::Fees, currency);
+        self.post(transaction.tx_id, fees.clone(), LedgerAccount::available(transaction.client_id, currency), fee);
+        let credited = (!self.is_locked(house_account)).then_some(house_account);
+        if let Some(house_account) = credited {
+            self.post(transaction.tx_id, LedgerAccount::available(house_account, currency), fees, fee);
+        }
+
+        if let Some(fee_lines) = self.fee_lines.as_mut() {
+            fee_lines.push(FeeLine {
+                tx_id: transaction.tx_id,
+                client_id: transaction.client_id,
+                tx_type: transaction.tx_type,
+                currency: currency.map(str::to_string),
+                amount: fee,
+                house_account: credited,
+            });
+        }
+    }
+
+    /// Looks up the transaction a dispute, resolve or chargeback refers to and returns
+    /// the funds it acts on. For a conversion these are the credited funds.
+    fn referenced_transaction(&self, transaction: &Transaction) -> Result<DisputedFunds, EngineError> {
+        let operation = transaction.tx_type;
+
         // Fetch the referenced transaction
-        let tx = self.transactions.get(&transaction.tx_id)
-            .ok_or_else(|| format!("Transaction with ID {} not found for resolve", transaction.tx_id))?;
+        let stored = self.transactions.get(&transaction.tx_id).ok_or(EngineError::TransactionNotFound {
+            tx_id: transaction.tx_id,
+            operation,
+        })?;
+        let tx = &stored.transaction;
+
+        // Transfers move money between clients of the engine, authorizations are settled by
+        // capture or void and refunds already give money back: there is no payment to dispute
+        if matches!(
+            tx.tx_type,
+            TransactionType::Transfer | TransactionType::Authorize | TransactionType::Refund
+        ) {
+            return Err(EngineError::NotDisputable { tx_id: transaction.tx_id, kind: tx.tx_type, operation });
+        }
+
+        // A conversion is disputed as a unit
+        if tx.tx_type == TransactionType::Convert && transaction.amount.is_some() {
+            return Err(EngineError::PartialConversionDispute(transaction.tx_id));
+        }
+
+        let currency = match tx.tx_type {
+            TransactionType::Convert => tx.to_currency.clone(),
+            _ => tx.currency.clone(),
+        };
 
         // Validate client ID
         if tx.client_id != transaction.client_id {
-            return Err(format!(
-                "Resolve client ID mismatch: expected {}, got {}",
-                tx.client_id, transaction.client_id
-            ));
+            return Err(EngineError::ClientMismatch {
+                expected: tx.client_id,
+                got: transaction.client_id,
+                operation,
+            });
         }
 
-        // Fetch the client's account
-        let account = self.accounts.get_mut(&tx.client_id)
-            .ok_or_else(|| format!("Account for client ID {} not found", tx.client_id))?;
+        // The referenced transaction decides the currency; a row may only repeat it
+        if let Some(got) = &transaction.currency {
+            if currency.as_ref() != Some(got) {
+                return Err(EngineError::CurrencyMismatch {
+                    tx_id: transaction.tx_id,
+                    expected: currency,
+                    got: got.clone(),
+                });
+            }
+        }
 
         // Ensure the transaction has an amount
-        let amount = tx.amount.ok_or_else(|| format!(
-            "Transaction with ID {} does not have an associated amount", transaction.tx_id
-        ))?;
+        let amount = stored
+            .credited
+            .or(tx.amount)
+            .ok_or(EngineError::MissingAmount(transaction.tx_id))?;
+
+        Ok(DisputedFunds {
+            client_id: tx.client_id,
+            currency,
+            amount,
+            disputed: stored.disputed,
+            held: stored.held,
+            charged_back: stored.charged_back,
+            refunded: stored.refunded,
+        })
+    }
+
+    /// Rounds a running total to the precision amounts are validated with, so partial
+    /// amounts add up exactly.
+    fn round(&self, value: f64) -> f64 {
+        round_to(value, self.validation.max_decimal_places())
+    }
+
+    fn handle_dispute(&mut self, transaction: Transaction) -> Result<(), EngineError> {
+        let funds = self.referenced_transaction(&transaction)?;
+
+        // Ensure the dispute is raised within the window, timed by the latest timestamp when the row has none
+        let occurred = self.transactions.get(&transaction.tx_id).and_then(|stored| stored.transaction.timestamp);
+        if let (Some(window), Some(occurred), Some(disputed)) =
+            (self.time.dispute_window, occurred, transaction.timestamp.or(self.latest_timestamp))
+        {
+            if disputed - occurred > window {
+                return Err(EngineError::DisputeWindowClosed { tx_id: transaction.tx_id, occurred, disputed });
+            }
+        }
+
+        // Hold the requested part, or everything neither disputed nor refunded yet
+        let remaining = self.round(funds.amount - funds.disputed - funds.refunded);
+        if remaining <= 0.0 {
+            return Err(EngineError::AlreadyDisputed(transaction.tx_id));
+        }
+        let amount = transaction.amount.unwrap_or(remaining);
+        if amount > remaining {
+            return Err(EngineError::DisputeExceedsRemaining { tx_id: transaction.tx_id, amount, remaining });
+        }
+        let (disputed, held) = (self.round(funds.disputed + amount), self.round(funds.held + amount));
+        let places = self.validation.max_decimal_places();
+        let credit_limit = self.credit.limit(funds.client_id, funds.currency.as_deref());
+
+        // Fetch the client's account in the referenced transaction's currency
+        let account = self.account_mut(funds.client_id, funds.currency.as_deref(), transaction.tx_type)?;
+
+        // Update account balances, drawing on any overdraft, without letting representation
+        // error refuse a partial amount
+        if round_to(account.available + credit_limit, places) < amount {
+            return Err(EngineError::InsufficientFunds {
+                available: account.available + credit_limit,
+                amount,
+                operation: transaction.tx_type,
+            });
+        }
+
+        let currency = funds.currency.as_deref();
+        self.post(
+            transaction.tx_id,
+            LedgerAccount::held(funds.client_id, currency),
+            LedgerAccount::available(funds.client_id, currency),
+            amount,
+        );
+
+        // Track the dispute against the referenced transaction
+        if let Some(stored) = self.transactions.get_mut(&transaction.tx_id) {
+            stored.disputed = disputed;
+            stored.held = held;
+        }
+
+        Ok(())
+    }
+
+    /// The part of a transaction's held funds a resolve or chargeback acts on: the
+    /// requested amount, or everything still held.
+    fn held_amount(transaction: &Transaction, funds: &DisputedFunds) -> Result<f64, EngineError> {
+        if funds.held <= 0.0 {
+            return Err(EngineError::NotDisputed { tx_id: transaction.tx_id, operation: transaction.tx_type });
+        }
+
+        let amount = transaction.amount.unwrap_or(funds.held);
+        if amount > funds.held {
+            return Err(EngineError::ExceedsDisputedAmount {
+                tx_id: transaction.tx_id,
+                amount,
+                held: funds.held,
+                operation: transaction.tx_type,
+            });
+        }
+
+        Ok(amount)
+    }
+
+    fn handle_resolve(&mut self, transaction: Transaction) -> Result<(), EngineError> {
+        let funds = self.referenced_transaction(&transaction)?;
+        let amount = Self::held_amount(&transaction, &funds)?;
+        let held = self.round(funds.held - amount);
+        let places = self.validation.max_decimal_places();
+
+        // Fetch the client's account in the referenced transaction's currency
+        let account = self.account_mut(funds.client_id, funds.currency.as_deref(), transaction.tx_type)?;
 
         // Ensure sufficient held funds
-        if account.held < amount {
-            return Err(format!(
-                "Insufficient held funds: Held = {}, Resolve amount = {}",
-                account.held, amount
-            ));
+        if round_to(account.held, places) < amount {
+            return Err(EngineError::InsufficientHeldFunds {
+                held: account.held,
+                amount,
+                operation: transaction.tx_type,
+            });
         }
 
-        account.held -= amount;
-        account.available += amount;
+        let currency = funds.currency.as_deref();
+        self.post(
+            transaction.tx_id,
+            LedgerAccount::available(funds.client_id, currency),
+            LedgerAccount::held(funds.client_id, currency),
+            amount,
+        );
+
+        // Released funds are not disputable again
+        if let Some(stored) = self.transactions.get_mut(&transaction.tx_id) {
+            stored.held = held;
+        }
 
         Ok(())
     }
 
-    fn handle_chargeback(&mut self, transaction: Transaction) -> Result<(), String> {
+    fn handle_chargeback(&mut self, transaction: Transaction) -> Result<(), EngineError> {
+        let funds = self.referenced_transaction(&transaction)?;
+        let amount = Self::held_amount(&transaction, &funds)?;
+        let held = self.round(funds.held - amount);
+        let charged_back = self.round(funds.charged_back + amount);
+        let places = self.validation.max_decimal_places();
+
+        // Fetch the client's account in the referenced transaction's currency
+        let account = self.account_mut(funds.client_id, funds.currency.as_deref(), transaction.tx_type)?;
+
+        // Ensure sufficient held funds
+        if round_to(account.held, places) < amount {
+            return Err(EngineError::InsufficientHeldFunds {
+                held: account.held,
+                amount,
+                operation: transaction.tx_type,
+            });
+        }
+
+        // A charged back conversion is reversed as a whole, each currency against the
+        // exchange: the credited amount goes back to it and the debited amount comes back
+        let conversion = self
+            .transactions
+            .get(&transaction.tx_id)
+            .map(|stored| &stored.transaction)
+            .filter(|tx| tx.tx_type == TransactionType::Convert)
+            .map(|tx| (tx.currency.clone(), tx.amount.unwrap_or_default()));
+        let book = match conversion {
+            Some(_) => SystemBook::Exchange,
+            None => SystemBook::ChargebackLoss,
+        };
+
+        // Update account balances
+        let currency = funds.currency.as_deref();
+        self.post(
+            transaction.tx_id,
+            LedgerAccount::system(book, currency),
+            LedgerAccount::held(funds.client_id, currency),
+            amount,
+        );
+        if let Some((from, debited)) = conversion {
+            self.post(
+                transaction.tx_id,
+                LedgerAccount::available(funds.client_id, from.as_deref()),
+                LedgerAccount::system(SystemBook::Exchange, from.as_deref()),
+                debited,
+            );
+        }
+
+        // Track the chargeback against the referenced transaction
+        if let Some(stored) = self.transactions.get_mut(&transaction.tx_id) {
+            stored.held = held;
+            stored.charged_back = charged_back;
+        }
+
+        // The chargeback fee takes what the client has available, never more
+        let available = self.account(funds.client_id, funds.currency.as_deref()).map_or(0.0, |a| a.available);
+        let fee = self.fee(&transaction, amount).min(available.max(0.0));
+        self.charge_fee(&transaction, funds.currency.as_deref(), fee);
+
+        // Lock every balance of the client
+        self.lock(funds.client_id, funds.currency.as_deref(), transaction.tx_id);
+
+        Ok(())
+    }
+
+    fn handle_authorize(&mut self, transaction: Transaction) -> Result<(), EngineError> {
+        // Validation guarantees the amount
+        let amount = transaction.amount.ok_or(ValidationError::MissingAmount(transaction.tx_type))?;
+
+        // Fetch the client's account in the authorization's currency
+        let account = self.account_mut(
+            transaction.client_id,
+            transaction.currency.as_deref(),
+            transaction.tx_type,
+        )?;
+
+        // Ensure sufficient available funds
+        if account.available < amount {
+            return Err(EngineError::InsufficientFunds {
+                available: account.available,
+                amount,
+                operation: transaction.tx_type,
+            });
+        }
+
+        // Reserve the funds until the authorization is captured, voided or expires
+        let currency = transaction.currency.as_deref();
+        self.post(
+            transaction.tx_id,
+            LedgerAccount::held(transaction.client_id, currency),
+            LedgerAccount::available(transaction.client_id, currency),
+            amount,
+        );
+
+        if let Some(expiry) = self.authorization_expiry {
+            self.expiring_authorizations.push_back((self.rows_handled + expiry, transaction.tx_id));
+        }
+
+        // Record the transaction
+        self.transactions.insert(
+            transaction.tx_id,
+            StoredTransaction {
+                authorization: Some(AuthorizationState::Outstanding),
+                ..StoredTransaction::new(transaction)
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Looks up the outstanding authorization a capture or void refers to and returns
+    /// its client, currency and amount.
+    fn referenced_authorization(
+        &self,
+        transaction: &Transaction,
+    ) -> Result<(u16, Option<Currency>, f64), EngineError> {
+        let operation = transaction.tx_type;
+
         // Fetch the referenced transaction
-        let tx = self.transactions.get(&transaction.tx_id)
-            .ok_or_else(|| format!("Transaction with ID {} not found for chargeback", transaction.tx_id))?;
+        let stored = self.transactions.get(&transaction.tx_id).ok_or(EngineError::TransactionNotFound {
+            tx_id: transaction.tx_id,
+            operation,
+        })?;
+        let tx = &stored.transaction;
+
+        // Ensure it is an authorization that still holds funds
+        match stored.authorization {
+            None => return Err(EngineError::NotAnAuthorization { tx_id: transaction.tx_id, operation }),
+            Some(AuthorizationState::Outstanding) => {}
+            Some(state) => {
+                return Err(EngineError::AuthorizationClosed { tx_id: transaction.tx_id, state, operation })
+            }
+        }
 
         // Validate client ID
         if tx.client_id != transaction.client_id {
-            return Err(format!(
-                "Chargeback client ID mismatch: expected {}, got {}",
-                tx.client_id, transaction.client_id
-            ));
+            return Err(EngineError::ClientMismatch {
+                expected: tx.client_id,
+                got: transaction.client_id,
+                operation,
+            });
         }
 
-        // Fetch the client's account
-        let account = self.accounts.get_mut(&tx.client_id)
-            .ok_or_else(|| format!("Account for client ID {} not found", tx.client_id))?;
+        // The authorization decides the currency; a row may only repeat it
+        if let Some(got) = &transaction.currency {
+            if tx.currency.as_ref() != Some(got) {
+                return Err(EngineError::CurrencyMismatch {
+                    tx_id: transaction.tx_id,
+                    expected: tx.currency.clone(),
+                    got: got.clone(),
+                });
+            }
+        }
 
-        // Ensure the transaction has an amount
-        let amount = tx.amount.ok_or_else(|| format!(
-            "Transaction with ID {} does not have an associated amount", transaction.tx_id
-        ))?;
+        let amount = tx.amount.ok_or(EngineError::MissingAmount(transaction.tx_id))?;
 
-        // Ensure sufficient held funds
-        if account.held < amount {
-            return Err(format!(
-                "Insufficient held funds: Held = {}, Chargeback amount = {}",
-                account.held, amount
-            ));
+        Ok((tx.client_id, tx.currency.clone(), amount))
+    }
+
+    fn handle_capture(&mut self, transaction: Transaction) -> Result<(), EngineError> {
+        let (client_id, currency, authorized) = self.referenced_authorization(&transaction)?;
+
+        // Capture the requested part, or the whole authorization
+        let amount = transaction.amount.unwrap_or(authorized);
+        if amount > authorized {
+            return Err(EngineError::CaptureExceedsAuthorization { tx_id: transaction.tx_id, amount, authorized });
         }
 
-        // Update account balances and lock the account
-        account.held -= amount;
-        account.total -= amount;
-        account.locked = true;
+        // Ensure the client's account in the authorization's currency exists
+        self.account_mut(client_id, currency.as_deref(), transaction.tx_type)?;
+
+        // Release the hold and settle the captured amount
+        let currency = currency.as_deref();
+        self.post(
+            transaction.tx_id,
+            LedgerAccount::available(client_id, currency),
+            LedgerAccount::held(client_id, currency),
+            authorized,
+        );
+        self.post(
+            transaction.tx_id,
+            LedgerAccount::system(SystemBook::ExternalFunding, currency),
+            LedgerAccount::available(client_id, currency),
+            amount,
+        );
+
+        self.close_authorization(transaction.tx_id, AuthorizationState::Captured);
+
+        Ok(())
+    }
+
+    fn handle_void(&mut self, transaction: Transaction) -> Result<(), EngineError> {
+        let (client_id, currency, authorized) = self.referenced_authorization(&transaction)?;
+
+        // Ensure the client's account in the authorization's currency exists
+        self.account_mut(client_id, currency.as_deref(), transaction.tx_type)?;
+
+        // Release the held funds
+        let currency = currency.as_deref();
+        self.post(
+            transaction.tx_id,
+            LedgerAccount::available(client_id, currency),
+            LedgerAccount::held(client_id, currency),
+            authorized,
+        );
+
+        self.close_authorization(transaction.tx_id, AuthorizationState::Voided);
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    fn close_authorization(&mut self, tx_id: u32, state: AuthorizationState) {
+        if let Some(stored) = self.transactions.get_mut(&tx_id) {
+            stored.authorization = Some(state);
+        }
+    }
+
+    /// Releases authorizations that outlived `authorization_expiry`. Locked accounts are
+    /// frozen, so their authorizations stay outstanding.
+    fn expire_authorizations(&mut self) {
+        while let Some(&(expires_after, tx_id)) = self.expiring_authorizations.front() {
+            if expires_after >= self.rows_handled {
+                break;
+            }
+            self.expiring_authorizations.pop_front();
+
+            let Some(stored) = self.transactions.get(&tx_id) else {
+                continue;
+            };
+            let tx = &stored.transaction;
+            if stored.authorization != Some(AuthorizationState::Outstanding) || self.is_locked(tx.client_id) {
+                continue;
+            }
+
+            let (client_id, currency, amount) = (tx.client_id, tx.currency.clone(), tx.amount.unwrap_or_default());
+            if self.account(client_id, currency.as_deref()).is_some() {
+                let currency = currency.as_deref();
+                self.post(
+                    tx_id,
+                    LedgerAccount::available(client_id, currency),
+                    LedgerAccount::held(client_id, currency),
+                    amount,
+                );
+            }
+            self.close_authorization(tx_id, AuthorizationState::Expired);
+            if let Some(changes) = self.changes.as_mut() {
+                changes.clients.insert(client_id);
+                changes.transactions.insert(tx_id);
+            }
+        }
+    }
+}
+
+/// Rounds a value to the given number of decimal places.
+pub(crate) fn round_to(value: f64, places: u32) -> f64 {
+    let factor = 10f64.powi(places as i32);
+    (value * factor).round() / factor
+}
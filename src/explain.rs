@@ -0,0 +1,224 @@
+use std::io;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::engine::{AuthorizationState, EngineError, StoredTransaction, TransactionEngine};
+use crate::models::{Transaction, TransactionType};
+
+/// How an explanation is written.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ExplainFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl FromStr for ExplainFormat {
+    type Err = &'static str;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.to_lowercase().as_str() {
+            "text" => Ok(ExplainFormat::Text),
+            "json" => Ok(ExplainFormat::Json),
+            _ => Err("Invalid explain format, expected 'text' or 'json'"),
+        }
+    }
+}
+
+/// A row naming the explained transaction, and what became of it.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExplainedRow {
+    pub line: Option<u64>,
+    pub row: Transaction,
+    /// Whether the row is the transaction itself or a later row referring to it.
+    pub role: &'static str,
+    /// `passed`, or the validation rule the row failed.
+    pub validation: &'static str,
+    /// `accepted`, `invalid` or `rejected`.
+    pub decision: &'static str,
+    /// The rule that refused the row, when it was refused.
+    pub rule: Option<&'static str>,
+    pub reason: String,
+}
+
+/// Where an accepted transaction stands once every row was processed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Lifecycle {
+    /// `settled`, `disputed`, `resolved`, `charged_back`, `refunded`, `partially_refunded`,
+    /// or the state of an authorization.
+    pub state: &'static str,
+    pub amount: f64,
+    pub disputed: f64,
+    pub held: f64,
+    pub refunded: f64,
+    /// Amount a conversion credited in its target currency.
+    pub credited: Option<f64>,
+}
+
+/// Everything that happened to one transaction id: the row that introduced it, the
+/// rows that referred to it later, and its state at the end.
+#[derive(Debug, Clone, Serialize)]
+pub struct Explanation {
+    #[serde(rename = "tx")]
+    pub tx_id: u32,
+    pub rows: Vec<ExplainedRow>,
+    /// `None` when the engine never accepted the transaction.
+    pub lifecycle: Option<Lifecycle>,
+}
+
+impl Explanation {
+    pub fn new(tx_id: u32) -> Self {
+        Self { tx_id, rows: Vec::new(), lifecycle: None }
+    }
+
+    /// Hands a transaction to the engine, recording the outcome if the row names the
+    /// explained transaction.
+    pub fn handle(
+        &mut self,
+        engine: &mut TransactionEngine,
+        transaction: Transaction,
+        line: Option<u64>,
+    ) -> Result<(), EngineError> {
+        let role = self.role(&transaction);
+        let row = role.map(|_| transaction.clone());
+        let result = engine.handle_transaction(transaction);
+
+        if let (Some(role), Some(row)) = (role, row) {
+            let (validation, decision, rule, reason) = match &result {
+                Ok(()) => ("passed", "accepted", None, String::new()),
+                Err(EngineError::Invalid(e)) => (e.rule(), "invalid", Some(e.rule()), e.to_string()),
+                Err(e) => ("passed", "rejected", Some(e.rule()), e.to_string()),
+            };
+            self.rows.push(ExplainedRow { line, row, role, validation, decision, rule, reason });
+        }
+
+        // Keep the lifecycle current, so it is right whenever processing stops
+        self.lifecycle = engine.transactions.get(&self.tx_id).map(|stored| self.lifecycle_of(stored));
+        result
+    }
+
+    /// `original` for the row that introduces the transaction, `reference` for a later row
+    /// acting on it, and `None` for rows about other transactions.
+    fn role(&self, transaction: &Transaction) -> Option<&'static str> {
+        if transaction.ref_tx == Some(self.tx_id) {
+            return Some("reference");
+        }
+        if transaction.tx_id != self.tx_id {
+            return None;
+        }
+        match transaction.tx_type {
+            TransactionType::Dispute
+            | TransactionType::Resolve
+            | TransactionType::Chargeback
+            | TransactionType::Capture
+            | TransactionType::Void => Some("reference"),
+            _ => Some("original"),
+        }
+    }
+
+    fn lifecycle_of(&self, stored: &StoredTransaction) -> Lifecycle {
+        let amount = stored.transaction.amount.unwrap_or_default();
+        let charged_back = self
+            .rows
+            .iter()
+            .any(|row| row.row.tx_type == TransactionType::Chargeback && row.decision == "accepted");
+
+        let state = match stored.authorization {
+            Some(AuthorizationState::Outstanding) => "outstanding",
+            Some(AuthorizationState::Captured) => "captured",
+            Some(AuthorizationState::Voided) => "voided",
+            Some(AuthorizationState::Expired) => "expired",
+            None if charged_back => "charged_back",
+            None if stored.held > 0.0 => "disputed",
+            // Money is only refunded once a dispute let go of it, so a refund is the later state
+            None if stored.refunded > 0.0 && stored.refunded >= amount => "refunded",
+            None if stored.refunded > 0.0 => "partially_refunded",
+            None if stored.disputed > 0.0 => "resolved",
+            None => "settled",
+        };
+
+        Lifecycle {
+            state,
+            amount,
+            disputed: stored.disputed,
+            held: stored.held,
+            refunded: stored.refunded,
+            credited: stored.credited,
+        }
+    }
+
+    pub fn write<W: io::Write>(&self, format: ExplainFormat, writer: W) -> Result<(), Box<dyn std::error::Error>> {
+        match format {
+            ExplainFormat::Text => self.write_text(writer)?,
+            ExplainFormat::Json => self.write_json(writer)?,
+        }
+        Ok(())
+    }
+
+    pub fn write_json<W: io::Write>(&self, mut writer: W) -> serde_json::Result<()> {
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer).map_err(serde_json::Error::io)
+    }
+
+    /// Writes every row with its validation and decision, then the final state.
+    pub fn write_text<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "Transaction {}", self.tx_id)?;
+        if self.rows.is_empty() {
+            writeln!(writer, "No row names this transaction")?;
+        }
+
+        for row in &self.rows {
+            let line = row.line.map(|n| format!("Line {}", n)).unwrap_or_else(|| "Row".to_string());
+            writeln!(writer, "{} ({}): {}", line, row.role, describe(&row.row))?;
+            writeln!(writer, "  validation: {}", row.validation)?;
+            match row.rule {
+                Some(rule) => writeln!(writer, "  decision: {} by {}: {}", row.decision, rule, row.reason)?,
+                None => writeln!(writer, "  decision: {}", row.decision)?,
+            }
+        }
+
+        match &self.lifecycle {
+            Some(lifecycle) => {
+                write!(
+                    writer,
+                    "State: {} (amount {:.4}, disputed {:.4}, held {:.4}, refunded {:.4}",
+                    lifecycle.state, lifecycle.amount, lifecycle.disputed, lifecycle.held, lifecycle.refunded
+                )?;
+                if let Some(credited) = lifecycle.credited {
+                    write!(writer, ", credited {:.4}", credited)?;
+                }
+                writeln!(writer, ")")
+            }
+            None => writeln!(writer, "State: never accepted"),
+        }
+    }
+}
+
+/// The fields a row set, e.g. `deposit client 1 tx 7 amount 10.5 currency EUR`.
+fn describe(transaction: &Transaction) -> String {
+    let mut fields = vec![
+        transaction.tx_type.to_string(),
+        format!("client {}", transaction.client_id),
+        format!("tx {}", transaction.tx_id),
+    ];
+    if let Some(amount) = transaction.amount {
+        fields.push(format!("amount {}", amount));
+    }
+    if let Some(currency) = &transaction.currency {
+        fields.push(format!("currency {}", currency));
+    }
+    if let Some(to_currency) = &transaction.to_currency {
+        fields.push(format!("to_currency {}", to_currency));
+    }
+    if let Some(to_client) = transaction.to_client {
+        fields.push(format!("to_client {}", to_client));
+    }
+    if let Some(ref_tx) = transaction.ref_tx {
+        fields.push(format!("ref_tx {}", ref_tx));
+    }
+    if let Some(timestamp) = transaction.timestamp {
+        fields.push(format!("timestamp {}", timestamp.to_rfc3339()));
+    }
+    fields.join(" ")
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+
+use serde::Deserialize;
+
+use crate::models::TransactionType;
+
+/// Transaction types the engine can charge a fee on.
+pub const FEE_TYPES: [TransactionType; 4] = [
+    TransactionType::Withdrawal,
+    TransactionType::Transfer,
+    TransactionType::Convert,
+    TransactionType::Chargeback,
+];
+
+/// Fee charged on a transaction type, for every client or only for one tier.
+/// The fee is `flat + amount * percent / 100`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FeeRule {
+    #[serde(rename = "type")]
+    pub tx_type: TransactionType,
+    #[serde(default)]
+    pub tier: Option<String>,
+    pub flat: f64,
+    pub percent: f64,
+}
+
+/// A row of the client tiers file.
+#[derive(Debug, Deserialize)]
+struct TierRow {
+    client: u16,
+    tier: String,
+}
+
+/// Why a fee schedule could not be loaded.
+#[derive(Debug)]
+pub enum FeeError {
+    Csv(csv::Error),
+    UnsupportedType(TransactionType),
+    InvalidRule(FeeRule),
+    DuplicateRule(FeeRule),
+}
+
+impl fmt::Display for FeeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeeError::Csv(e) => write!(f, "Error reading fee schedule: {}", e),
+            FeeError::UnsupportedType(tx_type) => write!(f, "Fees cannot be charged on {} transactions", tx_type),
+            FeeError::InvalidRule(rule) => write!(
+                f,
+                "Invalid {} fee: flat {} and percent {} must be non-negative, percent at most 100",
+                rule.tx_type, rule.flat, rule.percent
+            ),
+            FeeError::DuplicateRule(rule) => write!(
+                f,
+                "The {} fee for tier '{}' is listed more than once",
+                rule.tx_type,
+                rule.tier.as_deref().unwrap_or_default()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FeeError {}
+
+impl From<csv::Error> for FeeError {
+    fn from(e: csv::Error) -> Self {
+        FeeError::Csv(e)
+    }
+}
+
+/// Fees charged by the engine and credited to the house account.
+///
+/// Rules are loaded from a `type,tier,flat,percent` CSV file; a rule with an empty tier
+/// applies to clients without a more specific one. Client tiers come from a `client,tier`
+/// CSV file. No fee is charged until a house account is set.
+#[derive(Debug, Clone, Default)]
+pub struct FeeSchedule {
+    rules: HashMap<(TransactionType, Option<String>), FeeRule>,
+    tiers: HashMap<u16, String>,
+    /// Client whose balances receive every fee.
+    pub house_account: Option<u16>,
+}
+
+impl FeeSchedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the rules of a fee schedule CSV file.
+    pub fn load_rules(&mut self, path: &str) -> Result<(), FeeError> {
+        let file = std::fs::File::open(path).map_err(csv::Error::from)?;
+        self.read_rules(file)
+    }
+
+    /// Adds the rules of a fee schedule from any CSV source.
+    pub fn read_rules<R: io::Read>(&mut self, reader: R) -> Result<(), FeeError> {
+        let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(reader);
+        for rule in reader.deserialize() {
+            self.insert_rule(rule?)?;
+        }
+        Ok(())
+    }
+
+    /// Assigns the client tiers of a CSV file.
+    pub fn load_tiers(&mut self, path: &str) -> Result<(), FeeError> {
+        let file = std::fs::File::open(path).map_err(csv::Error::from)?;
+        self.read_tiers(file)
+    }
+
+    /// Assigns the client tiers from any CSV source.
+    pub fn read_tiers<R: io::Read>(&mut self, reader: R) -> Result<(), FeeError> {
+        let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(reader);
+        for row in reader.deserialize() {
+            let row: TierRow = row?;
+            self.set_tier(row.client, &row.tier);
+        }
+        Ok(())
+    }
+
+    pub fn insert_rule(&mut self, rule: FeeRule) -> Result<(), FeeError> {
+        if !FEE_TYPES.contains(&rule.tx_type) {
+            return Err(FeeError::UnsupportedType(rule.tx_type));
+        }
+        let valid = |value: f64| value.is_finite() && value >= 0.0;
+        if !valid(rule.flat) || !valid(rule.percent) || rule.percent > 100.0 {
+            return Err(FeeError::InvalidRule(rule));
+        }
+
+        let key = (rule.tx_type, rule.tier.clone());
+        if self.rules.contains_key(&key) {
+            return Err(FeeError::DuplicateRule(rule));
+        }
+        self.rules.insert(key, rule);
+
+        Ok(())
+    }
+
+    pub fn set_tier(&mut self, client_id: u16, tier: &str) {
+        self.tiers.insert(client_id, tier.to_string());
+    }
+
+    pub fn tier(&self, client_id: u16) -> Option<&str> {
+        self.tiers.get(&client_id).map(String::as_str)
+    }
+
+    /// Whether any fee can be charged at all.
+    pub fn is_active(&self) -> bool {
+        self.house_account.is_some() && !self.rules.is_empty()
+    }
+
+    /// Unrounded fee for a transaction of `amount`: the rule of the client's tier if there
+    /// is one, otherwise the rule for every client, otherwise nothing.
+    pub fn fee(&self, client_id: u16, tx_type: TransactionType, amount: f64) -> f64 {
+        if self.house_account.is_none() {
+            return 0.0;
+        }
+
+        let tier = self.tiers.get(&client_id).cloned();
+        let rule = tier
+            .and_then(|tier| self.rules.get(&(tx_type, Some(tier))))
+            .or_else(|| self.rules.get(&(tx_type, None)));
+
+        rule.map_or(0.0, |rule| rule.flat + amount * rule.percent / 100.0)
+    }
+}
@@ -0,0 +1,280 @@
+use std::fs::{self, File};
+use std::future::Future;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use csv::StringRecord;
+use tokio::time::Instant;
+
+use crate::engine::TransactionEngine;
+use crate::models::Transaction;
+use crate::server::SharedEngine;
+use crate::utils::{read_record, write_accounts, ProcessingReport, MAX_LINE_LENGTH};
+
+/// Rejections a follower keeps for its report; older ones are only counted.
+pub const KEPT_REJECTIONS: usize = 1000;
+
+/// Follows a CSV file that keeps growing, like `tail -f`.
+///
+/// Every poll processes the lines appended since the previous one. A last line without
+/// its newline is kept until the rest of it arrives. When the path is rotated (it names
+/// another file) or truncated below what was read, the rest of the old file is read
+/// first and the new file is then followed from its start, header row included.
+/// A line longer than [`MAX_LINE_LENGTH`] is malformed, and skipped up to its newline.
+pub struct Follower {
+    path: PathBuf,
+    file: Option<File>,
+    /// Bytes read from the current file so far.
+    position: u64,
+    /// Start of a line still being written.
+    partial: Vec<u8>,
+    /// Whether the line being read is too long and skipped up to its newline.
+    skipping: bool,
+    headers: Option<StringRecord>,
+    /// Physical lines of the current file read so far, blank ones included.
+    line: u64,
+    /// Rows rejected so far, of every kind.
+    pub rejected: usize,
+    /// Outcome of the rows read so far: every accepted row is counted, and the latest
+    /// [`KEPT_REJECTIONS`] rejections are kept.
+    pub report: ProcessingReport,
+}
+
+impl Follower {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            file: None,
+            position: 0,
+            partial: Vec::new(),
+            skipping: false,
+            headers: None,
+            line: 0,
+            rejected: 0,
+            report: ProcessingReport::default(),
+        }
+    }
+
+    /// Processes every complete line appended since the last poll. A missing file is
+    /// waited for.
+    pub fn poll(&mut self, engine: &mut TransactionEngine) -> io::Result<()> {
+        let rows = self.read()?;
+        self.process(rows, engine);
+        Ok(())
+    }
+
+    /// Reads the rows appended since the last read, without touching the engine, so the
+    /// blocking reads can happen while others use it.
+    fn read(&mut self) -> io::Result<Vec<ReadRow>> {
+        let mut rows = Vec::new();
+        self.read_appended(&mut rows)?;
+
+        // Switch to the file now at the path once the old one is exhausted
+        if self.file.is_some() && self.was_rotated()? {
+            // Lines written just before the rotation, and a last line that will never be finished
+            self.read_appended(&mut rows)?;
+            let rest = std::mem::take(&mut self.partial);
+            if !rest.is_empty() {
+                self.read_line(&rest, &mut rows);
+            }
+            self.skipping = false;
+            self.file = None;
+        }
+
+        if self.file.is_none() {
+            match File::open(&self.path) {
+                Ok(file) => {
+                    self.file = Some(file);
+                    self.position = 0;
+                    self.headers = None;
+                    self.line = 0;
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(rows),
+                Err(e) => return Err(e),
+            }
+            self.read_appended(&mut rows)?;
+        }
+
+        Ok(rows)
+    }
+
+    /// Hands rows read earlier to the engine, recording their outcome.
+    fn process(&mut self, rows: Vec<ReadRow>, engine: &mut TransactionEngine) {
+        let kept = self.report.rejections.len();
+        for (line, row) in rows {
+            match row {
+                Ok(transaction) => self.report.handle(engine, transaction, Some(line)),
+                Err(e) => self.report.malformed(Some(line), e),
+            }
+        }
+        self.rejected += self.report.rejections.len() - kept;
+
+        // A file followed all day must not keep every rejection in memory
+        let excess = self.report.rejections.len().saturating_sub(KEPT_REJECTIONS);
+        self.report.rejections.drain(..excess);
+    }
+
+    fn read_appended(&mut self, rows: &mut Vec<ReadRow>) -> io::Result<()> {
+        let mut chunk = vec![0; 64 * 1024];
+        loop {
+            let Some(file) = self.file.as_mut() else {
+                return Ok(());
+            };
+            let read = file.read(&mut chunk)?;
+            if read == 0 {
+                return Ok(());
+            }
+            self.position += read as u64;
+            self.split_lines(&chunk[..read], rows);
+        }
+    }
+
+    /// Adds bytes read to the line being read, reading every line they complete.
+    fn split_lines(&mut self, mut bytes: &[u8], rows: &mut Vec<ReadRow>) {
+        while let Some(end) = bytes.iter().position(|&byte| byte == b'\n') {
+            let (line, rest) = bytes.split_at(end + 1);
+            bytes = rest;
+            if std::mem::take(&mut self.skipping) {
+                continue;
+            }
+
+            self.partial.extend_from_slice(line);
+            let line = std::mem::take(&mut self.partial);
+            if line.len() > MAX_LINE_LENGTH + 1 {
+                self.overlong_line(rows);
+            } else {
+                self.read_line(&line, rows);
+            }
+        }
+
+        // Keep the start of an unfinished line, unless it is already too long
+        if !self.skipping {
+            self.partial.extend_from_slice(bytes);
+            if self.partial.len() > MAX_LINE_LENGTH {
+                self.partial.clear();
+                self.skipping = true;
+                self.overlong_line(rows);
+            }
+        }
+    }
+
+    fn overlong_line(&mut self, rows: &mut Vec<ReadRow>) {
+        self.line += 1;
+        rows.push((self.line, Err(format!("Line longer than {} bytes", MAX_LINE_LENGTH))));
+    }
+
+    /// Whether the path now names another file, or the file got shorter than what was read.
+    fn was_rotated(&self) -> io::Result<bool> {
+        let metadata = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            // Rotated away and not recreated yet: keep the old file until a new one shows up
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        if metadata.len() < self.position {
+            return Ok(true);
+        }
+
+        #[cfg(unix)]
+        if let Some(file) = &self.file {
+            use std::os::unix::fs::MetadataExt;
+            let current = file.metadata()?;
+            return Ok((current.dev(), current.ino()) != (metadata.dev(), metadata.ino()));
+        }
+
+        Ok(false)
+    }
+
+    /// Parses one physical line of the file. Blank lines are skipped but still counted,
+    /// so line numbers match the file.
+    fn read_line(&mut self, line: &[u8], rows: &mut Vec<ReadRow>) {
+        self.line += 1;
+        let line = String::from_utf8_lossy(line);
+        if line.trim().is_empty() {
+            return;
+        }
+
+        let record = match read_record(&line) {
+            Ok(record) => record,
+            Err(e) => return rows.push((self.line, Err(e.to_string()))),
+        };
+        let Some(headers) = &self.headers else {
+            self.headers = Some(record);
+            return;
+        };
+
+        let row = record.deserialize::<Transaction>(Some(headers)).map_err(|e| e.to_string());
+        rows.push((self.line, row));
+    }
+}
+
+/// A line read from the followed file: its number, and the transaction or why it
+/// could not be read.
+type ReadRow = (u64, Result<Transaction, String>);
+
+/// Where and how often `follow` writes the accounts.
+#[derive(Debug, Clone)]
+pub struct Export {
+    pub path: PathBuf,
+    pub interval: Duration,
+}
+
+/// Polls the followed file every `interval` until `shutdown` completes, writing the
+/// accounts to `export` as it goes. The engine is only held while the rows read are
+/// processed, so it can be shared with the HTTP API to query accounts on demand.
+pub async fn follow<F>(
+    follower: &mut Follower,
+    engine: SharedEngine,
+    interval: Duration,
+    export: Option<Export>,
+    shutdown: F,
+) -> io::Result<()>
+where
+    F: Future<Output = ()>,
+{
+    tokio::pin!(shutdown);
+    let mut exported = Instant::now();
+
+    loop {
+        poll_shared(follower, &engine).await?;
+
+        if let Some(export) = export.as_ref().filter(|export| exported.elapsed() >= export.interval) {
+            write_export(&export.path, &engine).await?;
+            exported = Instant::now();
+        }
+
+        tokio::select! {
+            _ = &mut shutdown => break,
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+
+    // Pick up what was appended since the last poll
+    poll_shared(follower, &engine).await?;
+    if let Some(export) = &export {
+        write_export(&export.path, &engine).await?;
+    }
+
+    Ok(())
+}
+
+/// Polls the follower, holding the engine only to process what was read. The read stays
+/// on this task, so the follower keeps its place whatever happens to it.
+async fn poll_shared(follower: &mut Follower, engine: &SharedEngine) -> io::Result<()> {
+    let rows = follower.read()?;
+    follower.process(rows, &mut *engine.lock().await);
+    Ok(())
+}
+
+/// Replaces the export file in one step, so readers never see half of it. The engine is
+/// only held while the accounts are written to memory.
+async fn write_export(path: &Path, engine: &SharedEngine) -> io::Result<()> {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+
+    let mut accounts = Vec::new();
+    write_accounts(&*engine.lock().await, &mut accounts)?;
+    tokio::fs::write(&partial, accounts).await?;
+    tokio::fs::rename(&partial, path).await
+}
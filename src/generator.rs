@@ -0,0 +1,518 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::models::{ClientAccount, TransactionType, ValidationPolicy};
+
+/// Relative weights of each transaction type in a generated dataset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionMix {
+    pub deposit: u32,
+    pub withdrawal: u32,
+    pub dispute: u32,
+    pub resolve: u32,
+    pub chargeback: u32,
+}
+
+impl Default for TransactionMix {
+    fn default() -> Self {
+        Self {
+            deposit: 60,
+            withdrawal: 25,
+            dispute: 8,
+            resolve: 4,
+            chargeback: 3,
+        }
+    }
+}
+
+impl TransactionMix {
+    fn pick(&self, rng: &mut SeededRng) -> TransactionType {
+        let weights = [
+            (TransactionType::Deposit, self.deposit),
+            (TransactionType::Withdrawal, self.withdrawal),
+            (TransactionType::Dispute, self.dispute),
+            (TransactionType::Resolve, self.resolve),
+            (TransactionType::Chargeback, self.chargeback),
+        ];
+        let total: u64 = weights.iter().map(|(_, w)| *w as u64).sum();
+        let mut roll = rng.below(total);
+
+        for (tx_type, weight) in weights {
+            if roll < weight as u64 {
+                return tx_type;
+            }
+            roll -= weight as u64;
+        }
+
+        TransactionType::Deposit
+    }
+}
+
+/// Parses a mix such as `deposit=60,withdrawal=25,dispute=8,resolve=4,chargeback=3`.
+/// Types that are not listed get a weight of zero.
+impl FromStr for TransactionMix {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut mix = TransactionMix {
+            deposit: 0,
+            withdrawal: 0,
+            dispute: 0,
+            resolve: 0,
+            chargeback: 0,
+        };
+
+        for part in input.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (name, weight) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid mix entry '{}', expected <type>=<weight>", part))?;
+            let weight: u32 = weight
+                .trim()
+                .parse()
+                .map_err(|_| format!("Invalid weight in mix entry '{}'", part))?;
+
+            match TransactionType::from_str(name.trim())? {
+                TransactionType::Deposit => mix.deposit = weight,
+                TransactionType::Withdrawal => mix.withdrawal = weight,
+                TransactionType::Dispute => mix.dispute = weight,
+                TransactionType::Resolve => mix.resolve = weight,
+                TransactionType::Chargeback => mix.chargeback = weight,
+                other => return Err(format!("{} transactions are not generated", other)),
+            }
+        }
+
+        if mix.deposit == 0 {
+            return Err("Mix must include deposits, otherwise no account is ever created".into());
+        }
+
+        Ok(mix)
+    }
+}
+
+/// Distribution used to draw deposit and withdrawal amounts.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AmountDistribution {
+    Fixed(f64),
+    Uniform { min: f64, max: f64 },
+    /// Many small amounts and a long tail of large ones.
+    Exponential { mean: f64 },
+}
+
+impl Default for AmountDistribution {
+    fn default() -> Self {
+        AmountDistribution::Uniform { min: 1.0, max: 1000.0 }
+    }
+}
+
+impl AmountDistribution {
+    /// Draws an amount rounded to the engine's four decimal places (never zero).
+    fn sample(&self, rng: &mut SeededRng) -> f64 {
+        let raw = match *self {
+            AmountDistribution::Fixed(value) => value,
+            AmountDistribution::Uniform { min, max } => min + (max - min) * rng.next_f64(),
+            AmountDistribution::Exponential { mean } => -mean * (1.0 - rng.next_f64()).ln(),
+        };
+
+        to_precision(raw.max(0.0001))
+    }
+}
+
+/// Parses `fixed:<amount>`, `uniform:<min>:<max>` or `exponential:<mean>`.
+impl FromStr for AmountDistribution {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = input.split(':').map(str::trim).collect();
+        let number = |value: &str| -> Result<f64, String> {
+            match value.parse::<f64>() {
+                Ok(v) if v.is_finite() && v > 0.0 => Ok(v),
+                _ => Err(format!("Invalid amount '{}' in distribution '{}'", value, input)),
+            }
+        };
+
+        match parts.as_slice() {
+            ["fixed", value] => Ok(AmountDistribution::Fixed(number(value)?)),
+            ["uniform", min, max] => {
+                let (min, max) = (number(min)?, number(max)?);
+                if min > max {
+                    return Err(format!("Invalid uniform range: {} > {}", min, max));
+                }
+                Ok(AmountDistribution::Uniform { min, max })
+            }
+            ["exponential", mean] => Ok(AmountDistribution::Exponential { mean: number(mean)? }),
+            _ => Err(format!(
+                "Invalid amount distribution '{}', expected fixed:<amount>, uniform:<min>:<max> or exponential:<mean>",
+                input
+            )),
+        }
+    }
+}
+
+/// Settings for a generated dataset.
+#[derive(Debug, Clone)]
+pub struct GeneratorConfig {
+    pub seed: u64,
+    pub clients: u16,
+    pub transactions: usize,
+    pub mix: TransactionMix,
+    pub amounts: AmountDistribution,
+    /// Share of withdrawals, disputes, resolves and chargebacks that are built to be rejected.
+    pub invalid_ratio: f64,
+    /// Share of rows written with a broken field count.
+    pub malformed_ratio: f64,
+    /// Largest amount the engine checking the dataset accepts; larger deposits and
+    /// withdrawals are expected to be refused.
+    pub max_amount: f64,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            clients: 10,
+            transactions: 1_000,
+            mix: TransactionMix::default(),
+            amounts: AmountDistribution::default(),
+            invalid_ratio: 0.1,
+            malformed_ratio: 0.0,
+            max_amount: ValidationPolicy::default().max_amount(),
+        }
+    }
+}
+
+/// Counts describing a generated dataset.
+#[derive(Debug, Default, PartialEq)]
+pub struct GeneratorSummary {
+    pub rows: usize,
+    pub malformed: usize,
+    pub deliberately_invalid: usize,
+    pub accounts: usize,
+}
+
+/// Writes a seeded dataset to `transactions` and the balances the engine is expected to
+/// produce for it to `expected`.
+///
+/// The expected balances are computed by a model that is kept separate from
+/// `TransactionEngine`, so the pair can be used as a test oracle for the engine.
+pub fn generate<W: Write, E: Write>(
+    config: &GeneratorConfig,
+    transactions: W,
+    expected: E,
+) -> Result<GeneratorSummary, csv::Error> {
+    let mut rng = SeededRng::new(config.seed);
+    let mut model = Model::new(config.max_amount);
+    let mut summary = GeneratorSummary::default();
+    let clients = config.clients.max(1) as u64;
+
+    let mut writer = csv::WriterBuilder::new().flexible(true).from_writer(transactions);
+    writer.write_record(["type", "client", "tx", "amount"])?;
+
+    let mut next_tx_id: u32 = 1;
+    let mut next_unknown_id: u32 = u32::MAX;
+
+    for _ in 0..config.transactions {
+        summary.rows += 1;
+        let client_id = 1 + rng.below(clients) as u16;
+
+        // Rows with a broken field count, which the reader reports and skips
+        if rng.chance(config.malformed_ratio) {
+            summary.malformed += 1;
+            let client = client_id.to_string();
+            match rng.below(3) {
+                0 => writer.write_record(["deposit", client.as_str()])?,
+                1 => writer.write_record(["withdrawal", client.as_str(), "0", "1.0", "unexpected"])?,
+                _ => writer.write_record(["#corrupted#"])?,
+            }
+            continue;
+        }
+
+        let invalid = rng.chance(config.invalid_ratio);
+        let tx_type = config.mix.pick(&mut rng);
+
+        match tx_type {
+            TransactionType::Deposit => {
+                let amount = config.amounts.sample(&mut rng);
+                write_row(&mut writer, tx_type, client_id, next_tx_id, Some(amount))?;
+                model.deposit(client_id, next_tx_id, amount);
+                next_tx_id += 1;
+            }
+            TransactionType::Withdrawal => {
+                let mut amount = config.amounts.sample(&mut rng);
+                if invalid {
+                    // Ask for more than the client holds
+                    summary.deliberately_invalid += 1;
+                    amount = to_precision(model.available(client_id) + amount);
+                }
+                write_row(&mut writer, tx_type, client_id, next_tx_id, Some(amount))?;
+                model.withdraw(client_id, amount);
+                next_tx_id += 1;
+            }
+            TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback => {
+                let wanted = if tx_type == TransactionType::Dispute {
+                    DepositState::Clean
+                } else {
+                    DepositState::Disputed
+                };
+
+                let target = if invalid {
+                    None
+                } else {
+                    model.pick_deposit(&mut rng, client_id, wanted)
+                };
+
+                let tx_id = match target {
+                    Some(tx_id) => tx_id,
+                    None => {
+                        summary.deliberately_invalid += 1;
+                        model.invalid_reference(&mut rng, client_id, tx_type, &mut next_unknown_id)
+                    }
+                };
+
+                write_row(&mut writer, tx_type, client_id, tx_id, None)?;
+                model.apply_reference(client_id, tx_id, tx_type);
+            }
+            // The mix only picks the types above
+            _ => unreachable!("the mix never picks {} transactions", tx_type),
+        }
+    }
+
+    writer.flush()?;
+
+    let mut expected_writer = csv::Writer::from_writer(expected);
+    for account in model.accounts.values() {
+        expected_writer.serialize(account)?;
+    }
+    expected_writer.flush()?;
+
+    summary.accounts = model.accounts.len();
+    Ok(summary)
+}
+
+fn write_row<W: Write>(
+    writer: &mut csv::Writer<W>,
+    tx_type: TransactionType,
+    client_id: u16,
+    tx_id: u32,
+    amount: Option<f64>,
+) -> Result<(), csv::Error> {
+    let amount = amount.map(|a| format!("{:.4}", a)).unwrap_or_default();
+    writer.write_record([
+        tx_type.to_string(),
+        client_id.to_string(),
+        tx_id.to_string(),
+        amount,
+    ])
+}
+
+fn to_precision(value: f64) -> f64 {
+    (value * 10_000.0).round() / 10_000.0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DepositState {
+    Clean,
+    Disputed,
+    Settled,
+}
+
+#[derive(Debug)]
+struct ModelDeposit {
+    client_id: u16,
+    amount: f64,
+    state: DepositState,
+}
+
+/// Reference model of the engine rules used to compute the expected balances.
+struct Model {
+    accounts: BTreeMap<u16, ClientAccount>,
+    deposits: BTreeMap<u32, ModelDeposit>,
+    deposits_by_client: HashMap<u16, Vec<u32>>,
+    max_amount: f64,
+}
+
+impl Model {
+    fn new(max_amount: f64) -> Self {
+        Self {
+            accounts: BTreeMap::new(),
+            deposits: BTreeMap::new(),
+            deposits_by_client: HashMap::new(),
+            max_amount,
+        }
+    }
+
+    fn is_locked(&self, client_id: u16) -> bool {
+        self.accounts.get(&client_id).is_some_and(|a| a.locked)
+    }
+
+    fn available(&self, client_id: u16) -> f64 {
+        self.accounts.get(&client_id).map_or(0.0, |a| a.available)
+    }
+
+    fn deposit(&mut self, client_id: u16, tx_id: u32, amount: f64) {
+        // Validation refuses the amount before the account is even looked at
+        if amount > self.max_amount || self.is_locked(client_id) {
+            return;
+        }
+
+        let account = self.accounts.entry(client_id).or_insert_with(|| ClientAccount {
+            client_id,
+            available: 0.0,
+            held: 0.0,
+            total: 0.0,
+            locked: false,
+        });
+        // Like the engine's ledger, every balance is kept to four decimal places
+        account.available = to_precision(account.available + amount);
+        account.total = to_precision(account.total + amount);
+
+        self.deposits.insert(tx_id, ModelDeposit { client_id, amount, state: DepositState::Clean });
+        self.deposits_by_client.entry(client_id).or_default().push(tx_id);
+    }
+
+    fn withdraw(&mut self, client_id: u16, amount: f64) {
+        if amount > self.max_amount {
+            return;
+        }
+        let Some(account) = self.accounts.get_mut(&client_id) else {
+            return;
+        };
+        if account.locked || account.available < amount {
+            return;
+        }
+
+        account.available = to_precision(account.available - amount);
+        account.total = to_precision(account.total - amount);
+    }
+
+    fn pick_deposit(&self, rng: &mut SeededRng, client_id: u16, state: DepositState) -> Option<u32> {
+        let candidates: Vec<u32> = self
+            .deposits_by_client
+            .get(&client_id)?
+            .iter()
+            .copied()
+            .filter(|tx_id| self.deposits[tx_id].state == state)
+            .collect();
+
+        if candidates.is_empty() {
+            return None;
+        }
+        Some(candidates[rng.below(candidates.len() as u64) as usize])
+    }
+
+    /// Chooses a reference that the engine must reject: an unknown transaction, another
+    /// client's deposit or, for resolves and chargebacks, a deposit that is not disputed.
+    fn invalid_reference(
+        &self,
+        rng: &mut SeededRng,
+        client_id: u16,
+        tx_type: TransactionType,
+        next_unknown_id: &mut u32,
+    ) -> u32 {
+        match rng.below(3) {
+            1 => {
+                let foreign = self.deposits.iter().find(|(_, d)| d.client_id != client_id);
+                if let Some((tx_id, _)) = foreign {
+                    return *tx_id;
+                }
+            }
+            2 if tx_type != TransactionType::Dispute => {
+                let held = self.accounts.get(&client_id).map_or(0.0, |a| a.held);
+                let undisputed = self.deposits_by_client.get(&client_id).and_then(|ids| {
+                    ids.iter().copied().find(|tx_id| {
+                        let deposit = &self.deposits[tx_id];
+                        deposit.state == DepositState::Clean && held < deposit.amount
+                    })
+                });
+                if let Some(tx_id) = undisputed {
+                    return tx_id;
+                }
+            }
+            _ => {}
+        }
+
+        let tx_id = *next_unknown_id;
+        *next_unknown_id -= 1;
+        tx_id
+    }
+
+    fn apply_reference(&mut self, client_id: u16, tx_id: u32, tx_type: TransactionType) {
+        if self.is_locked(client_id) {
+            return;
+        }
+
+        // Withdrawals are never referenced on purpose; unknown ids are rejected
+        let Some(deposit) = self.deposits.get_mut(&tx_id) else {
+            return;
+        };
+        if deposit.client_id != client_id {
+            return;
+        }
+        let Some(account) = self.accounts.get_mut(&client_id) else {
+            return;
+        };
+
+        match tx_type {
+            // A deposit is disputed at most once, and only a disputed one is settled.
+            // Like the engine, these rules round balances to four decimal places.
+            TransactionType::Dispute => {
+                if deposit.state != DepositState::Clean || to_precision(account.available) < deposit.amount {
+                    return;
+                }
+                account.available = to_precision(account.available - deposit.amount);
+                account.held = to_precision(account.held + deposit.amount);
+                deposit.state = DepositState::Disputed;
+            }
+            TransactionType::Resolve => {
+                if deposit.state != DepositState::Disputed || to_precision(account.held) < deposit.amount {
+                    return;
+                }
+                account.held = to_precision(account.held - deposit.amount);
+                account.available = to_precision(account.available + deposit.amount);
+                deposit.state = DepositState::Settled;
+            }
+            TransactionType::Chargeback => {
+                if deposit.state != DepositState::Disputed || to_precision(account.held) < deposit.amount {
+                    return;
+                }
+                account.held = to_precision(account.held - deposit.amount);
+                account.total = to_precision(account.total - deposit.amount);
+                account.locked = true;
+                deposit.state = DepositState::Settled;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// SplitMix64, so that a seed always produces the same dataset on every platform.
+struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound.max(1)
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        probability > 0.0 && self.next_f64() < probability
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::engine::{round_to, TransactionEngine};
+use crate::models::{ClientAccount, Currency};
+
+/// What happened to a balance.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum EventKind {
+    /// Funds came in: `available` and `total` grow.
+    Deposited,
+    /// Funds went out: `available` and `total` shrink.
+    Withdrew,
+    /// Funds moved from `available` to `held`.
+    FundsHeld,
+    /// Funds moved from `held` back to `available`.
+    FundsReleased,
+    /// Held funds were taken back: `held` and `total` shrink.
+    ChargedBack,
+    /// Every balance of the client was frozen.
+    AccountLocked,
+}
+
+/// One change to one balance, caused by the transaction `tx_id`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub kind: EventKind,
+    pub client_id: u16,
+    pub currency: Option<Currency>,
+    pub tx_id: u32,
+    /// Zero for `AccountLocked`.
+    pub amount: f64,
+}
+
+impl Event {
+    pub fn new(kind: EventKind, client_id: u16, currency: Option<&str>, tx_id: u32, amount: f64) -> Self {
+        Self { kind, client_id, currency: currency.map(str::to_string), tx_id, amount }
+    }
+}
+
+/// Balances in the same shape the engine keeps them.
+#[derive(Debug, Default)]
+pub struct Balances {
+    pub accounts: HashMap<u16, ClientAccount>,
+    pub currency_accounts: HashMap<u16, BTreeMap<Currency, ClientAccount>>,
+}
+
+impl Balances {
+    /// The balance of a client in a currency; `None` is the default currency.
+    pub fn account(&self, client_id: u16, currency: Option<&str>) -> Option<&ClientAccount> {
+        match currency {
+            None => self.accounts.get(&client_id),
+            Some(currency) => self.currency_accounts.get(&client_id)?.get(currency),
+        }
+    }
+
+    /// Every balance of every client, with its currency.
+    pub fn balances(&self) -> impl Iterator<Item = (Option<&str>, &ClientAccount)> {
+        let default = self.accounts.values().map(|account| (None, account));
+        let labelled = self
+            .currency_accounts
+            .values()
+            .flat_map(|wallets| wallets.iter().map(|(currency, account)| (Some(currency.as_str()), account)));
+        default.chain(labelled)
+    }
+}
+
+/// Applies an event to the balances it names, opening the balance if needed. Every
+/// balance is rounded to `places`, like the ledger postings behind it.
+///
+/// The engine changes its balances only through here, so folding the journal gives
+/// back exactly the balances the engine holds.
+pub(crate) fn apply(
+    accounts: &mut HashMap<u16, ClientAccount>,
+    currency_accounts: &mut HashMap<u16, BTreeMap<Currency, ClientAccount>>,
+    event: &Event,
+    places: u32,
+) {
+    let client_id = event.client_id;
+    let account = match &event.currency {
+        None => accounts.entry(client_id).or_insert_with(|| ClientAccount::new(client_id)),
+        Some(currency) => currency_accounts
+            .entry(client_id)
+            .or_default()
+            .entry(currency.clone())
+            .or_insert_with(|| ClientAccount::new(client_id)),
+    };
+
+    let amount = event.amount;
+    match event.kind {
+        EventKind::Deposited => {
+            account.available = round_to(account.available + amount, places);
+            account.total = round_to(account.total + amount, places);
+        }
+        EventKind::Withdrew => {
+            account.available = round_to(account.available - amount, places);
+            account.total = round_to(account.total - amount, places);
+        }
+        EventKind::FundsHeld => {
+            account.available = round_to(account.available - amount, places);
+            account.held = round_to(account.held + amount, places);
+        }
+        EventKind::FundsReleased => {
+            account.held = round_to(account.held - amount, places);
+            account.available = round_to(account.available + amount, places);
+        }
+        EventKind::ChargedBack => {
+            account.held = round_to(account.held - amount, places);
+            account.total = round_to(account.total - amount, places);
+        }
+        EventKind::AccountLocked => {
+            if let Some(account) = accounts.get_mut(&client_id) {
+                account.locked = true;
+            }
+            if let Some(wallets) = currency_accounts.get_mut(&client_id) {
+                wallets.values_mut().for_each(|a| a.locked = true);
+            }
+        }
+    }
+}
+
+/// A balance whose rebuilt state differs from the engine's.
+#[derive(Debug, PartialEq)]
+pub struct Mismatch {
+    pub client_id: u16,
+    pub currency: Option<Currency>,
+    /// `None` when the engine has no such balance.
+    pub live: Option<ClientAccount>,
+    /// `None` when the journal never opened the balance.
+    pub rebuilt: Option<ClientAccount>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let describe = |account: &Option<ClientAccount>| match account {
+            Some(a) => format!(
+                "available {:.4}, held {:.4}, total {:.4}, locked {}",
+                a.available, a.held, a.total, a.locked
+            ),
+            None => "no balance".to_string(),
+        };
+        write!(
+            f,
+            "Client {} ({}): engine has {}, journal gives {}",
+            self.client_id,
+            self.currency.as_deref().unwrap_or("default currency"),
+            describe(&self.live),
+            describe(&self.rebuilt)
+        )
+    }
+}
+
+/// Every event the engine applied since the journal was started, in order.
+#[derive(Debug, Default)]
+pub struct Journal {
+    pub events: Vec<Event>,
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Folds the journal into balances from nothing, rounding like an engine
+    /// validating amounts to `places` decimals.
+    pub fn rebuild(&self, places: u32) -> Balances {
+        let mut balances = Balances::default();
+        for event in &self.events {
+            apply(&mut balances.accounts, &mut balances.currency_accounts, event, places);
+        }
+        balances
+    }
+
+    /// Replays the journal and compares the result with every live balance of the engine,
+    /// exactly. The journal must have been started on an empty engine.
+    ///
+    /// The replay only sums the amounts of each event kind instead of going through
+    /// [`apply`], which also built the live balances; a mistake there then shows up as a
+    /// mismatch instead of being made on both sides.
+    pub fn verify(&self, engine: &TransactionEngine) -> Vec<Mismatch> {
+        let replayed = self.replay(engine.validation.max_decimal_places());
+
+        let mut keys: Vec<(u16, Option<&str>)> = engine
+            .balances()
+            .map(|(currency, account)| (account.client_id, currency))
+            .chain(replayed.keys().map(|(client_id, currency)| (*client_id, currency.as_deref())))
+            .collect();
+        keys.sort();
+        keys.dedup();
+
+        keys.into_iter()
+            .filter_map(|(client_id, currency)| {
+                let live = engine.account(client_id, currency);
+                let replayed = replayed.get(&(client_id, currency.map(str::to_string)));
+                (live != replayed).then(|| Mismatch {
+                    client_id,
+                    currency: currency.map(str::to_string),
+                    live: live.cloned(),
+                    rebuilt: replayed.cloned(),
+                })
+            })
+            .collect()
+    }
+
+    /// Every balance the journal opened: `available` and `held` are the sums of what the
+    /// events moved, `total` their sum, and a balance is locked if it was open when its
+    /// client was locked.
+    fn replay(&self, places: u32) -> BTreeMap<(u16, Option<Currency>), ClientAccount> {
+        let mut sums: BTreeMap<(u16, Option<Currency>), (f64, f64, bool)> = BTreeMap::new();
+        for event in &self.events {
+            let key = (event.client_id, event.currency.clone());
+            let (available, held, _) = sums.entry(key).or_default();
+            let amount = event.amount;
+            match event.kind {
+                EventKind::Deposited => *available += amount,
+                EventKind::Withdrew => *available -= amount,
+                EventKind::FundsHeld => {
+                    *available -= amount;
+                    *held += amount;
+                }
+                EventKind::FundsReleased => {
+                    *held -= amount;
+                    *available += amount;
+                }
+                EventKind::ChargedBack => *held -= amount,
+                EventKind::AccountLocked => sums
+                    .iter_mut()
+                    .filter(|((client_id, _), _)| *client_id == event.client_id)
+                    .for_each(|(_, (_, _, locked))| *locked = true),
+            }
+        }
+
+        sums.into_iter()
+            .map(|((client_id, currency), (available, held, locked))| {
+                let (available, held) = (round_to(available, places), round_to(held, places));
+                let total = round_to(available + held, places);
+                let account = ClientAccount { client_id, available, held, total, locked };
+                ((client_id, currency), account)
+            })
+            .collect()
+    }
+}
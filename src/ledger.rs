@@ -0,0 +1,170 @@
+use std::collections::BTreeMap;
+
+use crate::engine::round_to;
+use crate::journal::{Event, EventKind};
+use crate::models::Currency;
+
+/// Accounts of the engine itself, on the other side of the client balances.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SystemBook {
+    /// Money entering and leaving the engine: deposits, withdrawals, refunds and captures.
+    ExternalFunding,
+    /// Held funds taken back by chargebacks.
+    ChargebackLoss,
+    /// Fees charged, passed on to the house account.
+    Fees,
+    /// Counterparty of currency conversions, one per currency.
+    Exchange,
+    /// Balances carried over from an earlier run.
+    OpeningBalances,
+}
+
+impl SystemBook {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SystemBook::ExternalFunding => "external_funding",
+            SystemBook::ChargebackLoss => "chargeback_loss",
+            SystemBook::Fees => "fees",
+            SystemBook::Exchange => "exchange",
+            SystemBook::OpeningBalances => "opening_balances",
+        }
+    }
+}
+
+/// An account of the ledger. Every currency has its own set.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LedgerAccount {
+    /// What a client may use.
+    Available { client_id: u16, currency: Option<Currency> },
+    /// What is held from a client by disputes and authorizations.
+    Held { client_id: u16, currency: Option<Currency> },
+    System { book: SystemBook, currency: Option<Currency> },
+}
+
+impl LedgerAccount {
+    pub fn available(client_id: u16, currency: Option<&str>) -> Self {
+        LedgerAccount::Available { client_id, currency: currency.map(str::to_string) }
+    }
+
+    pub fn held(client_id: u16, currency: Option<&str>) -> Self {
+        LedgerAccount::Held { client_id, currency: currency.map(str::to_string) }
+    }
+
+    pub fn system(book: SystemBook, currency: Option<&str>) -> Self {
+        LedgerAccount::System { book, currency: currency.map(str::to_string) }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LedgerAccount::Available { .. } => "available",
+            LedgerAccount::Held { .. } => "held",
+            LedgerAccount::System { book, .. } => book.name(),
+        }
+    }
+
+    pub fn client_id(&self) -> Option<u16> {
+        match self {
+            LedgerAccount::Available { client_id, .. } | LedgerAccount::Held { client_id, .. } => Some(*client_id),
+            LedgerAccount::System { .. } => None,
+        }
+    }
+
+    pub fn currency(&self) -> Option<&str> {
+        match self {
+            LedgerAccount::Available { currency, .. }
+            | LedgerAccount::Held { currency, .. }
+            | LedgerAccount::System { currency, .. } => currency.as_deref(),
+        }
+    }
+}
+
+/// A balanced entry: `amount` is debited to one account and credited to another in the
+/// same currency. Debits increase a balance, credits decrease it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Posting {
+    pub debit: LedgerAccount,
+    pub credit: LedgerAccount,
+    pub amount: f64,
+}
+
+impl Posting {
+    pub fn new(debit: LedgerAccount, credit: LedgerAccount, amount: f64) -> Self {
+        Self { debit, credit, amount }
+    }
+
+    /// What the posting does to client balances, caused by the transaction `tx_id`.
+    pub fn events(&self, tx_id: u32) -> Vec<Event> {
+        let event = |kind, account: &LedgerAccount| {
+            Event::new(kind, account.client_id().unwrap_or_default(), account.currency(), tx_id, self.amount)
+        };
+
+        match (&self.debit, &self.credit) {
+            (held @ LedgerAccount::Held { .. }, LedgerAccount::Available { .. }) => {
+                vec![event(EventKind::FundsHeld, held)]
+            }
+            (available @ LedgerAccount::Available { .. }, LedgerAccount::Held { .. }) => {
+                vec![event(EventKind::FundsReleased, available)]
+            }
+            (to @ LedgerAccount::Available { .. }, from @ LedgerAccount::Available { .. }) => {
+                vec![event(EventKind::Withdrew, from), event(EventKind::Deposited, to)]
+            }
+            (LedgerAccount::System { .. }, held @ LedgerAccount::Held { .. }) => {
+                vec![event(EventKind::ChargedBack, held)]
+            }
+            (LedgerAccount::System { .. }, from @ LedgerAccount::Available { .. }) => {
+                vec![event(EventKind::Withdrew, from)]
+            }
+            (to @ LedgerAccount::Available { .. }, LedgerAccount::System { .. }) => {
+                vec![event(EventKind::Deposited, to)]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Balance of every ledger account. Every posting is balanced, so in each currency the
+/// balances always net to zero: money held for clients is exactly what came in from
+/// outside, less what left, was charged back or is still held by the engine.
+#[derive(Debug, Default)]
+pub struct Ledger {
+    balances: BTreeMap<LedgerAccount, f64>,
+}
+
+impl Ledger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies a posting, rounding both balances to `places` decimals.
+    pub fn post(&mut self, posting: &Posting, places: u32) {
+        let debit = self.balances.entry(posting.debit.clone()).or_default();
+        *debit = round_to(*debit + posting.amount, places);
+        let credit = self.balances.entry(posting.credit.clone()).or_default();
+        *credit = round_to(*credit - posting.amount, places);
+    }
+
+    pub fn balance(&self, account: &LedgerAccount) -> f64 {
+        self.balances.get(account).copied().unwrap_or_default()
+    }
+
+    /// Every account posted to, with its balance, ordered by account.
+    pub fn trial_balance(&self) -> impl Iterator<Item = (&LedgerAccount, f64)> {
+        self.balances.iter().map(|(account, balance)| (account, *balance))
+    }
+
+    /// Sum of the balances in each currency, rounded to `places` decimals; zero when
+    /// the ledger is balanced.
+    pub fn net(&self, places: u32) -> BTreeMap<Option<&str>, f64> {
+        let mut net = BTreeMap::new();
+        for (account, balance) in &self.balances {
+            *net.entry(account.currency()).or_default() += balance;
+        }
+        net.values_mut().for_each(|sum| *sum = round_to(*sum, places));
+        net
+    }
+
+    /// Currencies whose balances do not net to zero, with their net.
+    pub fn unbalanced(&self, places: u32) -> Vec<(Option<&str>, f64)> {
+        self.net(places).into_iter().filter(|(_, sum)| *sum != 0.0).collect()
+    }
+}
@@ -1,3 +1,19 @@
 pub mod models;
 pub mod engine;
-pub mod utils;
\ No newline at end of file
+pub mod utils;
+pub mod generator;
+pub mod rates;
+pub mod fees;
+pub mod limits;
+pub mod credit;
+pub mod server;
+pub mod tcp;
+pub mod watch;
+pub mod follow;
+pub mod journal;
+pub mod ledger;
+pub mod explain;
+pub mod reconcile;
+pub mod statement;
+#[cfg(feature = "sqlite")]
+pub mod store;
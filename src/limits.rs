@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+use chrono::TimeDelta;
+use serde::Deserialize;
+
+/// Risk limits of a client; a limit left empty is not enforced.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Limits {
+    /// Largest single withdrawal.
+    pub max_withdrawal: Option<f64>,
+    /// Most withdrawals within the window.
+    pub max_withdrawals: Option<u32>,
+    /// Largest sum of deposits within the window.
+    pub max_deposit_volume: Option<f64>,
+}
+
+impl Limits {
+    /// These limits, falling back to `defaults` for the ones left empty.
+    fn or(&self, defaults: &Limits) -> Limits {
+        Limits {
+            max_withdrawal: self.max_withdrawal.or(defaults.max_withdrawal),
+            max_withdrawals: self.max_withdrawals.or(defaults.max_withdrawals),
+            max_deposit_volume: self.max_deposit_volume.or(defaults.max_deposit_volume),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == Limits::default()
+    }
+}
+
+/// A row of the limits file; an empty client sets the defaults.
+#[derive(Debug, Deserialize)]
+struct LimitsRow {
+    #[serde(default)]
+    client: Option<u16>,
+    #[serde(default)]
+    max_withdrawal: Option<f64>,
+    #[serde(default)]
+    max_withdrawals: Option<u32>,
+    #[serde(default)]
+    max_deposit_volume: Option<f64>,
+}
+
+/// What the engine does when a transaction would breach a limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum BreachAction {
+    /// Reject the transaction.
+    #[default]
+    Reject,
+    /// Reject the transaction and lock the client, as a chargeback does.
+    Lock,
+}
+
+impl FromStr for BreachAction {
+    type Err = &'static str;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.to_lowercase().as_str() {
+            "reject" => Ok(BreachAction::Reject),
+            "lock" => Ok(BreachAction::Lock),
+            _ => Err("Invalid limit breach action, expected 'reject' or 'lock'"),
+        }
+    }
+}
+
+/// The stretch of a client's history the velocity limits looked at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LimitWindow {
+    /// The client's most recent rows.
+    Rows(u64),
+    /// The time before the row's timestamp.
+    Period(TimeDelta),
+}
+
+impl fmt::Display for LimitWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitWindow::Rows(rows) => write!(f, "{} rows", rows),
+            LimitWindow::Period(period) => write!(f, "{} seconds", period.num_seconds()),
+        }
+    }
+}
+
+/// The limit a transaction would have breached.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LimitBreach {
+    SingleWithdrawal { amount: f64, max: f64 },
+    WithdrawalCount { max: u32, window: LimitWindow },
+    DepositVolume { volume: f64, max: f64, window: LimitWindow },
+}
+
+impl fmt::Display for LimitBreach {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitBreach::SingleWithdrawal { amount, max } => {
+                write!(f, "withdrawal of {} exceeds the maximum of {}", amount, max)
+            }
+            LimitBreach::WithdrawalCount { max, window } => {
+                write!(f, "more than {} withdrawals within {}", max, window)
+            }
+            LimitBreach::DepositVolume { volume, max, window } => {
+                write!(f, "deposits of {} within {} exceed the maximum of {}", volume, window, max)
+            }
+        }
+    }
+}
+
+/// Why a limits file could not be loaded.
+#[derive(Debug)]
+pub enum LimitsError {
+    Csv(csv::Error),
+    InvalidLimit { client: Option<u16> },
+}
+
+impl fmt::Display for LimitsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitsError::Csv(e) => write!(f, "Error reading limits: {}", e),
+            LimitsError::InvalidLimit { client: Some(client) } => {
+                write!(f, "Limits of client {} must be positive numbers", client)
+            }
+            LimitsError::InvalidLimit { client: None } => write!(f, "Default limits must be positive numbers"),
+        }
+    }
+}
+
+impl std::error::Error for LimitsError {}
+
+impl From<csv::Error> for LimitsError {
+    fn from(e: csv::Error) -> Self {
+        LimitsError::Csv(e)
+    }
+}
+
+/// Withdrawal and velocity limits enforced by the engine.
+///
+/// Limits are loaded from a `client,max_withdrawal,max_withdrawals,max_deposit_volume`
+/// CSV file. The row with an empty client sets the defaults; a client row overrides
+/// the limits it fills in. Velocity limits look at the client's accepted transactions
+/// within its own last `window` rows, or within `period` of a row that has a timestamp.
+#[derive(Debug, Clone)]
+pub struct LimitPolicy {
+    pub defaults: Limits,
+    overrides: HashMap<u16, Limits>,
+    /// Number of the client's most recent rows the velocity limits look at.
+    pub window: u64,
+    /// Time before a row's timestamp the velocity limits look at instead; `None` always
+    /// counts rows.
+    pub period: Option<TimeDelta>,
+    pub on_breach: BreachAction,
+}
+
+impl Default for LimitPolicy {
+    fn default() -> Self {
+        Self {
+            defaults: Limits::default(),
+            overrides: HashMap::new(),
+            window: 1_000,
+            period: None,
+            on_breach: BreachAction::default(),
+        }
+    }
+}
+
+impl LimitPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the limits of a CSV file.
+    pub fn load(&mut self, path: &str) -> Result<(), LimitsError> {
+        let file = std::fs::File::open(path).map_err(csv::Error::from)?;
+        self.read(file)
+    }
+
+    /// Adds the limits from any CSV source.
+    pub fn read<R: io::Read>(&mut self, reader: R) -> Result<(), LimitsError> {
+        let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(reader);
+
+        for row in reader.deserialize() {
+            let row: LimitsRow = row?;
+            let positive = |value: Option<f64>| value.is_none_or(|v| v.is_finite() && v > 0.0);
+            if !positive(row.max_withdrawal) || !positive(row.max_deposit_volume) || row.max_withdrawals == Some(0) {
+                return Err(LimitsError::InvalidLimit { client: row.client });
+            }
+
+            let limits = Limits {
+                max_withdrawal: row.max_withdrawal,
+                max_withdrawals: row.max_withdrawals,
+                max_deposit_volume: row.max_deposit_volume,
+            };
+            match row.client {
+                Some(client_id) => self.set_limits(client_id, limits),
+                None => self.defaults = limits,
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn set_limits(&mut self, client_id: u16, limits: Limits) {
+        self.overrides.insert(client_id, limits);
+    }
+
+    /// The limits a client is held to.
+    pub fn limits(&self, client_id: u16) -> Limits {
+        match self.overrides.get(&client_id) {
+            Some(limits) => limits.or(&self.defaults),
+            None => self.defaults.clone(),
+        }
+    }
+
+    /// Whether any limit is configured at all.
+    pub fn is_active(&self) -> bool {
+        !self.defaults.is_empty() || self.overrides.values().any(|limits| !limits.is_empty())
+    }
+}
@@ -1,23 +1,110 @@
+use chrono::TimeDelta;
 use std::env;
-use crate::engine::TransactionEngine;
-use crate::utils::{export_accounts_to_stdout, process_file};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+use toy_payments_engine::credit::CreditLimits;
+use toy_payments_engine::engine::TransactionEngine;
+use toy_payments_engine::explain::{ExplainFormat, Explanation};
+use toy_payments_engine::models::{Timestamp, ValidationPolicy};
+use toy_payments_engine::rates::RateTable;
+use toy_payments_engine::reconcile::{read_expected, reconcile, write_discrepancies};
+use toy_payments_engine::statement::{Statement, StatementFormat, StatementRange};
+#[cfg(feature = "sqlite")]
+use toy_payments_engine::store::SqliteStore;
+#[cfg(feature = "sqlite")]
+use toy_payments_engine::utils::process_csv_with;
+use toy_payments_engine::follow::{self, Export, Follower};
+use toy_payments_engine::watch::{self, Inbox};
+use toy_payments_engine::{server, tcp};
+use toy_payments_engine::utils::{
+    export_accounts_to_stdout, process_csv_rows, process_file, write_authorizations, write_fees, write_journal,
+    write_trial_balance,
+};
 
-mod engine;
-mod models;
-mod utils;
+const USAGE_OPTIONS: &str = "[--max-amount <amount>] [--max-decimals <places>] [--zero-amounts allow|reject] \
+[--rates <rates_file>] [--authorization-expiry <rows>] [--authorizations <report_file>] \
+[--fee-schedule <fees_file> --house-account <client>] [--client-tiers <tiers_file>] [--fees <report_file>] \
+[--limits <limits_file>] [--limit-window <rows>] [--limit-period <seconds>] [--limit-breach reject|lock] [--credit-limits <credit_file>] \
+[--timestamp-tolerance <seconds>] [--dispute-window <days>] [--journal <report_file>] [--trial-balance <report_file>]";
+
+/// Options of the default command that keep the balances in a database.
+#[cfg(feature = "sqlite")]
+const DATABASE_OPTIONS: &str = " [--database <sqlite_file>] [--commit-every <rows>]";
+#[cfg(not(feature = "sqlite"))]
+const DATABASE_OPTIONS: &str = "";
+
+/// Options of `watch` that keep the balances in a database.
+#[cfg(feature = "sqlite")]
+const WATCH_DATABASE_OPTIONS: &str = " [--database <sqlite_file>]";
+#[cfg(not(feature = "sqlite"))]
+const WATCH_DATABASE_OPTIONS: &str = "";
+
+/// Reports written after processing, in addition to the accounts on stdout.
+#[derive(Default)]
+struct Reports {
+    authorizations: Option<String>,
+    fees: Option<String>,
+    journal: Option<String>,
+    trial_balance: Option<String>,
+}
+
+/// Address the HTTP API listens on unless `--listen` says otherwise.
+const DEFAULT_LISTEN: &str = "127.0.0.1:8080";
+/// Seconds between two looks at a watched directory unless `--poll-interval` says otherwise.
+const DEFAULT_POLL_INTERVAL: f64 = 1.0;
+/// Seconds between two account exports in follow mode unless `--export-interval` says otherwise.
+const DEFAULT_EXPORT_INTERVAL: f64 = 10.0;
+/// Address the TCP ingestion server listens on unless `--listen` says otherwise.
+const DEFAULT_STREAM_LISTEN: &str = "127.0.0.1:9000";
 
 pub async fn run_program(args: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    match args.get(1).map(String::as_str) {
+        Some("serve") => return run_server(args).await,
+        Some("stream") => return run_stream(args).await,
+        Some("watch") => return run_watch(args).await,
+        Some("follow") => return run_follow(args).await,
+        Some("statement") => return run_statement(args).await,
+        Some("explain") => return run_explain(args).await,
+        Some("reconcile") => return run_reconcile(args).await,
+        _ => {}
+    }
+
     if args.len() < 2 {
-        eprintln!("Usage: {} <input_file>", args[0]);
+        eprintln!("Usage: {} <input_file> {}{}", args[0], USAGE_OPTIONS, DATABASE_OPTIONS);
         return Err("Missing input file".into());
     }
 
     let input_file = &args[1];
     let mut engine = TransactionEngine::new();
 
-    if let Err(e) = process_file(input_file, &mut engine).await {
+    #[cfg(feature = "sqlite")]
+    let (database, options) = take_option(&args[2..], "--database");
+    #[cfg(feature = "sqlite")]
+    let (commit_every, options) = take_option(&options, "--commit-every");
+    #[cfg(not(feature = "sqlite"))]
+    let options = args[2..].to_vec();
+
+    let reports = match apply_options(&mut engine, &options) {
+        Ok(reports) => reports,
+        Err(e) => {
+            eprintln!("Usage: {} <input_file> {}{}", args[0], USAGE_OPTIONS, DATABASE_OPTIONS);
+            return Err(e);
+        }
+    };
+
+    #[cfg(feature = "sqlite")]
+    let processed = match database {
+        Some(path) => process_into_database(input_file, &mut engine, &path, commit_every.as_deref()).await,
+        None => process_file(input_file, &mut engine).await.map(drop),
+    };
+    #[cfg(not(feature = "sqlite"))]
+    let processed = process_file(input_file, &mut engine).await;
+
+    if let Err(e) = processed {
         eprintln!("Error processing file: {}", e);
-        return Err(e.into());
+        return Err(e);
     }
 
     if let Err(e) = export_accounts_to_stdout(&engine) {
@@ -25,11 +112,544 @@ pub async fn run_program(args: Vec<String>) -> Result<(), Box<dyn std::error::Er
         return Err(e.into());
     }
 
+    if let Some(path) = &reports.authorizations {
+        if let Err(e) = write_report(path, |file| write_authorizations(&engine, file)) {
+            eprintln!("Error writing authorizations report: {}", e);
+            return Err(e.into());
+        }
+    }
+
+    if let Some(path) = &reports.fees {
+        if let Err(e) = write_report(path, |file| write_fees(&engine, file)) {
+            eprintln!("Error writing fees report: {}", e);
+            return Err(e.into());
+        }
+    }
+
+    if let Some(path) = &reports.journal {
+        if let Err(e) = write_report(path, |file| write_journal(&engine, file)) {
+            eprintln!("Error writing journal: {}", e);
+            return Err(e.into());
+        }
+
+        // The accounts must be exactly what replaying the journal gives
+        let mismatches = engine.journal().map(|journal| journal.verify(&engine)).unwrap_or_default();
+        if !mismatches.is_empty() {
+            for mismatch in &mismatches {
+                eprintln!("{}", mismatch);
+            }
+            return Err(format!("Journal disagrees with {} balances", mismatches.len()).into());
+        }
+    }
+
+    if let Some(path) = &reports.trial_balance {
+        if let Err(e) = write_report(path, |file| write_trial_balance(&engine, file)) {
+            eprintln!("Error writing trial balance: {}", e);
+            return Err(e.into());
+        }
+
+        // Every posting is balanced, so anything else means money appeared or vanished
+        let unbalanced = engine.ledger().unbalanced(engine.validation.max_decimal_places());
+        if !unbalanced.is_empty() {
+            for (currency, net) in &unbalanced {
+                eprintln!("Ledger nets to {:.4} in {}", net, currency.unwrap_or("the default currency"));
+            }
+            return Err("Trial balance does not net to zero".into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Processes the input on top of the accounts and transactions stored in `path`, writing
+/// the changes back every `commit_every` rows and once more at the end.
+#[cfg(feature = "sqlite")]
+async fn process_into_database(
+    input_file: &str,
+    engine: &mut TransactionEngine,
+    path: &str,
+    commit_every: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Replaying a journal starts from empty balances, which a database does not
+    if engine.journal().is_some() {
+        return Err("--journal cannot be combined with --database".into());
+    }
+
+    let mut store = SqliteStore::open(path)?;
+    if let Some(rows) = commit_every {
+        store.batch_size = rows.parse()?;
+        if store.batch_size == 0 {
+            return Err(format!("Invalid commit interval: {}", rows).into());
+        }
+    }
+
+    store.load(engine)?;
+    process_csv_with(input_file, engine, |engine| Ok(store.row_handled(engine)?)).await?;
+    store.commit(engine)?;
+    Ok(())
+}
+
+/// Keeps an engine behind the HTTP API until interrupted, then exports the accounts.
+async fn run_server(args: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let (listen, options) = take_option(&args[2..], "--listen");
+    let mut engine = TransactionEngine::new();
+
+    if let Err(e) = apply_options(&mut engine, &options) {
+        eprintln!("Usage: {} serve [--listen <address>] {}", args[0], USAGE_OPTIONS);
+        return Err(e);
+    }
+
+    let listener = TcpListener::bind(listen.as_deref().unwrap_or(DEFAULT_LISTEN)).await?;
+    eprintln!("Listening on http://{}", listener.local_addr()?);
+
+    let engine = Arc::new(Mutex::new(engine));
+    server::serve(listener, engine.clone(), shutdown_signal()).await?;
+
+    export_accounts_to_stdout(&*engine.lock().await)?;
+    Ok(())
+}
+
+/// Streams CSV lines from TCP connections into one engine until interrupted, then
+/// exports the accounts.
+async fn run_stream(args: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let (listen, options) = take_option(&args[2..], "--listen");
+    let mut engine = TransactionEngine::new();
+
+    if let Err(e) = apply_options(&mut engine, &options) {
+        eprintln!("Usage: {} stream [--listen <address>] {}", args[0], USAGE_OPTIONS);
+        return Err(e);
+    }
+
+    let listener = TcpListener::bind(listen.as_deref().unwrap_or(DEFAULT_STREAM_LISTEN)).await?;
+    eprintln!("Listening on tcp://{}", listener.local_addr()?);
+
+    let engine = Arc::new(Mutex::new(engine));
+    tcp::serve(listener, engine.clone(), shutdown_signal()).await?;
+
+    export_accounts_to_stdout(&*engine.lock().await)?;
+    Ok(())
+}
+
+/// Processes the files dropped into a directory until interrupted, then exports the accounts.
+async fn run_watch(args: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let usage = || {
+        eprintln!(
+            "Usage: {} watch <directory> [--poll-interval <seconds>] {}{}",
+            args[0], USAGE_OPTIONS, WATCH_DATABASE_OPTIONS
+        )
+    };
+    let Some(dir) = args.get(2) else {
+        usage();
+        return Err("Missing directory".into());
+    };
+
+    let (interval, options) = take_option(&args[3..], "--poll-interval");
+    let Some(interval) = parse_seconds(interval, DEFAULT_POLL_INTERVAL) else {
+        usage();
+        return Err("Invalid poll interval".into());
+    };
+
+    #[cfg(feature = "sqlite")]
+    let (database, options) = take_option(&options, "--database");
+
+    let mut engine = TransactionEngine::new();
+    if let Err(e) = apply_options(&mut engine, &options) {
+        usage();
+        return Err(e);
+    }
+
+    // The inbox remembers files across runs, so the balances they built must outlive
+    // the run as well; without a database they start from empty every time
+    #[cfg(feature = "sqlite")]
+    let mut store = match database {
+        Some(path) => {
+            if engine.journal().is_some() {
+                return Err("--journal cannot be combined with --database".into());
+            }
+            let store = SqliteStore::open(&path)?;
+            store.load(&mut engine)?;
+            Some(store)
+        }
+        None => None,
+    };
+    #[cfg(feature = "sqlite")]
+    let save = |engine: &mut TransactionEngine| match store.as_mut() {
+        Some(store) => store.commit(engine).map_err(std::io::Error::other),
+        None => Ok(()),
+    };
+    #[cfg(not(feature = "sqlite"))]
+    let save = |_: &mut TransactionEngine| Ok(());
+
+    let mut inbox = Inbox::open(dir)?;
+    eprintln!("Watching {}", dir);
+    watch::watch(&mut inbox, &mut engine, interval, shutdown_signal(), save).await?;
+
+    export_accounts_to_stdout(&engine)?;
+    Ok(())
+}
+
+/// Follows a growing file until interrupted, then exports the accounts. The accounts
+/// can be exported periodically and, with `--listen`, queried through the HTTP API.
+async fn run_follow(args: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let usage = || {
+        eprintln!(
+            "Usage: {} follow <input_file> [--poll-interval <seconds>] [--export <file>] \
+[--export-interval <seconds>] [--listen <address>] {}",
+            args[0], USAGE_OPTIONS
+        )
+    };
+    let Some(input_file) = args.get(2) else {
+        usage();
+        return Err("Missing input file".into());
+    };
+
+    let (interval, options) = take_option(&args[3..], "--poll-interval");
+    let (export, options) = take_option(&options, "--export");
+    let (export_interval, options) = take_option(&options, "--export-interval");
+    let (listen, options) = take_option(&options, "--listen");
+    let (Some(interval), Some(export_interval)) = (
+        parse_seconds(interval, DEFAULT_POLL_INTERVAL),
+        parse_seconds(export_interval, DEFAULT_EXPORT_INTERVAL),
+    ) else {
+        usage();
+        return Err("Invalid interval".into());
+    };
+
+    let mut engine = TransactionEngine::new();
+    if let Err(e) = apply_options(&mut engine, &options) {
+        usage();
+        return Err(e);
+    }
+    let engine = Arc::new(Mutex::new(engine));
+
+    if let Some(listen) = listen {
+        let listener = TcpListener::bind(listen).await?;
+        eprintln!("Serving accounts on http://{}", listener.local_addr()?);
+        tokio::spawn(server::serve_read_only(listener, engine.clone(), std::future::pending()));
+    }
+
+    let export = export.map(|path| Export { path: path.into(), interval: export_interval });
+    let mut follower = Follower::new(input_file);
+    eprintln!("Following {}", input_file);
+    follow::follow(&mut follower, engine.clone(), interval, export, shutdown_signal()).await?;
+
+    export_accounts_to_stdout(&*engine.lock().await)?;
+    Ok(())
+}
+
+/// Processes a file and writes to stdout the statement of one client: every row that
+/// touched them, with the balances it left behind, and what locked them.
+async fn run_statement(args: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let usage = || {
+        eprintln!(
+            "Usage: {} statement <input_file> <client> [--from-tx <tx>] [--to-tx <tx>] [--from <rfc3339>] \
+[--to <rfc3339>] [--format text|csv|json] {}",
+            args[0], USAGE_OPTIONS
+        )
+    };
+    let (Some(input_file), Some(client)) = (args.get(2), args.get(3)) else {
+        usage();
+        return Err("Missing input file or client".into());
+    };
+    let Ok(client_id) = client.parse::<u16>() else {
+        usage();
+        return Err("Invalid client".into());
+    };
+
+    let (from_tx, options) = take_option(&args[4..], "--from-tx");
+    let (to_tx, options) = take_option(&options, "--to-tx");
+    let (from_time, options) = take_option(&options, "--from");
+    let (to_time, options) = take_option(&options, "--to");
+    let (format, options) = take_option(&options, "--format");
+    let range = parse_range(from_tx, to_tx, from_time, to_time);
+    let (range, format) = match (range, format.map_or(Ok(StatementFormat::Text), |f| f.parse())) {
+        (Ok(range), Ok(format)) => (range, format),
+        (Err(e), _) => {
+            usage();
+            return Err(e);
+        }
+        (_, Err(e)) => {
+            usage();
+            return Err(e.into());
+        }
+    };
+
+    let mut engine = TransactionEngine::new();
+    if let Err(e) = apply_options(&mut engine, &options) {
+        usage();
+        return Err(e);
+    }
+
+    let mut statement = Statement::new(client_id, range);
+    process_csv_rows(input_file, |report, transaction, line| {
+        let (client_id, tx_id) = (transaction.client_id, transaction.tx_id);
+        let result = statement.handle(&mut engine, transaction, line);
+        report.record(line, client_id, tx_id, &result);
+        Ok(())
+    })
+    .await?;
+
+    statement.write(format, std::io::stdout().lock())
+}
+
+/// Processes a file and writes to stdout what became of one transaction: the row that
+/// introduced it, the rows referring to it, why any of them was refused, and its state.
+async fn run_explain(args: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let usage = || eprintln!("Usage: {} explain <input_file> <tx> [--format text|json] {}", args[0], USAGE_OPTIONS);
+    let (Some(input_file), Some(tx)) = (args.get(2), args.get(3)) else {
+        usage();
+        return Err("Missing input file or transaction".into());
+    };
+    let Ok(tx_id) = tx.parse::<u32>() else {
+        usage();
+        return Err("Invalid transaction".into());
+    };
+
+    let (format, options) = take_option(&args[4..], "--format");
+    let format = match format.map_or(Ok(ExplainFormat::Text), |f| f.parse()) {
+        Ok(format) => format,
+        Err(e) => {
+            usage();
+            return Err(e.into());
+        }
+    };
+
+    let mut engine = TransactionEngine::new();
+    if let Err(e) = apply_options(&mut engine, &options) {
+        usage();
+        return Err(e);
+    }
+
+    let mut explanation = Explanation::new(tx_id);
+    process_csv_rows(input_file, |report, transaction, line| {
+        let (client_id, tx_id) = (transaction.client_id, transaction.tx_id);
+        let result = explanation.handle(&mut engine, transaction, line);
+        report.record(line, client_id, tx_id, &result);
+        Ok(())
+    })
+    .await?;
+
+    explanation.write(format, std::io::stdout().lock())
+}
+
+/// Processes a file and compares the resulting accounts with an expected accounts file,
+/// writing every discrepancy to stdout. Fails if there is any.
+async fn run_reconcile(args: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let usage = || {
+        eprintln!(
+            "Usage: {} reconcile <input_file> <expected_accounts_file> [--tolerance <amount>] {}",
+            args[0], USAGE_OPTIONS
+        )
+    };
+    let (Some(input_file), Some(expected_file)) = (args.get(2), args.get(3)) else {
+        usage();
+        return Err("Missing input file or expected accounts file".into());
+    };
+
+    let (tolerance, options) = take_option(&args[4..], "--tolerance");
+    let Some(tolerance) = tolerance
+        .map_or(Ok(0.0), |tolerance| tolerance.parse::<f64>())
+        .ok()
+        .filter(|tolerance| tolerance.is_finite() && *tolerance >= 0.0)
+    else {
+        usage();
+        return Err("Invalid tolerance".into());
+    };
+
+    let mut engine = TransactionEngine::new();
+    if let Err(e) = apply_options(&mut engine, &options) {
+        usage();
+        return Err(e);
+    }
+
+    let expected = read_expected(std::fs::File::open(expected_file)?)?;
+    process_file(input_file, &mut engine).await?;
+
+    let discrepancies = reconcile(&engine, &expected, tolerance);
+    write_discrepancies(&discrepancies, std::io::stdout().lock())?;
+    if !discrepancies.is_empty() {
+        return Err(format!("Found {} discrepancies", discrepancies.len()).into());
+    }
+    eprintln!("Accounts match");
     Ok(())
 }
 
+/// Bounds of a statement: transaction ids, and RFC 3339 times like `2024-01-31T12:00:00Z`.
+fn parse_range(
+    from_tx: Option<String>,
+    to_tx: Option<String>,
+    from_time: Option<String>,
+    to_time: Option<String>,
+) -> Result<StatementRange, Box<dyn std::error::Error>> {
+    Ok(StatementRange {
+        from_tx: from_tx.map(|tx| tx.parse()).transpose()?,
+        to_tx: to_tx.map(|tx| tx.parse()).transpose()?,
+        from_time: from_time.map(|time| time.parse::<Timestamp>()).transpose()?,
+        to_time: to_time.map(|time| time.parse::<Timestamp>()).transpose()?,
+    })
+}
+
+/// A positive number of seconds, or `default` when the option was not given.
+fn parse_seconds(value: Option<String>, default: f64) -> Option<Duration> {
+    value
+        .map_or(Ok(default), |value| value.parse())
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .filter(|duration| !duration.is_zero())
+}
+
+/// Completes on Ctrl-C or, on Unix, SIGTERM.
+async fn shutdown_signal() {
+    let interrupt = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => {}
+        _ = terminate => {}
+    }
+}
+
+/// Removes the `name <value>` pair from the options, returning its value and the rest.
+fn take_option(options: &[String], name: &str) -> (Option<String>, Vec<String>) {
+    let mut value = None;
+    let mut rest = Vec::new();
+    let mut iter = options.iter();
+
+    while let Some(option) = iter.next() {
+        if option == name {
+            // Without a value the option is left for `apply_options` to report
+            match iter.next() {
+                Some(v) => value = Some(v.clone()),
+                None => rest.push(option.clone()),
+            }
+        } else {
+            rest.push(option.clone());
+            rest.extend(iter.next().cloned());
+        }
+    }
+
+    (value, rest)
+}
+
+/// Creates `path` and hands it to a report writer.
+fn write_report<F>(path: &str, write: F) -> Result<(), csv::Error>
+where
+    F: FnOnce(std::fs::File) -> Result<(), csv::Error>,
+{
+    let file = std::fs::File::create(path)?;
+    write(file)
+}
+
+/// Applies the `--option <value>` pairs that follow the input file.
+fn apply_options(engine: &mut TransactionEngine, options: &[String]) -> Result<Reports, Box<dyn std::error::Error>> {
+    let mut reports = Reports::default();
+    let mut fee_schedule = false;
+    let mut iter = options.iter();
+
+    while let Some(option) = iter.next() {
+        let value = iter
+            .next()
+            .ok_or_else(|| format!("Missing value for {}", option))?;
+
+        match option.as_str() {
+            "--max-amount" => {
+                let policy = &engine.validation;
+                engine.validation = ValidationPolicy::new(value.parse()?, policy.max_decimal_places(), policy.zero_amounts)
+                    .map_err(|e| e.to_string())?;
+            }
+            "--max-decimals" => {
+                let policy = &engine.validation;
+                engine.validation = ValidationPolicy::new(policy.max_amount(), value.parse()?, policy.zero_amounts)
+                    .map_err(|e| e.to_string())?;
+            }
+            "--zero-amounts" => engine.validation.zero_amounts = value.parse()?,
+            "--rates" => engine.rates = RateTable::from_path(value)?,
+            "--authorization-expiry" => engine.authorization_expiry = Some(value.parse()?),
+            "--authorizations" => reports.authorizations = Some(value.clone()),
+            "--fee-schedule" => {
+                engine.fees.load_rules(value)?;
+                fee_schedule = true;
+            }
+            "--client-tiers" => engine.fees.load_tiers(value)?,
+            "--house-account" => {
+                let house_account: u16 = value.parse()?;
+                if house_account == 0 {
+                    return Err(format!("Invalid house account: {}", value).into());
+                }
+                engine.fees.house_account = Some(house_account);
+            }
+            "--fees" => {
+                engine.keep_fee_lines();
+                reports.fees = Some(value.clone());
+            }
+            "--trial-balance" => reports.trial_balance = Some(value.clone()),
+            "--journal" => {
+                engine.keep_journal();
+                reports.journal = Some(value.clone());
+            }
+            "--limits" => engine.limits.load(value)?,
+            "--limit-window" => {
+                let window: u64 = value.parse()?;
+                if window == 0 {
+                    return Err(format!("Invalid limit window: {}", value).into());
+                }
+                engine.limits.window = window;
+            }
+            "--limit-period" => {
+                let period = value
+                    .parse()
+                    .ok()
+                    .filter(|seconds| *seconds > 0)
+                    .and_then(TimeDelta::try_seconds)
+                    .ok_or_else(|| format!("Invalid limit period: {}", value))?;
+                engine.limits.period = Some(period);
+            }
+            "--limit-breach" => engine.limits.on_breach = value.parse()?,
+            "--credit-limits" => engine.credit = CreditLimits::from_path(value)?,
+            "--timestamp-tolerance" => {
+                engine.time.tolerance = value
+                    .parse()
+                    .ok()
+                    .filter(|seconds| *seconds >= 0)
+                    .and_then(TimeDelta::try_seconds)
+                    .ok_or_else(|| format!("Invalid timestamp tolerance: {}", value))?;
+            }
+            "--dispute-window" => {
+                let window = value
+                    .parse()
+                    .ok()
+                    .filter(|days| *days >= 0)
+                    .and_then(TimeDelta::try_days)
+                    .ok_or_else(|| format!("Invalid dispute window: {}", value))?;
+                engine.time.dispute_window = Some(window);
+            }
+            _ => return Err(format!("Unknown option {}", option).into()),
+        }
+    }
+
+    // Fees are credited somewhere, or not charged at all
+    if fee_schedule && engine.fees.house_account.is_none() {
+        return Err("--fee-schedule requires --house-account".into());
+    }
+
+    Ok(reports)
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args: Vec<String> = env::args().collect();
     run_program(args).await
-}
\ No newline at end of file
+}
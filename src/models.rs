@@ -1,8 +1,12 @@
-use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
 use std::str::FromStr;
 
+use crate::journal::EventKind;
+
 //Enum for transaction types, ensuring type safety
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum TransactionType {
     Deposit,
@@ -10,6 +14,12 @@ pub enum TransactionType {
     Dispute,
     Resolve,
     Chargeback,
+    Convert,
+    Transfer,
+    Authorize,
+    Capture,
+    Void,
+    Refund,
 }
 
 impl FromStr for TransactionType {
@@ -22,12 +32,63 @@ impl FromStr for TransactionType {
             "dispute" => Ok(TransactionType::Dispute),
             "resolve" => Ok(TransactionType::Resolve),
             "chargeback" => Ok(TransactionType::Chargeback),
+            "convert" => Ok(TransactionType::Convert),
+            "transfer" => Ok(TransactionType::Transfer),
+            "authorize" => Ok(TransactionType::Authorize),
+            "capture" => Ok(TransactionType::Capture),
+            "void" => Ok(TransactionType::Void),
+            "refund" => Ok(TransactionType::Refund),
             _ => Err("Invalid transaction type"),
         }
     }
 }
 
-#[derive(Debug, Deserialize)]
+impl TransactionType {
+    /// Whether a row of this type brings a new transaction id, rather than acting on an
+    /// earlier one.
+    pub fn introduces_id(&self) -> bool {
+        !matches!(
+            self,
+            TransactionType::Dispute
+                | TransactionType::Resolve
+                | TransactionType::Chargeback
+                | TransactionType::Capture
+                | TransactionType::Void
+        )
+    }
+}
+
+impl fmt::Display for TransactionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TransactionType::Deposit => "deposit",
+            TransactionType::Withdrawal => "withdrawal",
+            TransactionType::Dispute => "dispute",
+            TransactionType::Resolve => "resolve",
+            TransactionType::Chargeback => "chargeback",
+            TransactionType::Convert => "convert",
+            TransactionType::Transfer => "transfer",
+            TransactionType::Authorize => "authorize",
+            TransactionType::Capture => "capture",
+            TransactionType::Void => "void",
+            TransactionType::Refund => "refund",
+        };
+        f.write_str(name)
+    }
+}
+
+/// ISO 4217 style currency code, e.g. `EUR`.
+pub type Currency = String;
+
+/// Point in time a transaction happened, read from an RFC 3339 `timestamp` column.
+pub type Timestamp = DateTime<Utc>;
+
+/// Whether `code` is three uppercase ASCII letters.
+pub fn is_valid_currency(code: &str) -> bool {
+    code.len() == 3 && code.bytes().all(|b| b.is_ascii_uppercase())
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Transaction {
     #[serde(rename = "type")]
     pub tx_type: TransactionType,
@@ -35,11 +96,25 @@ pub struct Transaction {
     pub client_id: u16,
     #[serde(rename = "tx")]
     pub tx_id: u32,
-    #[serde(deserialize_with = "deserialize_option_float_with_precision")]
     pub amount: Option<f64>,
+    /// Optional column; rows without it use the client's default (unlabelled) balance.
+    #[serde(default)]
+    pub currency: Option<Currency>,
+    /// Currency credited by a conversion; only valid on `convert` rows.
+    #[serde(default)]
+    pub to_currency: Option<Currency>,
+    /// Client credited by a transfer; only valid on `transfer` rows.
+    #[serde(default)]
+    pub to_client: Option<u16>,
+    /// Deposit a refund gives money back from; only valid on `refund` rows.
+    #[serde(default)]
+    pub ref_tx: Option<u32>,
+    /// Optional column; rows without it are not subject to time-based rules.
+    #[serde(default)]
+    pub timestamp: Option<Timestamp>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct ClientAccount {
     #[serde(rename = "client")]
     pub client_id: u16,
@@ -52,26 +127,508 @@ pub struct ClientAccount {
     pub locked: bool,
 }
 
+impl ClientAccount {
+    /// An empty, unlocked account.
+    pub fn new(client_id: u16) -> Self {
+        Self {
+            client_id,
+            available: 0.0,
+            held: 0.0,
+            total: 0.0,
+            locked: false,
+        }
+    }
+}
+
+/// Output row keyed by (client, currency), used once any transaction names a currency.
+/// The default currency is written as an empty `currency` field.
+#[derive(Debug, Serialize)]
+pub struct CurrencyAccountRow<'a> {
+    #[serde(rename = "client")]
+    pub client_id: u16,
+    pub currency: &'a str,
+    #[serde(serialize_with = "serialize_float_with_precision")]
+    pub available: f64,
+    #[serde(serialize_with = "serialize_float_with_precision")]
+    pub held: f64,
+    #[serde(serialize_with = "serialize_float_with_precision")]
+    pub total: f64,
+    pub locked: bool,
+}
+
+impl<'a> CurrencyAccountRow<'a> {
+    pub fn new(currency: Option<&'a str>, account: &ClientAccount) -> Self {
+        Self {
+            client_id: account.client_id,
+            currency: currency.unwrap_or_default(),
+            available: account.available,
+            held: account.held,
+            total: account.total,
+            locked: account.locked,
+        }
+    }
+}
+
+/// Output row used once any credit limit is configured: the balance followed by its
+/// credit limit and the headroom left, i.e. how much more may still be withdrawn.
+/// `currency` is only written for multi-currency output.
+#[derive(Debug, Serialize)]
+pub struct CreditAccountRow<'a> {
+    #[serde(rename = "client")]
+    pub client_id: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<&'a str>,
+    #[serde(serialize_with = "serialize_float_with_precision")]
+    pub available: f64,
+    #[serde(serialize_with = "serialize_float_with_precision")]
+    pub held: f64,
+    #[serde(serialize_with = "serialize_float_with_precision")]
+    pub total: f64,
+    pub locked: bool,
+    #[serde(serialize_with = "serialize_float_with_precision")]
+    pub credit_limit: f64,
+    #[serde(serialize_with = "serialize_float_with_precision")]
+    pub headroom: f64,
+}
+
+impl<'a> CreditAccountRow<'a> {
+    pub fn new(currency: Option<&'a str>, account: &ClientAccount, credit_limit: f64) -> Self {
+        Self {
+            client_id: account.client_id,
+            currency,
+            available: account.available,
+            held: account.held,
+            total: account.total,
+            locked: account.locked,
+            credit_limit,
+            headroom: account.available + credit_limit,
+        }
+    }
+}
+
+/// Output row of the outstanding authorizations report.
+#[derive(Debug, Serialize)]
+pub struct AuthorizationRow<'a> {
+    #[serde(rename = "tx")]
+    pub tx_id: u32,
+    #[serde(rename = "client")]
+    pub client_id: u16,
+    pub currency: &'a str,
+    #[serde(serialize_with = "serialize_float_with_precision")]
+    pub amount: f64,
+}
+
+/// Output row of the fees report.
+#[derive(Debug, Serialize)]
+pub struct FeeRow<'a> {
+    #[serde(rename = "tx")]
+    pub tx_id: u32,
+    #[serde(rename = "client")]
+    pub client_id: u16,
+    #[serde(rename = "type")]
+    pub tx_type: String,
+    pub currency: &'a str,
+    #[serde(serialize_with = "serialize_float_with_precision")]
+    pub amount: f64,
+}
+
+/// Output row of the journal report.
+#[derive(Debug, Serialize)]
+pub struct JournalRow<'a> {
+    pub event: EventKind,
+    #[serde(rename = "client")]
+    pub client_id: u16,
+    pub currency: &'a str,
+    #[serde(rename = "tx")]
+    pub tx_id: u32,
+    #[serde(serialize_with = "serialize_float_with_precision")]
+    pub amount: f64,
+}
+
+/// Output row of the trial balance report.
+#[derive(Debug, Serialize)]
+pub struct TrialBalanceRow<'a> {
+    pub account: &'a str,
+    /// Empty for the engine's own accounts.
+    pub client: Option<u16>,
+    pub currency: &'a str,
+    #[serde(serialize_with = "serialize_float_with_precision")]
+    pub balance: f64,
+}
+
 // Serializes a floating-point value with the required precision.
-fn serialize_float_with_precision<S>(value: &f64, serializer: S) -> Result<S::Ok, S::Error>
+pub(crate) fn serialize_float_with_precision<S>(value: &f64, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
     serializer.serialize_str(&format!("{:.4}", value))
 }
 
-// Deserializes a floating-point value with the required precision.
-fn deserialize_option_float_with_precision<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+// Like `serialize_float_with_precision`, leaving a missing value empty.
+pub(crate) fn serialize_optional_float_with_precision<S>(value: &Option<f64>, serializer: S) -> Result<S::Ok, S::Error>
 where
-    D: Deserializer<'de>,
+    S: Serializer,
 {
-    let value: Option<f64> = Option::deserialize(deserializer)?;
-    
-    if let Some(v) = value {
-        let factor = 10f64.powi(4);
-        let rounded = (v * factor).round() / factor;
-        Ok(Some(rounded))
-    } else {
-        Ok(None)
-    }
-}
\ No newline at end of file
+    match value {
+        Some(value) => serialize_float_with_precision(value, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Whether amounts of exactly zero are accepted.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ZeroAmountPolicy {
+    #[default]
+    Reject,
+    Allow,
+}
+
+impl FromStr for ZeroAmountPolicy {
+    type Err = &'static str;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.to_lowercase().as_str() {
+            "reject" => Ok(ZeroAmountPolicy::Reject),
+            "allow" => Ok(ZeroAmountPolicy::Allow),
+            _ => Err("Invalid zero amount policy, expected 'allow' or 'reject'"),
+        }
+    }
+}
+
+/// Most decimal places a policy may allow; accounts are written with four.
+pub const MAX_DECIMAL_PLACES: u32 = 4;
+
+/// Limits applied to transaction amounts before they reach the engine. The amount
+/// limits are checked when the policy is built, see [`ValidationPolicy::new`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationPolicy {
+    max_amount: f64,
+    max_decimal_places: u32,
+    pub zero_amounts: ZeroAmountPolicy,
+}
+
+impl ValidationPolicy {
+    /// Builds a policy, refusing a `max_amount` that is not a positive finite number
+    /// and more than [`MAX_DECIMAL_PLACES`] decimal places.
+    pub fn new(max_amount: f64, max_decimal_places: u32, zero_amounts: ZeroAmountPolicy) -> Result<Self, PolicyError> {
+        if !max_amount.is_finite() || max_amount <= 0.0 {
+            return Err(PolicyError::InvalidMaxAmount(max_amount));
+        }
+        if max_decimal_places > MAX_DECIMAL_PLACES {
+            return Err(PolicyError::InvalidMaxDecimalPlaces(max_decimal_places));
+        }
+        Ok(Self { max_amount, max_decimal_places, zero_amounts })
+    }
+
+    /// Largest amount accepted for a single transaction.
+    pub fn max_amount(&self) -> f64 {
+        self.max_amount
+    }
+
+    /// Most decimal places an amount may carry.
+    pub fn max_decimal_places(&self) -> u32 {
+        self.max_decimal_places
+    }
+}
+
+impl Default for ValidationPolicy {
+    fn default() -> Self {
+        Self {
+            // Below this bound an f64 still represents every four-decimal value exactly
+            max_amount: 100_000_000_000.0,
+            max_decimal_places: MAX_DECIMAL_PLACES,
+            zero_amounts: ZeroAmountPolicy::default(),
+        }
+    }
+}
+
+/// Reasons a [`ValidationPolicy`] cannot be built.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PolicyError {
+    InvalidMaxAmount(f64),
+    InvalidMaxDecimalPlaces(u32),
+}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyError::InvalidMaxAmount(amount) => {
+                write!(f, "Invalid maximum amount: {}, expected a positive number", amount)
+            }
+            PolicyError::InvalidMaxDecimalPlaces(places) => write!(
+                f,
+                "Invalid maximum decimal places: {}, expected 0 to {}",
+                places, MAX_DECIMAL_PLACES
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PolicyError {}
+
+/// Rules that depend on when transactions happened. They only apply to rows that
+/// carry a timestamp.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimePolicy {
+    /// How far a timestamp may go back behind the latest one seen; zero requires
+    /// timestamps that never decrease.
+    pub tolerance: TimeDelta,
+    /// Longest time after a transaction it may still be disputed; `None` for no limit.
+    pub dispute_window: Option<TimeDelta>,
+}
+
+impl Default for TimePolicy {
+    fn default() -> Self {
+        Self {
+            tolerance: TimeDelta::zero(),
+            dispute_window: None,
+        }
+    }
+}
+
+/// Reasons a transaction is rejected before it reaches the engine.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    InvalidClientId,
+    InvalidTransactionId,
+    MissingAmount(TransactionType),
+    UnexpectedAmount(TransactionType),
+    /// Zero or negative amount while zero amounts are rejected.
+    AmountNotPositive(TransactionType),
+    /// Negative amount while zero amounts are allowed.
+    NegativeAmount(TransactionType),
+    NonFiniteAmount,
+    AmountTooLarge { amount: f64, max_amount: f64 },
+    ExcessPrecision { amount: f64, max_decimal_places: u32 },
+    InvalidCurrency(Currency),
+    MissingCurrency(TransactionType),
+    MissingTargetCurrency,
+    UnexpectedTargetCurrency(TransactionType),
+    SameCurrencyConversion(Currency),
+    MissingDestinationClient,
+    UnexpectedDestinationClient(TransactionType),
+    InvalidDestinationClient,
+    SelfTransfer,
+    MissingReferencedTransaction,
+    UnexpectedReferencedTransaction(TransactionType),
+    InvalidReferencedTransaction,
+}
+
+impl ValidationError {
+    /// Stable name of the check that failed, e.g. `missing_amount`.
+    pub fn rule(&self) -> &'static str {
+        match self {
+            ValidationError::InvalidClientId => "invalid_client_id",
+            ValidationError::InvalidTransactionId => "invalid_transaction_id",
+            ValidationError::MissingAmount(_) => "missing_amount",
+            ValidationError::UnexpectedAmount(_) => "unexpected_amount",
+            ValidationError::AmountNotPositive(_) => "amount_not_positive",
+            ValidationError::NegativeAmount(_) => "negative_amount",
+            ValidationError::NonFiniteAmount => "non_finite_amount",
+            ValidationError::AmountTooLarge { .. } => "amount_too_large",
+            ValidationError::ExcessPrecision { .. } => "excess_precision",
+            ValidationError::InvalidCurrency(_) => "invalid_currency",
+            ValidationError::MissingCurrency(_) => "missing_currency",
+            ValidationError::MissingTargetCurrency => "missing_target_currency",
+            ValidationError::UnexpectedTargetCurrency(_) => "unexpected_target_currency",
+            ValidationError::SameCurrencyConversion(_) => "same_currency_conversion",
+            ValidationError::MissingDestinationClient => "missing_destination_client",
+            ValidationError::UnexpectedDestinationClient(_) => "unexpected_destination_client",
+            ValidationError::InvalidDestinationClient => "invalid_destination_client",
+            ValidationError::SelfTransfer => "self_transfer",
+            ValidationError::MissingReferencedTransaction => "missing_referenced_transaction",
+            ValidationError::UnexpectedReferencedTransaction(_) => "unexpected_referenced_transaction",
+            ValidationError::InvalidReferencedTransaction => "invalid_referenced_transaction",
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::InvalidClientId => write!(f, "Client ID must be greater than 0"),
+            ValidationError::InvalidTransactionId => write!(f, "Transaction ID must be greater than 0"),
+            ValidationError::MissingAmount(tx_type) => write!(f, "{:?} transaction requires an amount", tx_type),
+            ValidationError::UnexpectedAmount(tx_type) => {
+                write!(f, "{:?} transaction must not have an amount", tx_type)
+            }
+            ValidationError::AmountNotPositive(tx_type) => write!(f, "{:?} amount must be positive", tx_type),
+            ValidationError::NegativeAmount(tx_type) => write!(f, "{:?} amount must be non-negative", tx_type),
+            ValidationError::NonFiniteAmount => write!(f, "Amount must be a finite number"),
+            ValidationError::AmountTooLarge { amount, max_amount } => {
+                write!(f, "Amount {} exceeds the maximum of {}", amount, max_amount)
+            }
+            ValidationError::ExcessPrecision { amount, max_decimal_places } => write!(
+                f,
+                "Amount {} has more than {} decimal places",
+                amount, max_decimal_places
+            ),
+            ValidationError::InvalidCurrency(code) => {
+                write!(f, "Invalid currency code '{}', expected three uppercase letters", code)
+            }
+            ValidationError::MissingCurrency(tx_type) => write!(f, "{:?} transaction requires a currency", tx_type),
+            ValidationError::MissingTargetCurrency => write!(f, "Convert transaction requires a target currency"),
+            ValidationError::UnexpectedTargetCurrency(tx_type) => {
+                write!(f, "{:?} transaction must not have a target currency", tx_type)
+            }
+            ValidationError::SameCurrencyConversion(code) => {
+                write!(f, "Cannot convert {} into itself", code)
+            }
+            ValidationError::MissingDestinationClient => write!(f, "Transfer transaction requires a destination client"),
+            ValidationError::UnexpectedDestinationClient(tx_type) => {
+                write!(f, "{:?} transaction must not have a destination client", tx_type)
+            }
+            ValidationError::InvalidDestinationClient => write!(f, "Destination client ID must be greater than 0"),
+            ValidationError::SelfTransfer => write!(f, "Cannot transfer to the same client"),
+            ValidationError::MissingReferencedTransaction => {
+                write!(f, "Refund transaction requires a referenced transaction")
+            }
+            ValidationError::UnexpectedReferencedTransaction(tx_type) => {
+                write!(f, "{:?} transaction must not have a referenced transaction", tx_type)
+            }
+            ValidationError::InvalidReferencedTransaction => {
+                write!(f, "Referenced transaction ID must be greater than 0")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl Transaction {
+    /// Builds a transaction in the default currency.
+    pub fn new(tx_type: TransactionType, client_id: u16, tx_id: u32, amount: Option<f64>) -> Self {
+        Self {
+            tx_type,
+            client_id,
+            tx_id,
+            amount,
+            currency: None,
+            to_currency: None,
+            to_client: None,
+            ref_tx: None,
+            timestamp: None,
+        }
+    }
+
+    /// Checks the transaction's fields against the given policy.
+    pub fn validate(&self, policy: &ValidationPolicy) -> Result<(), ValidationError> {
+        // Validate client_id
+        if self.client_id == 0 {
+            return Err(ValidationError::InvalidClientId);
+        }
+
+        // Validate tx_id
+        if self.tx_id == 0 {
+            return Err(ValidationError::InvalidTransactionId);
+        }
+
+        // Validate the currency codes when given
+        for currency in self.currency.iter().chain(&self.to_currency) {
+            if !is_valid_currency(currency) {
+                return Err(ValidationError::InvalidCurrency(currency.clone()));
+            }
+        }
+
+        // Only conversions name a target currency
+        if self.tx_type != TransactionType::Convert && self.to_currency.is_some() {
+            return Err(ValidationError::UnexpectedTargetCurrency(self.tx_type));
+        }
+
+        // Only transfers name a destination client
+        if self.tx_type != TransactionType::Transfer && self.to_client.is_some() {
+            return Err(ValidationError::UnexpectedDestinationClient(self.tx_type));
+        }
+
+        // Only refunds name a referenced transaction
+        if self.tx_type != TransactionType::Refund && self.ref_tx.is_some() {
+            return Err(ValidationError::UnexpectedReferencedTransaction(self.tx_type));
+        }
+
+        // Validate fields based on transaction type
+        match self.tx_type {
+            TransactionType::Deposit | TransactionType::Withdrawal | TransactionType::Authorize => {
+                // Deposit, Withdrawal and Authorize must have a valid amount
+                let amount = self.amount.ok_or(ValidationError::MissingAmount(self.tx_type))?;
+                validate_amount(self.tx_type, amount, policy)
+            }
+            TransactionType::Convert => {
+                // Convert moves an amount between two different named currencies
+                let amount = self.amount.ok_or(ValidationError::MissingAmount(self.tx_type))?;
+                let from = self.currency.as_ref().ok_or(ValidationError::MissingCurrency(self.tx_type))?;
+                let to = self.to_currency.as_ref().ok_or(ValidationError::MissingTargetCurrency)?;
+                if from == to {
+                    return Err(ValidationError::SameCurrencyConversion(from.clone()));
+                }
+                validate_amount(self.tx_type, amount, policy)
+            }
+            TransactionType::Transfer => {
+                // Transfer moves an amount to another, valid client
+                let amount = self.amount.ok_or(ValidationError::MissingAmount(self.tx_type))?;
+                match self.to_client {
+                    None => return Err(ValidationError::MissingDestinationClient),
+                    Some(0) => return Err(ValidationError::InvalidDestinationClient),
+                    Some(to_client) if to_client == self.client_id => return Err(ValidationError::SelfTransfer),
+                    Some(_) => {}
+                }
+                validate_amount(self.tx_type, amount, policy)
+            }
+            TransactionType::Refund => {
+                // Refund gives back an amount of an earlier transaction
+                let amount = self.amount.ok_or(ValidationError::MissingAmount(self.tx_type))?;
+                match self.ref_tx {
+                    None => return Err(ValidationError::MissingReferencedTransaction),
+                    Some(0) => return Err(ValidationError::InvalidReferencedTransaction),
+                    Some(_) => {}
+                }
+                validate_amount(self.tx_type, amount, policy)
+            }
+            TransactionType::Void => {
+                // Void always releases the whole authorization
+                if self.amount.is_some() {
+                    return Err(ValidationError::UnexpectedAmount(self.tx_type));
+                }
+                Ok(())
+            }
+            TransactionType::Dispute
+            | TransactionType::Resolve
+            | TransactionType::Chargeback
+            | TransactionType::Capture => {
+                // An amount makes the dispute, resolve, chargeback or capture partial
+                match self.amount {
+                    Some(amount) => validate_amount(self.tx_type, amount, policy),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+fn validate_amount(tx_type: TransactionType, amount: f64, policy: &ValidationPolicy) -> Result<(), ValidationError> {
+    // NaN fails every comparison, so it has to be caught first
+    if !amount.is_finite() {
+        return Err(ValidationError::NonFiniteAmount);
+    }
+
+    match policy.zero_amounts {
+        ZeroAmountPolicy::Reject if amount <= 0.0 => return Err(ValidationError::AmountNotPositive(tx_type)),
+        ZeroAmountPolicy::Allow if amount < 0.0 => return Err(ValidationError::NegativeAmount(tx_type)),
+        _ => {}
+    }
+
+    if amount > policy.max_amount() {
+        return Err(ValidationError::AmountTooLarge { amount, max_amount: policy.max_amount() });
+    }
+
+    // Allow for the representation error of the scaled value, not for extra digits
+    let scaled = amount * 10f64.powi(policy.max_decimal_places() as i32);
+    let tolerance = (4.0 * f64::EPSILON * scaled.abs()).max(1e-6);
+    if (scaled - scaled.round()).abs() > tolerance {
+        return Err(ValidationError::ExcessPrecision {
+            amount,
+            max_decimal_places: policy.max_decimal_places(),
+        });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+
+use serde::Deserialize;
+
+use crate::models::{is_valid_currency, Currency};
+
+/// A row of the rate table: one unit of `from` buys `rate` units of `to`.
+#[derive(Debug, Deserialize)]
+struct RateRow {
+    from: Currency,
+    to: Currency,
+    rate: f64,
+}
+
+/// Why a rate table could not be loaded.
+#[derive(Debug)]
+pub enum RateError {
+    Csv(csv::Error),
+    InvalidCurrency(Currency),
+    InvalidRate { from: Currency, to: Currency, rate: f64 },
+    DuplicateRate { from: Currency, to: Currency },
+}
+
+impl fmt::Display for RateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RateError::Csv(e) => write!(f, "Error reading rate table: {}", e),
+            RateError::InvalidCurrency(code) => {
+                write!(f, "Invalid currency code '{}' in rate table", code)
+            }
+            RateError::InvalidRate { from, to, rate } => {
+                write!(f, "Rate {} from {} to {} must be a positive number", rate, from, to)
+            }
+            RateError::DuplicateRate { from, to } => {
+                write!(f, "Rate from {} to {} is listed more than once", from, to)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RateError {}
+
+impl From<csv::Error> for RateError {
+    fn from(e: csv::Error) -> Self {
+        RateError::Csv(e)
+    }
+}
+
+/// Exchange rates used by `convert` transactions, loaded once at startup from a
+/// `from,to,rate` CSV file. Only listed pairs convert; inverse rates are not derived.
+#[derive(Debug, Clone, Default)]
+pub struct RateTable {
+    rates: HashMap<(Currency, Currency), f64>,
+}
+
+impl RateTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads a rate table from a CSV file.
+    pub fn from_path(path: &str) -> Result<Self, RateError> {
+        let file = std::fs::File::open(path).map_err(csv::Error::from)?;
+        Self::from_reader(file)
+    }
+
+    /// Loads a rate table from any CSV source.
+    pub fn from_reader<R: io::Read>(reader: R) -> Result<Self, RateError> {
+        let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(reader);
+        let mut table = Self::new();
+
+        for row in reader.deserialize() {
+            let row: RateRow = row?;
+            table.insert(&row.from, &row.to, row.rate)?;
+        }
+
+        Ok(table)
+    }
+
+    /// Adds the rate for converting `from` into `to`.
+    pub fn insert(&mut self, from: &str, to: &str, rate: f64) -> Result<(), RateError> {
+        for code in [from, to] {
+            if !is_valid_currency(code) {
+                return Err(RateError::InvalidCurrency(code.to_string()));
+            }
+        }
+        if !rate.is_finite() || rate <= 0.0 {
+            return Err(RateError::InvalidRate { from: from.to_string(), to: to.to_string(), rate });
+        }
+
+        let key = (from.to_string(), to.to_string());
+        if self.rates.contains_key(&key) {
+            return Err(RateError::DuplicateRate { from: key.0, to: key.1 });
+        }
+        self.rates.insert(key, rate);
+
+        Ok(())
+    }
+
+    /// The rate for converting `from` into `to`, if the table lists one.
+    pub fn rate(&self, from: &str, to: &str) -> Option<f64> {
+        self.rates.get(&(from.to_string(), to.to_string())).copied()
+    }
+}
+
+/// Amount credited for converting `amount` at `rate`, rounded half away from zero to the
+/// four decimal places balances are reported with.
+pub fn convert_amount(amount: f64, rate: f64) -> f64 {
+    (amount * rate * 10_000.0).round() / 10_000.0
+}
@@ -0,0 +1,144 @@
+use std::collections::BTreeMap;
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{round_to, TransactionEngine};
+use crate::models::{ClientAccount, Currency};
+
+/// A balance someone else computed, in the columns the engine writes its accounts in.
+/// `currency` is optional; without it, or when empty, the row is the default balance.
+/// Other columns, like `credit_limit`, are ignored.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ExpectedAccount {
+    #[serde(rename = "client")]
+    pub client_id: u16,
+    #[serde(default)]
+    pub currency: Option<Currency>,
+    pub available: f64,
+    pub held: f64,
+    pub total: f64,
+    pub locked: bool,
+}
+
+/// Reads an expected accounts CSV.
+pub fn read_expected<R: io::Read>(reader: R) -> Result<Vec<ExpectedAccount>, csv::Error> {
+    csv::Reader::from_reader(reader).deserialize().collect()
+}
+
+/// How the engine's accounts and the expected ones disagree.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiscrepancyKind {
+    /// An expected balance the engine does not have.
+    Missing,
+    /// A balance the engine has that was not expected.
+    Extra,
+    /// `available`, `held` or `total` differ by more than the tolerance.
+    Balance,
+    /// One side is locked and the other is not.
+    Lock,
+}
+
+/// One disagreement, written as a CSV row `kind,client,currency,field,expected,actual`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Discrepancy {
+    pub kind: DiscrepancyKind,
+    #[serde(rename = "client")]
+    pub client_id: u16,
+    /// Empty for the default currency.
+    pub currency: Currency,
+    /// The column that differs. It is empty for missing and extra balances, and so are
+    /// `expected` and `actual`.
+    pub field: &'static str,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// Compares every balance of the engine with the expected ones. Amounts are compared
+/// once rounded to the engine's decimal places, and differ when they are more than
+/// `tolerance` apart. Discrepancies are ordered by client, then currency. The house
+/// account collects the engine's own fees, so it is left out on both sides.
+pub fn reconcile(engine: &TransactionEngine, expected: &[ExpectedAccount], tolerance: f64) -> Vec<Discrepancy> {
+    let places = engine.validation.max_decimal_places();
+    let house_account = engine.fees.house_account;
+
+    let expected: BTreeMap<(u16, Currency), &ExpectedAccount> = expected
+        .iter()
+        .filter(|account| Some(account.client_id) != house_account)
+        .map(|account| ((account.client_id, account.currency.clone().unwrap_or_default()), account))
+        .collect();
+    let actual: BTreeMap<(u16, Currency), &ClientAccount> = engine
+        .balances()
+        .filter(|(_, account)| Some(account.client_id) != house_account)
+        .map(|(currency, account)| ((account.client_id, currency.unwrap_or_default().to_string()), account))
+        .collect();
+
+    let mut keys: Vec<&(u16, Currency)> = expected.keys().chain(actual.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    let mut discrepancies = Vec::new();
+    for key in keys {
+        let (client_id, currency) = key.clone();
+        let discrepancy = |kind, field, expected: String, actual: String| Discrepancy {
+            kind,
+            client_id,
+            currency: currency.clone(),
+            field,
+            expected,
+            actual,
+        };
+
+        let (expected, actual) = match (expected.get(key), actual.get(key)) {
+            (Some(expected), Some(actual)) => (expected, actual),
+            (Some(_), None) => {
+                discrepancies.push(discrepancy(DiscrepancyKind::Missing, "", String::new(), String::new()));
+                continue;
+            }
+            (None, Some(_)) => {
+                discrepancies.push(discrepancy(DiscrepancyKind::Extra, "", String::new(), String::new()));
+                continue;
+            }
+            (None, None) => continue,
+        };
+
+        let fields = [
+            ("available", expected.available, actual.available),
+            ("held", expected.held, actual.held),
+            ("total", expected.total, actual.total),
+        ];
+        for (field, expected, actual) in fields {
+            if round_to((round_to(expected, places) - round_to(actual, places)).abs(), places) > tolerance {
+                discrepancies.push(discrepancy(
+                    DiscrepancyKind::Balance,
+                    field,
+                    format!("{:.4}", expected),
+                    format!("{:.4}", actual),
+                ));
+            }
+        }
+        if expected.locked != actual.locked {
+            discrepancies.push(discrepancy(
+                DiscrepancyKind::Lock,
+                "locked",
+                expected.locked.to_string(),
+                actual.locked.to_string(),
+            ));
+        }
+    }
+    discrepancies
+}
+
+/// Writes the discrepancies as CSV.
+pub fn write_discrepancies<W: io::Write>(discrepancies: &[Discrepancy], writer: W) -> Result<(), csv::Error> {
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    if discrepancies.is_empty() {
+        csv_writer.write_record(["kind", "client", "currency", "field", "expected", "actual"])?;
+    }
+    for discrepancy in discrepancies {
+        csv_writer.serialize(discrepancy)?;
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,195 @@
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use indicatif::ProgressBar;
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+
+use crate::engine::TransactionEngine;
+use crate::models::{ClientAccount, Transaction};
+use crate::utils::{process_reader, ProcessingReport};
+
+/// An engine shared by every request; each request holds it for as long as it runs.
+pub type SharedEngine = Arc<Mutex<TransactionEngine>>;
+
+/// Why a request was refused as a whole.
+#[derive(Debug)]
+pub enum ApiError {
+    /// The body could not be read as transactions.
+    Malformed(String),
+    UnsupportedMediaType(String),
+    AccountNotFound(u16),
+}
+
+impl ApiError {
+    fn status(&self) -> StatusCode {
+        match self {
+            ApiError::Malformed(_) => StatusCode::BAD_REQUEST,
+            ApiError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::AccountNotFound(_) => StatusCode::NOT_FOUND,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            ApiError::Malformed(_) => "malformed",
+            ApiError::UnsupportedMediaType(_) => "unsupported_media_type",
+            ApiError::AccountNotFound(_) => "account_not_found",
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Malformed(e) => write!(f, "Error reading transactions: {}", e),
+            ApiError::UnsupportedMediaType(content_type) => write!(
+                f,
+                "Unsupported content type '{}', expected application/json or text/csv",
+                content_type
+            ),
+            ApiError::AccountNotFound(client_id) => write!(f, "Client {} has no account", client_id),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// Body of every error response.
+#[derive(Debug, Serialize)]
+struct ErrorBody {
+    error: &'static str,
+    message: String,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ErrorBody { error: self.kind(), message: self.to_string() };
+        (self.status(), Json(body)).into_response()
+    }
+}
+
+/// A JSON body: one transaction or a batch of them.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum TransactionBatch {
+    One(Transaction),
+    Many(Vec<Transaction>),
+}
+
+/// One balance of a client; `currency` is null for the default balance.
+#[derive(Debug, Serialize)]
+struct AccountView<'a> {
+    #[serde(flatten)]
+    account: &'a ClientAccount,
+    currency: Option<&'a str>,
+}
+
+/// Routes of the HTTP API:
+/// - `POST /transactions`: a JSON transaction or array, or a CSV file with a header row.
+/// - `GET /accounts`: every balance.
+/// - `GET /accounts/{client}`: the balances of one client.
+pub fn router(engine: SharedEngine) -> Router {
+    Router::new()
+        .route("/transactions", post(post_transactions))
+        .route("/accounts", get(get_accounts))
+        .route("/accounts/{client}", get(get_account))
+        .with_state(engine)
+}
+
+/// The `GET` routes of `router` only, for when rows must not come from anywhere else.
+pub fn read_only_router(engine: SharedEngine) -> Router {
+    Router::new()
+        .route("/accounts", get(get_accounts))
+        .route("/accounts/{client}", get(get_account))
+        .with_state(engine)
+}
+
+/// Serves the API on `listener` until `shutdown` completes.
+pub async fn serve<F>(listener: TcpListener, engine: SharedEngine, shutdown: F) -> io::Result<()>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    axum::serve(listener, router(engine)).with_graceful_shutdown(shutdown).await
+}
+
+/// Serves the read-only API on `listener` until `shutdown` completes.
+pub async fn serve_read_only<F>(listener: TcpListener, engine: SharedEngine, shutdown: F) -> io::Result<()>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    axum::serve(listener, read_only_router(engine)).with_graceful_shutdown(shutdown).await
+}
+
+/// Processes the posted transactions and reports the outcome of each row. The response
+/// is `200 OK` when every row was accepted and `422 Unprocessable Entity` otherwise. A
+/// JSON body that cannot be read is refused as a whole, before any row is applied.
+async fn post_transactions(
+    State(engine): State<SharedEngine>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<(StatusCode, Json<ProcessingReport>), ApiError> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let mut engine = engine.lock().await;
+
+    let report = match content_type.split(';').next().unwrap_or_default().trim() {
+        "application/json" => {
+            let batch: TransactionBatch =
+                serde_json::from_slice(&body).map_err(|e| ApiError::Malformed(e.to_string()))?;
+            let transactions = match batch {
+                TransactionBatch::One(transaction) => vec![transaction],
+                TransactionBatch::Many(transactions) => transactions,
+            };
+
+            let mut report = ProcessingReport::default();
+            for transaction in transactions {
+                report.handle(&mut engine, transaction, None);
+            }
+            report
+        }
+        // A row with an unreadable field is reported as malformed, as with `process_csv`;
+        // only a body whose header row cannot be read is refused before anything is applied
+        "text/csv" => process_reader(&body[..], &mut engine, &ProgressBar::hidden())
+            .await
+            .map_err(|e| ApiError::Malformed(e.to_string()))?,
+        other => return Err(ApiError::UnsupportedMediaType(other.to_string())),
+    };
+
+    let status = if report.rejections.is_empty() { StatusCode::OK } else { StatusCode::UNPROCESSABLE_ENTITY };
+    Ok((status, Json(report)))
+}
+
+async fn get_accounts(State(engine): State<SharedEngine>) -> Response {
+    let engine = engine.lock().await;
+    let accounts: Vec<_> = engine
+        .balances()
+        .map(|(currency, account)| AccountView { account, currency })
+        .collect();
+    Json(accounts).into_response()
+}
+
+async fn get_account(State(engine): State<SharedEngine>, Path(client_id): Path<u16>) -> Result<Response, ApiError> {
+    let engine = engine.lock().await;
+    let accounts: Vec<_> = engine
+        .balances()
+        .filter(|(_, account)| account.client_id == client_id)
+        .map(|(currency, account)| AccountView { account, currency })
+        .collect();
+
+    if accounts.is_empty() {
+        return Err(ApiError::AccountNotFound(client_id));
+    }
+    Ok(Json(accounts).into_response())
+}
@@ -0,0 +1,408 @@
+use std::collections::HashSet;
+use std::io;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::engine::{EngineError, FeeLine, TransactionEngine};
+use crate::journal::{self, Balances, Event, EventKind};
+use crate::models::{
+    serialize_float_with_precision, serialize_optional_float_with_precision, ClientAccount, Currency, Timestamp,
+    Transaction, TransactionType,
+};
+use crate::utils::RejectionKind;
+
+/// How a statement is written.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum StatementFormat {
+    #[default]
+    Text,
+    Csv,
+    Json,
+}
+
+impl FromStr for StatementFormat {
+    type Err = &'static str;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.to_lowercase().as_str() {
+            "text" => Ok(StatementFormat::Text),
+            "csv" => Ok(StatementFormat::Csv),
+            "json" => Ok(StatementFormat::Json),
+            _ => Err("Invalid statement format, expected 'text', 'csv' or 'json'"),
+        }
+    }
+}
+
+/// Rows a statement lists. Every bound is inclusive and optional; once a time bound is
+/// set, rows without a timestamp are left out.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatementRange {
+    pub from_tx: Option<u32>,
+    pub to_tx: Option<u32>,
+    pub from_time: Option<Timestamp>,
+    pub to_time: Option<Timestamp>,
+}
+
+impl StatementRange {
+    pub fn contains(&self, transaction: &Transaction) -> bool {
+        let tx_id = transaction.tx_id;
+        if self.from_tx.is_some_and(|from| tx_id < from) || self.to_tx.is_some_and(|to| tx_id > to) {
+            return false;
+        }
+        if self.from_time.is_none() && self.to_time.is_none() {
+            return true;
+        }
+        transaction.timestamp.is_some_and(|time| {
+            self.from_time.is_none_or(|from| time >= from) && self.to_time.is_none_or(|to| time <= to)
+        })
+    }
+}
+
+/// What a statement line stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatementEntry {
+    /// The row itself, accepted or not.
+    Row,
+    /// A fee the row charged, paid by the client or credited to it as the house account.
+    Fee,
+    /// An authorization of the client released because it expired while the row was handled.
+    Expiry,
+}
+
+impl StatementEntry {
+    fn as_str(&self) -> &'static str {
+        match self {
+            StatementEntry::Row => "row",
+            StatementEntry::Fee => "fee",
+            StatementEntry::Expiry => "expiry",
+        }
+    }
+}
+
+/// One change to a client's balance as seen by the client, with the balance it left
+/// behind. A row touching several balances of the client gets one line per balance,
+/// and every fee or expiry it caused gets its own line.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatementLine {
+    pub line: Option<u64>,
+    /// The transaction the change belongs to: the row's own, or the expired authorization.
+    #[serde(rename = "tx")]
+    pub tx_id: u32,
+    #[serde(rename = "type")]
+    pub tx_type: TransactionType,
+    pub entry: StatementEntry,
+    /// Client named by the row; another client for transfers to this one.
+    #[serde(rename = "client")]
+    pub client_id: u16,
+    /// Currency of the balance; empty for the default currency.
+    pub currency: Currency,
+    /// Amount of the row, or of the fee or released authorization.
+    #[serde(serialize_with = "serialize_optional_float_with_precision")]
+    pub amount: Option<f64>,
+    pub timestamp: Option<Timestamp>,
+    /// `accepted`, `invalid` or `rejected`.
+    pub status: &'static str,
+    /// Why the row was refused; empty when it was accepted.
+    pub reason: String,
+    #[serde(serialize_with = "serialize_float_with_precision")]
+    pub available: f64,
+    #[serde(serialize_with = "serialize_float_with_precision")]
+    pub held: f64,
+    #[serde(serialize_with = "serialize_float_with_precision")]
+    pub total: f64,
+    pub locked: bool,
+}
+
+impl StatementLine {
+    fn set_balance(&mut self, account: &ClientAccount) {
+        self.available = account.available;
+        self.held = account.held;
+        self.total = account.total;
+        self.locked = account.locked;
+    }
+}
+
+/// Positions of the events behind `fees`, the fees charged by one row. A fee is posted
+/// as the payer's withdrawal directly followed by the house account's deposit, in the
+/// currency and for the amount of its fee line; a fee kept in the fees book has no deposit.
+fn fee_events(events: &[Event], fees: &[FeeLine]) -> HashSet<usize> {
+    let mut positions = HashSet::new();
+    let mut end = events.len();
+    for fee in fees.iter().rev() {
+        let matches = |event: &Event, kind, client_id| {
+            event.kind == kind
+                && event.client_id == client_id
+                && event.tx_id == fee.tx_id
+                && event.currency == fee.currency
+                && event.amount == fee.amount
+        };
+        let Some(house_account) = fee.house_account else {
+            let found = (0..end).rev().find(|&index| matches(&events[index], EventKind::Withdrew, fee.client_id));
+            if let Some(index) = found {
+                positions.insert(index);
+                end = index;
+            }
+            continue;
+        };
+        let found = (0..end.saturating_sub(1)).rev().find(|&index| {
+            matches(&events[index], EventKind::Withdrew, fee.client_id)
+                && matches(&events[index + 1], EventKind::Deposited, house_account)
+        });
+        if let Some(index) = found {
+            positions.extend([index, index + 1]);
+            end = index;
+        }
+    }
+    positions
+}
+
+/// Every operation that touched one client, accepted or not, with the running balances
+/// after each one and what locked the client, if anything did.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Statement {
+    #[serde(rename = "client")]
+    pub client_id: u16,
+    pub lines: Vec<StatementLine>,
+    /// Set by the row that locked the client, even when it is outside the range.
+    pub lock_reason: Option<String>,
+    #[serde(skip)]
+    range: StatementRange,
+}
+
+impl Statement {
+    pub fn new(client_id: u16, range: StatementRange) -> Self {
+        Self { client_id, lines: Vec::new(), lock_reason: None, range }
+    }
+
+    /// Hands a transaction to the engine and records what it did to the client. The
+    /// lines are built from the journal and the fee lines, both kept from the first row on.
+    pub fn handle(
+        &mut self,
+        engine: &mut TransactionEngine,
+        transaction: Transaction,
+        line: Option<u64>,
+    ) -> Result<(), EngineError> {
+        engine.keep_journal();
+        engine.keep_fee_lines();
+        let events_before = engine.journal().map_or(0, |journal| journal.events.len());
+        let fees_before = engine.fee_lines().len();
+        let mut balances = self.balances(engine);
+        let was_locked = engine.is_locked(self.client_id);
+        let in_range = self.range.contains(&transaction);
+        let named = transaction.client_id == self.client_id || transaction.to_client == Some(self.client_id);
+        let row = transaction.clone();
+
+        let result = engine.handle_transaction(transaction);
+
+        // Remember what locked the client; nothing unlocks it again
+        if !was_locked && engine.is_locked(self.client_id) {
+            self.lock_reason = Some(match &result {
+                Ok(()) => format!("Locked by the {} of transaction {}", row.tx_type, row.tx_id),
+                Err(e) => format!("Locked when the {} of transaction {} was refused: {}", row.tx_type, row.tx_id, e),
+            });
+        }
+
+        if !in_range {
+            return result;
+        }
+
+        let (status, reason) = match &result {
+            Ok(()) => ("accepted", String::new()),
+            Err(e) => match RejectionKind::from(e) {
+                RejectionKind::Invalid => ("invalid", e.to_string()),
+                _ => ("rejected", e.to_string()),
+            },
+        };
+
+        // Replay the client's events on its balances, one line per transaction, kind of
+        // entry and currency they belong to, in the order they happened
+        let events = engine.journal().map_or(&[][..], |journal| &journal.events[events_before..]);
+        let fee_events = fee_events(events, &engine.fee_lines()[fees_before..]);
+        let places = engine.validation.max_decimal_places();
+        let first = self.lines.len();
+        let mut last_key = None;
+        for (index, event) in events.iter().enumerate().filter(|(_, event)| event.client_id == self.client_id) {
+            journal::apply(&mut balances.accounts, &mut balances.currency_accounts, event, places);
+            if event.kind == EventKind::AccountLocked {
+                continue;
+            }
+
+            let entry = if fee_events.contains(&index) {
+                StatementEntry::Fee
+            } else if event.tx_id != row.tx_id {
+                StatementEntry::Expiry
+            } else {
+                StatementEntry::Row
+            };
+            let account = balances.account(self.client_id, event.currency.as_deref()).cloned();
+            let key = Some((event.tx_id, entry, event.currency.clone()));
+            if key == last_key {
+                if let (Some(line), Some(account)) = (self.lines.last_mut(), account) {
+                    line.set_balance(&account);
+                }
+                continue;
+            }
+            last_key = key;
+
+            let mut statement_line = match entry {
+                StatementEntry::Row => self.row_line(&row, line, status, &reason),
+                StatementEntry::Fee => StatementLine {
+                    entry,
+                    amount: Some(event.amount),
+                    status: "accepted",
+                    reason: String::new(),
+                    ..self.row_line(&row, line, status, &reason)
+                },
+                StatementEntry::Expiry => self.expiry_line(engine, event, &row, line),
+            };
+            statement_line.currency = event.currency.clone().unwrap_or_default();
+            if let Some(account) = account {
+                statement_line.set_balance(&account);
+            }
+            self.lines.push(statement_line);
+        }
+
+        // A row that named the client without changing its balances still gets a line
+        let row_listed = self.lines[first..].iter().any(|line| line.entry == StatementEntry::Row);
+        if !row_listed && named {
+            let mut statement_line = self.row_line(&row, line, status, &reason);
+            if let Some(account) = balances.account(self.client_id, row.currency.as_deref()) {
+                statement_line.set_balance(account);
+            }
+            self.lines.push(statement_line);
+        }
+
+        // Whatever the row locked shows on its own lines
+        for statement_line in &mut self.lines[first..] {
+            if statement_line.entry != StatementEntry::Expiry {
+                let currency = Some(statement_line.currency.as_str()).filter(|currency| !currency.is_empty());
+                statement_line.locked = balances.account(self.client_id, currency).is_some_and(|a| a.locked);
+            }
+        }
+
+        result
+    }
+
+    /// A line for the row itself, with a zero balance until one is set.
+    fn row_line(&self, row: &Transaction, line: Option<u64>, status: &'static str, reason: &str) -> StatementLine {
+        StatementLine {
+            line,
+            tx_id: row.tx_id,
+            tx_type: row.tx_type,
+            entry: StatementEntry::Row,
+            client_id: row.client_id,
+            currency: row.currency.clone().unwrap_or_default(),
+            amount: row.amount,
+            timestamp: row.timestamp,
+            status,
+            reason: reason.to_string(),
+            available: 0.0,
+            held: 0.0,
+            total: 0.0,
+            locked: false,
+        }
+    }
+
+    /// A line for an authorization released by `event` while `row` was handled.
+    fn expiry_line(&self, engine: &TransactionEngine, event: &Event, row: &Transaction, line: Option<u64>) -> StatementLine {
+        let authorization = engine.transactions.get(&event.tx_id).map(|stored| &stored.transaction);
+        StatementLine {
+            line,
+            tx_id: event.tx_id,
+            tx_type: authorization.map_or(TransactionType::Authorize, |tx| tx.tx_type),
+            entry: StatementEntry::Expiry,
+            client_id: self.client_id,
+            currency: event.currency.clone().unwrap_or_default(),
+            amount: Some(event.amount),
+            timestamp: row.timestamp,
+            status: "accepted",
+            reason: String::new(),
+            available: 0.0,
+            held: 0.0,
+            total: 0.0,
+            locked: false,
+        }
+    }
+
+    /// Every balance of the client, in the shape the journal applies events to.
+    fn balances(&self, engine: &TransactionEngine) -> Balances {
+        let mut balances = Balances::default();
+        if let Some(account) = engine.account(self.client_id, None) {
+            balances.accounts.insert(self.client_id, account.clone());
+        }
+        if let Some(wallets) = engine.currency_accounts.get(&self.client_id) {
+            balances.currency_accounts.insert(self.client_id, wallets.clone());
+        }
+        balances
+    }
+
+    pub fn write<W: io::Write>(&self, format: StatementFormat, writer: W) -> Result<(), Box<dyn std::error::Error>> {
+        match format {
+            StatementFormat::Text => self.write_text(writer)?,
+            StatementFormat::Csv => self.write_csv(writer)?,
+            StatementFormat::Json => self.write_json(writer)?,
+        }
+        Ok(())
+    }
+
+    /// Writes the lines as CSV; the lock reason is left to the other formats.
+    pub fn write_csv<W: io::Write>(&self, writer: W) -> Result<(), csv::Error> {
+        let mut csv_writer = csv::Writer::from_writer(writer);
+        for line in &self.lines {
+            csv_writer.serialize(line)?;
+        }
+        csv_writer.flush()?;
+        Ok(())
+    }
+
+    pub fn write_json<W: io::Write>(&self, mut writer: W) -> serde_json::Result<()> {
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer).map_err(serde_json::Error::io)
+    }
+
+    /// Writes the lines as an aligned table, followed by the lock status of the client.
+    pub fn write_text<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        let header = ["line", "tx", "type", "entry", "currency", "amount", "status", "available", "held", "total", "locked", "reason"];
+        let rows: Vec<Vec<String>> = self
+            .lines
+            .iter()
+            .map(|line| {
+                vec![
+                    line.line.map(|n| n.to_string()).unwrap_or_default(),
+                    line.tx_id.to_string(),
+                    line.tx_type.to_string(),
+                    line.entry.as_str().to_string(),
+                    line.currency.clone(),
+                    line.amount.map(|amount| format!("{:.4}", amount)).unwrap_or_default(),
+                    line.status.to_string(),
+                    format!("{:.4}", line.available),
+                    format!("{:.4}", line.held),
+                    format!("{:.4}", line.total),
+                    line.locked.to_string(),
+                    line.reason.clone(),
+                ]
+            })
+            .collect();
+
+        let mut widths: Vec<usize> = header.iter().map(|name| name.len()).collect();
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+
+        writeln!(writer, "Statement for client {}", self.client_id)?;
+        let header: Vec<String> = header.iter().map(|name| name.to_string()).collect();
+        for row in std::iter::once(&header).chain(&rows) {
+            let cells: Vec<String> =
+                row.iter().zip(&widths).map(|(cell, width)| format!("{:<width$}", cell, width = width)).collect();
+            writeln!(writer, "{}", cells.join("  ").trim_end())?;
+        }
+        match &self.lock_reason {
+            Some(reason) => writeln!(writer, "Locked: {}", reason),
+            None => writeln!(writer, "Not locked"),
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use std::fmt;
+use std::path::Path;
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::engine::{AuthorizationState, StoredTransaction, TransactionEngine};
+use crate::models::{ClientAccount, Transaction, TransactionType};
+
+/// Schema changes, in order; a database at version `n` has had the first `n` applied.
+const MIGRATIONS: &[&str] = &[
+    // 1: accounts and transactions
+    "CREATE TABLE accounts (
+        client INTEGER NOT NULL,
+        currency TEXT NOT NULL DEFAULT '',
+        available REAL NOT NULL,
+        held REAL NOT NULL,
+        total REAL NOT NULL,
+        locked INTEGER NOT NULL,
+        PRIMARY KEY (client, currency)
+    );
+    CREATE TABLE transactions (
+        tx INTEGER PRIMARY KEY,
+        type TEXT NOT NULL,
+        client INTEGER NOT NULL,
+        amount REAL,
+        currency TEXT,
+        to_currency TEXT,
+        to_client INTEGER,
+        ref_tx INTEGER,
+        timestamp TEXT,
+        credited REAL,
+        disputed REAL NOT NULL,
+        held REAL NOT NULL,
+        refunded REAL NOT NULL,
+        authorization TEXT
+    );
+    CREATE INDEX transactions_client ON transactions (client);",
+    // 2: the charged back part of a dispute, apart from the resolved part
+    "ALTER TABLE transactions ADD COLUMN charged_back REAL NOT NULL DEFAULT 0;",
+];
+
+/// Why the database could not be read or written.
+#[derive(Debug)]
+pub enum StoreError {
+    Sqlite(rusqlite::Error),
+    /// The database was written by a newer version with more migrations.
+    UnsupportedVersion(u32),
+    /// A stored value this version cannot read back.
+    Corrupt(String),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Sqlite(e) => write!(f, "Database error: {}", e),
+            StoreError::UnsupportedVersion(version) => write!(
+                f,
+                "Database schema version {} is newer than the supported version {}",
+                version,
+                MIGRATIONS.len()
+            ),
+            StoreError::Corrupt(value) => write!(f, "Unreadable value in database: {}", value),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(e: rusqlite::Error) -> Self {
+        StoreError::Sqlite(e)
+    }
+}
+
+/// Accounts and transactions kept in a SQLite database, so other tools can query them
+/// and later runs carry on where earlier ones stopped.
+///
+/// Only what changed is written, in one database transaction every `batch_size` rows.
+/// Fee lines, limit windows and the timestamp clock are not stored; they start afresh
+/// with every run.
+pub struct SqliteStore {
+    connection: Connection,
+    /// Rows handled between two commits.
+    pub batch_size: u64,
+    pending: u64,
+}
+
+impl SqliteStore {
+    /// Opens or creates the database at `path` and brings its schema up to date.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        let mut connection = Connection::open(path)?;
+        migrate(&mut connection)?;
+        Ok(Self { connection, batch_size: 1_000, pending: 0 })
+    }
+
+    /// Schema version of the database.
+    pub fn version(&self) -> Result<u32, StoreError> {
+        Ok(self.connection.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+    }
+
+    /// Fills the engine with the stored accounts and transactions and starts tracking
+    /// its changes. Options such as the authorization expiry should be set first.
+    pub fn load(&self, engine: &mut TransactionEngine) -> Result<(), StoreError> {
+        let mut accounts = self
+            .connection
+            .prepare("SELECT client, currency, available, held, total, locked FROM accounts")?;
+        let mut rows = accounts.query([])?;
+        while let Some(row) = rows.next()? {
+            let client_id: u16 = row.get(0)?;
+            let currency: String = row.get(1)?;
+            let account = ClientAccount {
+                client_id,
+                available: row.get(2)?,
+                held: row.get(3)?,
+                total: row.get(4)?,
+                locked: row.get(5)?,
+            };
+
+            // The default balance is stored with an empty currency
+            engine.restore_account(Some(currency.as_str()).filter(|c| !c.is_empty()), account);
+        }
+
+        let mut transactions = self.connection.prepare(
+            "SELECT tx, type, client, amount, currency, to_currency, to_client, ref_tx, timestamp,
+                    credited, disputed, held, refunded, authorization, charged_back
+             FROM transactions ORDER BY tx",
+        )?;
+        let mut rows = transactions.query([])?;
+        while let Some(row) = rows.next()? {
+            let tx_type: String = row.get(1)?;
+            let tx_type: TransactionType = tx_type.parse().map_err(|_| StoreError::Corrupt(tx_type))?;
+            let timestamp = row
+                .get::<_, Option<String>>(8)?
+                .map(|timestamp| timestamp.parse().map_err(|_| StoreError::Corrupt(timestamp)))
+                .transpose()?;
+            let authorization = row
+                .get::<_, Option<String>>(13)?
+                .map(|state| parse_authorization(&state).ok_or(StoreError::Corrupt(state)))
+                .transpose()?;
+
+            let mut transaction = Transaction::new(tx_type, row.get(2)?, row.get(0)?, row.get(3)?);
+            transaction.currency = row.get(4)?;
+            transaction.to_currency = row.get(5)?;
+            transaction.to_client = row.get(6)?;
+            transaction.ref_tx = row.get(7)?;
+            transaction.timestamp = timestamp;
+
+            engine.restore_transaction(StoredTransaction {
+                transaction,
+                credited: row.get(9)?,
+                disputed: row.get(10)?,
+                held: row.get(11)?,
+                charged_back: row.get(14)?,
+                refunded: row.get(12)?,
+                authorization,
+            });
+        }
+
+        engine.track_changes();
+        Ok(())
+    }
+
+    /// Counts a handled row and commits once `batch_size` rows have gone by.
+    pub fn row_handled(&mut self, engine: &mut TransactionEngine) -> Result<(), StoreError> {
+        self.pending += 1;
+        if self.pending >= self.batch_size {
+            self.commit(engine)?;
+        }
+        Ok(())
+    }
+
+    /// Writes everything that changed since the previous commit, all or nothing.
+    pub fn commit(&mut self, engine: &mut TransactionEngine) -> Result<(), StoreError> {
+        self.pending = 0;
+        let changes = engine.take_changes();
+        let transaction = self.connection.transaction()?;
+
+        for client_id in &changes.clients {
+            transaction.execute("DELETE FROM accounts WHERE client = ?1", [client_id])?;
+
+            let default = engine.accounts.get(client_id).map(|account| ("", account));
+            let labelled = engine
+                .currency_accounts
+                .get(client_id)
+                .into_iter()
+                .flatten()
+                .map(|(currency, account)| (currency.as_str(), account));
+            for (currency, account) in default.into_iter().chain(labelled) {
+                transaction.execute(
+                    "INSERT INTO accounts (client, currency, available, held, total, locked)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![client_id, currency, account.available, account.held, account.total, account.locked],
+                )?;
+            }
+        }
+
+        for tx_id in &changes.transactions {
+            let Some(stored) = engine.transactions.get(tx_id) else {
+                continue;
+            };
+            let tx = &stored.transaction;
+            transaction.execute(
+                "INSERT OR REPLACE INTO transactions
+                 (tx, type, client, amount, currency, to_currency, to_client, ref_tx, timestamp,
+                  credited, disputed, held, refunded, authorization, charged_back)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+                params![
+                    tx.tx_id,
+                    tx.tx_type.to_string(),
+                    tx.client_id,
+                    tx.amount,
+                    tx.currency,
+                    tx.to_currency,
+                    tx.to_client,
+                    tx.ref_tx,
+                    tx.timestamp.map(|timestamp| timestamp.to_rfc3339()),
+                    stored.credited,
+                    stored.disputed,
+                    stored.held,
+                    stored.refunded,
+                    stored.authorization.map(authorization_name),
+                    stored.charged_back,
+                ],
+            )?;
+        }
+
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// The stored balance of a client in `currency` (`None` for the default balance).
+    pub fn account(&self, client_id: u16, currency: Option<&str>) -> Result<Option<ClientAccount>, StoreError> {
+        let account = self
+            .connection
+            .query_row(
+                "SELECT available, held, total, locked FROM accounts WHERE client = ?1 AND currency = ?2",
+                params![client_id, currency.unwrap_or_default()],
+                |row| {
+                    Ok(ClientAccount {
+                        client_id,
+                        available: row.get(0)?,
+                        held: row.get(1)?,
+                        total: row.get(2)?,
+                        locked: row.get(3)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(account)
+    }
+}
+
+/// Applies the migrations the database has not had yet, each in its own transaction.
+fn migrate(connection: &mut Connection) -> Result<(), StoreError> {
+    let version: u32 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version as usize > MIGRATIONS.len() {
+        return Err(StoreError::UnsupportedVersion(version));
+    }
+
+    for (applied, migration) in (0..).zip(MIGRATIONS).skip(version as usize) {
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", applied + 1u32)?;
+        transaction.commit()?;
+    }
+
+    Ok(())
+}
+
+fn authorization_name(state: AuthorizationState) -> &'static str {
+    match state {
+        AuthorizationState::Outstanding => "outstanding",
+        AuthorizationState::Captured => "captured",
+        AuthorizationState::Voided => "voided",
+        AuthorizationState::Expired => "expired",
+    }
+}
+
+fn parse_authorization(name: &str) -> Option<AuthorizationState> {
+    match name {
+        "outstanding" => Some(AuthorizationState::Outstanding),
+        "captured" => Some(AuthorizationState::Captured),
+        "voided" => Some(AuthorizationState::Voided),
+        "expired" => Some(AuthorizationState::Expired),
+        _ => None,
+    }
+}
@@ -0,0 +1,149 @@
+use std::future::Future;
+use std::io;
+
+use csv::StringRecord;
+use futures_util::StreamExt;
+use serde::Serialize;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinSet;
+use tokio_util::codec::{FramedRead, LinesCodec, LinesCodecError};
+use tokio_util::sync::CancellationToken;
+
+use crate::models::Transaction;
+use crate::server::SharedEngine;
+use crate::utils::{read_record, RejectionKind, MAX_LINE_LENGTH};
+
+/// Acknowledgement written back for every row after the header, as a CSV line
+/// `line,status,tx,reason`. `status` is `accepted`, `invalid`, `rejected` or `malformed`.
+#[derive(Debug, Serialize)]
+struct Ack {
+    line: u64,
+    status: &'static str,
+    tx: Option<u32>,
+    reason: String,
+}
+
+impl Ack {
+    fn rejected(line: u64, kind: RejectionKind, tx: Option<u32>, reason: String) -> Self {
+        let status = match kind {
+            RejectionKind::Malformed => "malformed",
+            RejectionKind::Invalid => "invalid",
+            RejectionKind::Rejected => "rejected",
+        };
+        Self { line, status, tx, reason }
+    }
+}
+
+/// Accepts connections that each stream CSV lines into the shared engine, until
+/// `shutdown` completes. The first line of a connection is the header row, in the
+/// format `process_csv` accepts; every further line is acknowledged in order.
+///
+/// A connection reads its next line only once the previous one went through the engine,
+/// so a producer that outpaces the engine is held back by TCP flow control. On shutdown
+/// no more connections are accepted and open ones stop after the line in progress.
+/// A line longer than [`MAX_LINE_LENGTH`] is acknowledged as malformed and closes
+/// its connection.
+pub async fn serve<F>(listener: TcpListener, engine: SharedEngine, shutdown: F) -> io::Result<()>
+where
+    F: Future<Output = ()>,
+{
+    let stopping = CancellationToken::new();
+    let mut connections = JoinSet::new();
+    tokio::pin!(shutdown);
+
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            accepted = listener.accept() => match accepted {
+                Ok((socket, _)) => {
+                    connections.spawn(handle_connection(socket, engine.clone(), stopping.clone()));
+                }
+                Err(e) => eprintln!("Error accepting connection: {}", e),
+            },
+            Some(finished) = connections.join_next(), if !connections.is_empty() => report(finished),
+        }
+    }
+
+    // Let every connection finish the line it is on before the final export
+    stopping.cancel();
+    while let Some(finished) = connections.join_next().await {
+        report(finished);
+    }
+
+    Ok(())
+}
+
+fn report(finished: Result<io::Result<()>, tokio::task::JoinError>) {
+    match finished {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => eprintln!("Error on connection: {}", e),
+        Err(e) => eprintln!("Connection task failed: {}", e),
+    }
+}
+
+async fn handle_connection(socket: TcpStream, engine: SharedEngine, stopping: CancellationToken) -> io::Result<()> {
+    let (reader, writer) = socket.into_split();
+    let mut lines = FramedRead::new(reader, LinesCodec::new_with_max_length(MAX_LINE_LENGTH));
+    let mut writer = BufWriter::new(writer);
+    let mut headers: Option<StringRecord> = None;
+    let mut line_number = 0;
+
+    loop {
+        let line = tokio::select! {
+            _ = stopping.cancelled() => break,
+            line = lines.next() => match line {
+                Some(Ok(line)) => line,
+                Some(Err(LinesCodecError::MaxLineLengthExceeded)) => {
+                    // The rest of the line is never read, so the connection ends here
+                    let reason = format!("Line longer than {} bytes", MAX_LINE_LENGTH);
+                    let ack = Ack::rejected(line_number + 1, RejectionKind::Malformed, None, reason);
+                    writer.write_all(&ack_line(&ack)?).await?;
+                    break;
+                }
+                Some(Err(LinesCodecError::Io(e))) => return Err(e),
+                None => break,
+            },
+        };
+        line_number += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let ack = match (read_record(&line), &headers) {
+            (Ok(record), None) => {
+                headers = Some(record);
+                continue;
+            }
+            (Ok(record), Some(headers)) => match record.deserialize::<Transaction>(Some(headers)) {
+                Ok(transaction) => process(&engine, transaction, line_number).await,
+                Err(e) => Ack::rejected(line_number, RejectionKind::Malformed, None, e.to_string()),
+            },
+            (Err(e), _) => Ack::rejected(line_number, RejectionKind::Malformed, None, e.to_string()),
+        };
+        writer.write_all(&ack_line(&ack)?).await?;
+
+        // Send acknowledgements off whenever the producer is not already waiting with more lines
+        if lines.read_buffer().is_empty() {
+            writer.flush().await?;
+        }
+    }
+
+    writer.flush().await?;
+    writer.shutdown().await
+}
+
+/// Runs one transaction through the engine.
+async fn process(engine: &SharedEngine, transaction: Transaction, line: u64) -> Ack {
+    let tx_id = transaction.tx_id;
+    match engine.lock().await.handle_transaction(transaction) {
+        Ok(()) => Ack { line, status: "accepted", tx: Some(tx_id), reason: String::new() },
+        Err(e) => Ack::rejected(line, RejectionKind::from(&e), Some(tx_id), e.to_string()),
+    }
+}
+
+fn ack_line(ack: &Ack) -> io::Result<Vec<u8>> {
+    let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(Vec::new());
+    writer.serialize(ack)?;
+    writer.into_inner().map_err(|e| e.into_error())
+}
@@ -1,26 +1,138 @@
 use std::io;
 use std::io::BufWriter;
 use csv_async::AsyncReaderBuilder;
+use tokio::io::AsyncRead;
 use tokio_util::compat::TokioAsyncReadCompatExt;
 use futures_util::StreamExt;
 use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
 
-use crate::engine::TransactionEngine;
-use crate::models::{Transaction, TransactionType};
+use crate::engine::{EngineError, TransactionEngine};
+use crate::models::{AuthorizationRow, CreditAccountRow, CurrencyAccountRow, FeeRow, JournalRow, Transaction, TrialBalanceRow, ValidationError, ValidationPolicy};
+
+/// How a row that did not change any account was classified.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RejectionKind {
+    /// The row could not be read as a transaction at all.
+    Malformed,
+    /// The row failed field validation (see `ValidationPolicy`).
+    Invalid,
+    /// The row was valid but a business rule refused it.
+    Rejected,
+}
+
+impl From<&EngineError> for RejectionKind {
+    fn from(e: &EngineError) -> Self {
+        if e.is_validation() {
+            RejectionKind::Invalid
+        } else {
+            RejectionKind::Rejected
+        }
+    }
+}
+
+/// A row that did not change any account.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Rejection {
+    pub line: Option<u64>,
+    #[serde(rename = "client")]
+    pub client_id: Option<u16>,
+    #[serde(rename = "tx")]
+    pub tx_id: Option<u32>,
+    pub kind: RejectionKind,
+    pub reason: String,
+}
+
+/// Outcome of processing a CSV source.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct ProcessingReport {
+    pub accepted: usize,
+    pub rejections: Vec<Rejection>,
+}
+
+impl ProcessingReport {
+    /// Number of rejections of the given kind.
+    pub fn count(&self, kind: RejectionKind) -> usize {
+        self.rejections.iter().filter(|r| r.kind == kind).count()
+    }
+
+    /// Hands a transaction to the engine and records the outcome.
+    pub fn handle(&mut self, engine: &mut TransactionEngine, transaction: Transaction, line: Option<u64>) {
+        let (client_id, tx_id) = (transaction.client_id, transaction.tx_id);
+
+        // Validate and process the transaction; the engine tells the two failures apart
+        let result = engine.handle_transaction(transaction);
+        self.record(line, client_id, tx_id, &result);
+    }
+
+    /// Records the outcome of a transaction the engine has handled.
+    pub fn record(&mut self, line: Option<u64>, client_id: u16, tx_id: u32, result: &Result<(), EngineError>) {
+        match result {
+            Ok(()) => self.accepted += 1,
+            Err(e) => self.reject(Rejection {
+                line,
+                client_id: Some(client_id),
+                tx_id: Some(tx_id),
+                kind: RejectionKind::from(e),
+                reason: e.to_string(),
+            }),
+        }
+    }
+
+    /// Records a row that could not be read as a transaction.
+    pub fn malformed(&mut self, line: Option<u64>, reason: String) {
+        self.reject(Rejection { line, client_id: None, tx_id: None, kind: RejectionKind::Malformed, reason });
+    }
+
+    fn reject(&mut self, rejection: Rejection) {
+        let prefix = match rejection.kind {
+            RejectionKind::Malformed => "Error reading CSV transaction",
+            RejectionKind::Invalid => "Invalid transaction",
+            RejectionKind::Rejected => "Error processing transaction",
+        };
+        match (rejection.client_id, rejection.tx_id) {
+            (Some(client_id), Some(tx_id)) => eprintln!(
+                "{}: {} (client {}, tx {})",
+                prefix, rejection.reason, client_id, tx_id
+            ),
+            _ => eprintln!("{}: {}", prefix, rejection.reason),
+        }
+        self.rejections.push(rejection);
+    }
+}
 
 /// Stream transactions from the CSV file and process them.
 pub async fn process_csv(
     file_path: &str,
     engine: &mut TransactionEngine,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let file = tokio::fs::File::open(file_path).await?;
-    let file = file.compat();
+) -> Result<ProcessingReport, Box<dyn std::error::Error>> {
+    process_csv_with(file_path, engine, |_| Ok(())).await
+}
 
-    let mut reader = AsyncReaderBuilder::new()
-        .trim(csv_async::Trim::All)
-        .create_reader(file);
+/// Like `process_csv`, calling `after_row` after every row that could be read.
+pub async fn process_csv_with<F>(
+    file_path: &str,
+    engine: &mut TransactionEngine,
+    mut after_row: F,
+) -> Result<ProcessingReport, Box<dyn std::error::Error>>
+where
+    F: FnMut(&mut TransactionEngine) -> Result<(), Box<dyn std::error::Error>>,
+{
+    process_csv_rows(file_path, |report, transaction, line| {
+        report.handle(engine, transaction, line);
+        after_row(engine)
+    })
+    .await
+}
 
-    let mut records = reader.records();
+/// Like `process_csv`, handing every row that could be read to `handle_row`, which
+/// processes it and records the outcome in the report.
+pub async fn process_csv_rows<F>(file_path: &str, handle_row: F) -> Result<ProcessingReport, Box<dyn std::error::Error>>
+where
+    F: FnMut(&mut ProcessingReport, Transaction, Option<u64>) -> Result<(), Box<dyn std::error::Error>>,
+{
+    let file = tokio::fs::File::open(file_path).await?;
 
     let metadata = tokio::fs::metadata(file_path).await?;
     let total_zise = metadata.len();
@@ -33,78 +145,122 @@ pub async fn process_csv(
     );
     progress_bar.set_message("Processing CSV");
 
+    let report = process_reader_rows(file, &progress_bar, handle_row).await?;
+
+    progress_bar.finish_with_message("Processing complete");
+    eprintln!(
+        "{} accepted, {} invalid, {} rejected, {} malformed",
+        report.accepted,
+        report.count(RejectionKind::Invalid),
+        report.count(RejectionKind::Rejected),
+        report.count(RejectionKind::Malformed)
+    );
+
+    Ok(report)
+}
+
+/// Stream transactions from any CSV source (file, socket, in-memory buffer) and process them.
+pub async fn process_reader<R>(
+    reader: R,
+    engine: &mut TransactionEngine,
+    progress_bar: &ProgressBar,
+) -> Result<ProcessingReport, Box<dyn std::error::Error>>
+where
+    R: AsyncRead + Unpin + Send,
+{
+    process_reader_with(reader, engine, progress_bar, |_| Ok(())).await
+}
+
+/// Like `process_reader`, calling `after_row` after every row that could be read.
+pub async fn process_reader_with<R, F>(
+    reader: R,
+    engine: &mut TransactionEngine,
+    progress_bar: &ProgressBar,
+    mut after_row: F,
+) -> Result<ProcessingReport, Box<dyn std::error::Error>>
+where
+    R: AsyncRead + Unpin + Send,
+    F: FnMut(&mut TransactionEngine) -> Result<(), Box<dyn std::error::Error>>,
+{
+    process_reader_rows(reader, progress_bar, |report, transaction, line| {
+        report.handle(engine, transaction, line);
+        after_row(engine)
+    })
+    .await
+}
+
+/// Like `process_reader`, handing every row that could be read to `handle_row`, which
+/// processes it and records the outcome in the report.
+pub async fn process_reader_rows<R, F>(
+    reader: R,
+    progress_bar: &ProgressBar,
+    mut handle_row: F,
+) -> Result<ProcessingReport, Box<dyn std::error::Error>>
+where
+    R: AsyncRead + Unpin + Send,
+    F: FnMut(&mut ProcessingReport, Transaction, Option<u64>) -> Result<(), Box<dyn std::error::Error>>,
+{
+    let mut reader = AsyncReaderBuilder::new()
+        .trim(csv_async::Trim::All)
+        .create_reader(reader.compat());
+
+    // Map fields by column name, so optional columns can be left out or reordered
+    let headers = reader.headers().await?.clone();
+    let mut records = reader.records();
+    let mut report = ProcessingReport::default();
+
     while let Some(result) = records.next().await {
         match result {
             Ok(record) => {
-                // Deserialize the record into a Transaction
-                let transaction: Transaction = record.deserialize(None)?;
-
-                // Validate the transaction
-                if let Err(e) = validate_transaction(&transaction) {
-                    eprintln!("Invalid transaction: {}: {:?}", e, transaction);
-                    continue; // Skip invalid transactions
-                }
-
-                // Process the valid transaction
-                if let Err(e) = engine.handle_transaction(transaction) {
-                    eprintln!("Error processing transaction: {}", e);
+                // Deserialize the record into a Transaction; a bad field only loses its row
+                let line = record.position().map(|p| p.line());
+                match record.deserialize::<Transaction>(Some(&headers)) {
+                    Ok(transaction) => handle_row(&mut report, transaction, line)?,
+                    Err(e) => report.malformed(line, e.to_string()),
                 }
             }
-            Err(e) => {
-                eprintln!("Error reading CSV transaction: {}", e);
-            }
+            Err(e) => report.malformed(e.position().map(|p| p.line()), e.to_string()),
         }
          // Increment progress bar for each record processed
          progress_bar.inc(1);
     }
 
-    progress_bar.finish_with_message("Processing complete");
-    Ok(())
+    Ok(report)
 }
 
-/// Validate a transaction's fields.
-pub fn validate_transaction(transaction: &Transaction) -> Result<(), String> {
-    // Validate client_id
-    if transaction.client_id == 0 {
-        return Err("Client ID must be greater than 0".into());
-    }
+/// Longest line, in bytes, a streamed source may send; a longer one is malformed.
+pub const MAX_LINE_LENGTH: usize = 64 * 1024;
 
-    // Validate tx_id
-    if transaction.tx_id == 0 {
-        return Err("Transaction ID must be greater than 0".into());
-    }
+/// Reads a single CSV line, trimmed like the rows of `process_csv`.
+pub fn read_record(line: &str) -> Result<csv::StringRecord, csv::Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .trim(csv::Trim::All)
+        .from_reader(line.as_bytes());
 
-    // Validate fields based on transaction type
-    match transaction.tx_type {
-        TransactionType::Deposit | TransactionType::Withdrawal => {
-            // Deposit and Withdrawal must have a valid amount
-            if let Some(amount) = transaction.amount {
-                if amount < 0.0 {
-                    return Err(format!("{:?} amount must be non-negative", transaction));
-                }
-            } else {
-                return Err(format!("{:?} transaction requires an amount", transaction));
-            }
-        }
-        TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback => {
-            // Dispute, Resolve, and Chargeback must not have an amount
-            if transaction.amount.is_some() {
-                return Err(format!(
-                    "{:?} transaction must not have an amount",
-                    transaction
-                ));
-            }
-        }
-    }
+    let mut record = csv::StringRecord::new();
+    reader.read_record(&mut record)?;
+    Ok(record)
+}
 
-    Ok(())
+/// Validate a transaction's fields against the default policy.
+pub fn validate_transaction(transaction: &Transaction) -> Result<(), ValidationError> {
+    validate_transaction_with(transaction, &ValidationPolicy::default())
+}
+
+/// Validate a transaction's fields against the given policy.
+pub fn validate_transaction_with(
+    transaction: &Transaction,
+    policy: &ValidationPolicy,
+) -> Result<(), ValidationError> {
+    transaction.validate(policy)
 }
 
 /// Processes a CSV file and updates the transaction engine.
 pub async fn process_file(
     input_file: &str,
     engine: &mut TransactionEngine,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<ProcessingReport, Box<dyn std::error::Error>> {
     process_csv(input_file, engine).await
 }
 
@@ -112,11 +268,107 @@ pub async fn process_file(
 pub fn export_accounts_to_stdout(engine: &TransactionEngine) -> Result<(), csv::Error> {
     let stdout = io::stdout();
     let writer = BufWriter::new(stdout.lock());
+    write_accounts(engine, writer)
+}
+
+/// Writes the accounts as CSV. Rows gain a `currency` column only once a transaction
+/// named a currency, and `credit_limit` and `headroom` columns only once credit limits
+/// are configured, so plain output keeps its original shape.
+pub fn write_accounts<W: io::Write>(engine: &TransactionEngine, writer: W) -> Result<(), csv::Error> {
     let mut csv_writer = csv::Writer::from_writer(writer);
-        
-    for account in engine.accounts.values() {
-        csv_writer.serialize(account)?;
+
+    if engine.credit.is_active() {
+        let multi_currency = engine.is_multi_currency();
+        for (currency, account) in engine.balances() {
+            let credit_limit = engine.credit.limit(account.client_id, currency);
+            let currency = multi_currency.then(|| currency.unwrap_or_default());
+            csv_writer.serialize(CreditAccountRow::new(currency, account, credit_limit))?;
+        }
+    } else if engine.is_multi_currency() {
+        for (currency, account) in engine.balances() {
+            csv_writer.serialize(CurrencyAccountRow::new(currency, account))?;
+        }
+    } else {
+        for account in engine.accounts.values() {
+            csv_writer.serialize(account)?;
+        }
     }
 
     csv_writer.flush().map_err(csv::Error::from)
-}
\ No newline at end of file
+}
+
+/// Writes the outstanding authorizations as CSV, ordered by transaction ID.
+pub fn write_authorizations<W: io::Write>(engine: &TransactionEngine, writer: W) -> Result<(), csv::Error> {
+    let mut csv_writer = csv::Writer::from_writer(writer);
+
+    for tx in engine.outstanding_authorizations() {
+        csv_writer.serialize(AuthorizationRow {
+            tx_id: tx.tx_id,
+            client_id: tx.client_id,
+            currency: tx.currency.as_deref().unwrap_or_default(),
+            amount: tx.amount.unwrap_or_default(),
+        })?;
+    }
+
+    csv_writer.flush().map_err(csv::Error::from)
+}
+
+/// Writes every fee charged as CSV, in the order charged.
+pub fn write_fees<W: io::Write>(engine: &TransactionEngine, writer: W) -> Result<(), csv::Error> {
+    let mut csv_writer = csv::Writer::from_writer(writer);
+
+    for line in engine.fee_lines() {
+        csv_writer.serialize(FeeRow {
+            tx_id: line.tx_id,
+            client_id: line.client_id,
+            tx_type: line.tx_type.to_string(),
+            currency: line.currency.as_deref().unwrap_or_default(),
+            amount: line.amount,
+        })?;
+    }
+
+    csv_writer.flush().map_err(csv::Error::from)
+}
+
+/// Writes every event in the engine's journal as CSV, in order.
+pub fn write_journal<W: io::Write>(engine: &TransactionEngine, writer: W) -> Result<(), csv::Error> {
+    let mut csv_writer = csv::Writer::from_writer(writer);
+
+    for event in engine.journal().into_iter().flat_map(|journal| &journal.events) {
+        csv_writer.serialize(JournalRow {
+            event: event.kind,
+            client_id: event.client_id,
+            currency: event.currency.as_deref().unwrap_or_default(),
+            tx_id: event.tx_id,
+            amount: event.amount,
+        })?;
+    }
+
+    csv_writer.flush().map_err(csv::Error::from)
+}
+
+/// Writes the balance of every ledger account as CSV, ordered by account, followed by
+/// one `net` row per currency.
+pub fn write_trial_balance<W: io::Write>(engine: &TransactionEngine, writer: W) -> Result<(), csv::Error> {
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    let ledger = engine.ledger();
+
+    for (account, balance) in ledger.trial_balance() {
+        csv_writer.serialize(TrialBalanceRow {
+            account: account.name(),
+            client: account.client_id(),
+            currency: account.currency().unwrap_or_default(),
+            balance,
+        })?;
+    }
+    for (currency, net) in ledger.net(engine.validation.max_decimal_places()) {
+        csv_writer.serialize(TrialBalanceRow {
+            account: "net",
+            client: None,
+            currency: currency.unwrap_or_default(),
+            balance: net,
+        })?;
+    }
+
+    csv_writer.flush().map_err(csv::Error::from)
+}
@@ -0,0 +1,254 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::future::Future;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use sha2::{Digest, Sha256};
+
+use crate::engine::TransactionEngine;
+use crate::utils::{process_csv_rows, ProcessingReport};
+
+/// File in the inbox holding the content hash of every file taken so far, one per line.
+const HASHES_FILE: &str = ".processed_hashes";
+
+/// What became of a file taken from the inbox.
+#[derive(Debug)]
+pub enum FileOutcome {
+    /// Processed and moved to `processed/`.
+    Processed { file: PathBuf, report: ProcessingReport },
+    /// Could not be processed and moved to `failed/`, next to a `<name>.error` file.
+    Failed { file: PathBuf, error: String },
+    /// Same content as a file taken before; moved to `failed/` without processing.
+    Duplicate { file: PathBuf },
+}
+
+/// A drop folder partners deliver CSV files into.
+///
+/// A file is taken once its size and modification time are unchanged between two polls,
+/// so files still being uploaded are left alone. Ready files are processed oldest first
+/// and then moved to `processed/` or `failed/`, next to a `<name>.rejections.csv` report.
+/// The content hash of every file processed is kept in the inbox, so the same content is
+/// never processed twice, even across runs; the engine must then outlive the run too.
+/// A file that failed is not recorded, so it is processed again if delivered again; the
+/// rows it applied earlier in the same run are skipped then, so none is applied twice.
+pub struct Inbox {
+    dir: PathBuf,
+    processed: PathBuf,
+    failed: PathBuf,
+    hashes: HashSet<String>,
+    /// Rows applied by earlier attempts at content that did not go through, by hash. A new
+    /// run starts from what was saved, so this is not kept across runs.
+    applied: HashMap<String, usize>,
+    /// Size and modification time of each file at the previous poll.
+    last_seen: HashMap<PathBuf, (u64, SystemTime)>,
+}
+
+impl Inbox {
+    /// Opens the inbox at `dir`, creating `processed/` and `failed/` if needed.
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        let (processed, failed) = (dir.join("processed"), dir.join("failed"));
+        fs::create_dir_all(&processed)?;
+        fs::create_dir_all(&failed)?;
+
+        let hashes = match fs::read_to_string(dir.join(HASHES_FILE)) {
+            Ok(contents) => contents.lines().map(str::to_string).collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashSet::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self { dir, processed, failed, hashes, applied: HashMap::new(), last_seen: HashMap::new() })
+    }
+
+    /// Processes every file that is ready, oldest first.
+    pub async fn poll(&mut self, engine: &mut TransactionEngine) -> io::Result<Vec<FileOutcome>> {
+        self.poll_with(engine, |_| Ok(())).await
+    }
+
+    /// Like [`Inbox::poll`], calling `save` once each file has gone through the engine.
+    /// A file is only recorded as processed after `save` succeeded.
+    pub async fn poll_with<S>(&mut self, engine: &mut TransactionEngine, mut save: S) -> io::Result<Vec<FileOutcome>>
+    where
+        S: FnMut(&mut TransactionEngine) -> io::Result<()>,
+    {
+        let mut outcomes = Vec::new();
+        for file in self.ready_files()? {
+            outcomes.push(self.take(&file, engine, &mut save).await?);
+        }
+        Ok(outcomes)
+    }
+
+    /// CSV files unchanged since the previous poll, by modification time then name.
+    fn ready_files(&mut self) -> io::Result<Vec<PathBuf>> {
+        let mut seen = HashMap::new();
+        let mut ready = Vec::new();
+
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if !path.is_file() || path.extension().and_then(|e| e.to_str()) != Some("csv") {
+                continue;
+            }
+
+            let metadata = fs::metadata(&path)?;
+            let state = (metadata.len(), metadata.modified()?);
+            if self.last_seen.get(&path) == Some(&state) {
+                ready.push((state.1, path.clone()));
+            }
+            seen.insert(path, state);
+        }
+
+        self.last_seen = seen;
+        ready.sort();
+        Ok(ready.into_iter().map(|(_, path)| path).collect())
+    }
+
+    async fn take<S>(&mut self, file: &Path, engine: &mut TransactionEngine, save: &mut S) -> io::Result<FileOutcome>
+    where
+        S: FnMut(&mut TransactionEngine) -> io::Result<()>,
+    {
+        self.last_seen.remove(file);
+        let hash = content_hash(file)?;
+
+        // Never process the same content twice
+        if self.hashes.contains(&hash) {
+            let moved = move_into(file, &self.failed)?;
+            return Ok(FileOutcome::Duplicate { file: moved });
+        }
+
+        // Rows an earlier attempt at the same content applied are not applied again
+        let skip = self.applied.remove(&hash).unwrap_or(0);
+        let mut rows = 0;
+        let path = file.to_string_lossy().into_owned();
+        let processed = process_csv_rows(&path, |report, transaction, line| {
+            rows += 1;
+            if rows > skip {
+                report.handle(engine, transaction, line);
+            }
+            Ok(())
+        })
+        .await;
+        let applied = rows.max(skip);
+
+        if let Err(e) = save(engine) {
+            self.applied.insert(hash, applied);
+            return Err(e);
+        }
+
+        match processed {
+            Ok(report) => {
+                // Only content that went through completely is skipped from now on
+                self.remember(hash)?;
+                let moved = move_into(file, &self.processed)?;
+                write_rejections(&moved, &report)?;
+                Ok(FileOutcome::Processed { file: moved, report })
+            }
+            Err(e) => {
+                // Rows before the failure have been applied, as with a single file
+                self.applied.insert(hash, applied);
+                let moved = move_into(file, &self.failed)?;
+                write_error(&moved, &e.to_string())?;
+                Ok(FileOutcome::Failed { file: moved, error: e.to_string() })
+            }
+        }
+    }
+
+    fn remember(&mut self, hash: String) -> io::Result<()> {
+        let mut hashes = fs::OpenOptions::new().create(true).append(true).open(self.dir.join(HASHES_FILE))?;
+        writeln!(hashes, "{}", hash)?;
+        self.hashes.insert(hash);
+        Ok(())
+    }
+}
+
+/// Polls the inbox every `interval` until `shutdown` completes, logging each file taken
+/// and calling `save` after each one, see [`Inbox::poll_with`].
+pub async fn watch<F, S>(
+    inbox: &mut Inbox,
+    engine: &mut TransactionEngine,
+    interval: Duration,
+    shutdown: F,
+    mut save: S,
+) -> io::Result<()>
+where
+    F: Future<Output = ()>,
+    S: FnMut(&mut TransactionEngine) -> io::Result<()>,
+{
+    tokio::pin!(shutdown);
+
+    loop {
+        for outcome in inbox.poll_with(engine, &mut save).await? {
+            match outcome {
+                FileOutcome::Processed { file, report } => eprintln!(
+                    "Processed {}: {} accepted, {} not",
+                    file.display(),
+                    report.accepted,
+                    report.rejections.len()
+                ),
+                FileOutcome::Failed { file, error } => eprintln!("Failed {}: {}", file.display(), error),
+                FileOutcome::Duplicate { file } => {
+                    eprintln!("Skipped {}: already processed", file.display())
+                }
+            }
+        }
+
+        tokio::select! {
+            _ = &mut shutdown => return Ok(()),
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+}
+
+/// SHA-256 of a file's contents, in hex.
+fn content_hash(file: &Path) -> io::Result<String> {
+    let mut reader = fs::File::open(file)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 64 * 1024];
+
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Moves `file` into `dir`, numbering the name if it is taken, and returns the new path.
+fn move_into(file: &Path, dir: &Path) -> io::Result<PathBuf> {
+    let name = file.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let mut destination = dir.join(&name);
+    let mut copy = 1;
+
+    while destination.exists() {
+        destination = dir.join(format!("{}.{}", name, copy));
+        copy += 1;
+    }
+
+    fs::rename(file, &destination)?;
+    Ok(destination)
+}
+
+/// Writes the rows of a file that did not change any account next to it.
+fn write_rejections(file: &Path, report: &ProcessingReport) -> io::Result<()> {
+    let mut path = file.as_os_str().to_owned();
+    path.push(".rejections.csv");
+
+    // The header is written by hand so that a file without rejections still gets one
+    let mut writer = csv::WriterBuilder::new().has_headers(false).from_path(path)?;
+    writer.write_record(["line", "client", "tx", "kind", "reason"])?;
+    for rejection in &report.rejections {
+        writer.serialize(rejection)?;
+    }
+    writer.flush()
+}
+
+/// Writes why a file could not be processed next to it.
+fn write_error(file: &Path, error: &str) -> io::Result<()> {
+    let mut path = file.as_os_str().to_owned();
+    path.push(".error");
+    fs::write(path, format!("{}\n", error))
+}
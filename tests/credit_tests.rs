@@ -0,0 +1,29 @@
+use toy_payments_engine::credit::{CreditError, CreditLimits};
+
+#[test]
+fn test_credit_limits_from_reader() {
+    let data = "client,currency,credit_limit\n1,,500\n1,EUR,100\n2,,0\n";
+    let limits = CreditLimits::from_reader(data.as_bytes()).unwrap();
+
+    assert!(limits.is_active());
+    assert_eq!(limits.limit(1, None), 500.0);
+    assert_eq!(limits.limit(1, Some("EUR")), 100.0);
+    assert_eq!(limits.limit(1, Some("USD")), 0.0);
+    assert_eq!(limits.limit(3, None), 0.0);
+
+    // The currency column may be omitted
+    let limits = CreditLimits::from_reader("client,credit_limit\n4,25\n".as_bytes()).unwrap();
+    assert_eq!(limits.limit(4, None), 25.0);
+}
+
+#[test]
+fn test_credit_limits_reject_bad_rows() {
+    let result = CreditLimits::from_reader("client,currency,credit_limit\n1,,-1\n".as_bytes());
+    assert!(matches!(result, Err(CreditError::InvalidLimit { client: 1, .. })));
+
+    let result = CreditLimits::from_reader("client,currency,credit_limit\n1,eur,10\n".as_bytes());
+    assert!(matches!(result, Err(CreditError::InvalidCurrency(code)) if code == "eur"));
+
+    let result = CreditLimits::from_reader("client,currency,credit_limit\n1,,10\n1,,20\n".as_bytes());
+    assert!(matches!(result, Err(CreditError::DuplicateLimit { client: 1, currency: None })));
+}
@@ -0,0 +1,168 @@
+use proptest::prelude::*;
+use toy_payments_engine::engine::TransactionEngine;
+use toy_payments_engine::ledger::LedgerAccount;
+use toy_payments_engine::models::{Transaction, TransactionType};
+
+const EPSILON: f64 = 1e-6;
+
+// A generated row: deposits, withdrawals and transfers get a fresh tx id, the other
+// types reference an id that may or may not exist. Transfers derive their destination
+// from the reference.
+#[derive(Debug, Clone)]
+struct Op {
+    tx_type: TransactionType,
+    client_id: u16,
+    reference: u32,
+    amount: f64,
+}
+
+fn op_strategy() -> impl Strategy<Value = Op> {
+    let tx_type = prop_oneof![
+        4 => Just(TransactionType::Deposit),
+        3 => Just(TransactionType::Withdrawal),
+        2 => Just(TransactionType::Dispute),
+        1 => Just(TransactionType::Resolve),
+        1 => Just(TransactionType::Chargeback),
+        2 => Just(TransactionType::Transfer),
+    ];
+    // Amounts carry the engine's four decimal places
+    let amount = (1u32..10_000_000).prop_map(|units| units as f64 / 10_000.0);
+
+    (tx_type, 1u16..=4, 1u32..=64, amount).prop_map(|(tx_type, client_id, reference, amount)| Op {
+        tx_type,
+        client_id,
+        reference,
+        amount,
+    })
+}
+
+fn to_transaction(op: &Op, next_tx_id: &mut u32) -> Transaction {
+    match op.tx_type {
+        TransactionType::Deposit | TransactionType::Withdrawal => {
+            let tx_id = *next_tx_id;
+            *next_tx_id += 1;
+            Transaction::new(op.tx_type, op.client_id, tx_id, Some(op.amount))
+        }
+        TransactionType::Transfer => {
+            let tx_id = *next_tx_id;
+            *next_tx_id += 1;
+            let mut transaction = Transaction::new(op.tx_type, op.client_id, tx_id, Some(op.amount));
+            transaction.to_client = Some((op.reference % 4) as u16 + 1);
+            transaction
+        }
+        _ => Transaction::new(op.tx_type, op.client_id, op.reference, None),
+    }
+}
+
+fn snapshot(engine: &TransactionEngine, client_id: u16) -> Option<(f64, f64, f64, bool)> {
+    engine
+        .accounts
+        .get(&client_id)
+        .map(|a| (a.available, a.held, a.total, a.locked))
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(256))]
+
+    #[test]
+    fn prop_balances_stay_consistent(ops in prop::collection::vec(op_strategy(), 1..200)) {
+        let mut engine = TransactionEngine::new();
+        let mut next_tx_id = 1;
+
+        for op in &ops {
+            let transaction = to_transaction(op, &mut next_tx_id);
+            let _ = engine.handle_transaction(transaction);
+
+            for account in engine.accounts.values() {
+                prop_assert!(
+                    (account.total - (account.available + account.held)).abs() < EPSILON,
+                    "total != available + held for {:?}", account
+                );
+                prop_assert!(account.held > -EPSILON, "held is negative for {:?}", account);
+            }
+        }
+    }
+
+    #[test]
+    fn prop_locked_accounts_never_change(ops in prop::collection::vec(op_strategy(), 1..200)) {
+        let mut engine = TransactionEngine::new();
+        let mut next_tx_id = 1;
+        let mut frozen: Vec<(u16, (f64, f64, f64, bool))> = Vec::new();
+
+        for op in &ops {
+            let transaction = to_transaction(op, &mut next_tx_id);
+            let _ = engine.handle_transaction(transaction);
+
+            for (client_id, state) in &frozen {
+                prop_assert_eq!(snapshot(&engine, *client_id), Some(*state));
+            }
+
+            let client_id = op.client_id;
+            if let Some(state) = snapshot(&engine, client_id) {
+                if state.3 && !frozen.iter().any(|(c, _)| *c == client_id) {
+                    frozen.push((client_id, state));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn prop_money_is_conserved(ops in prop::collection::vec(op_strategy(), 1..200)) {
+        let mut engine = TransactionEngine::new();
+        let mut next_tx_id = 1;
+        let mut expected_total = 0.0;
+
+        for op in &ops {
+            let transaction = to_transaction(op, &mut next_tx_id);
+            let tx_type = transaction.tx_type;
+            // Chargebacks remove the amount of the transaction they reference
+            let referenced = engine.transactions.get(&transaction.tx_id).and_then(|stored| stored.transaction.amount);
+            let amount = transaction.amount;
+
+            if engine.handle_transaction(transaction).is_ok() {
+                match tx_type {
+                    TransactionType::Deposit => expected_total += amount.unwrap(),
+                    TransactionType::Withdrawal => expected_total -= amount.unwrap(),
+                    TransactionType::Chargeback => expected_total -= referenced.unwrap(),
+                    _ => {}
+                }
+            }
+        }
+
+        let total: f64 = engine.accounts.values().map(|a| a.total).sum();
+        prop_assert!((total - expected_total).abs() < EPSILON, "{} != {}", total, expected_total);
+    }
+
+    #[test]
+    fn prop_journal_rebuilds_the_accounts(ops in prop::collection::vec(op_strategy(), 1..200)) {
+        let mut engine = TransactionEngine::new();
+        engine.keep_journal();
+        let mut next_tx_id = 1;
+
+        for op in &ops {
+            let transaction = to_transaction(op, &mut next_tx_id);
+            let _ = engine.handle_transaction(transaction);
+        }
+
+        let mismatches = engine.journal().unwrap().verify(&engine);
+        prop_assert!(mismatches.is_empty(), "{:?}", mismatches);
+    }
+
+    #[test]
+    fn prop_ledger_nets_to_zero(ops in prop::collection::vec(op_strategy(), 1..200)) {
+        let mut engine = TransactionEngine::new();
+        let mut next_tx_id = 1;
+
+        for op in &ops {
+            let transaction = to_transaction(op, &mut next_tx_id);
+            let _ = engine.handle_transaction(transaction);
+            prop_assert!(engine.ledger().unbalanced(4).is_empty(), "{:?}", engine.ledger().net(4));
+        }
+
+        for account in engine.accounts.values() {
+            let ledger = engine.ledger();
+            prop_assert_eq!(ledger.balance(&LedgerAccount::available(account.client_id, None)), account.available);
+            prop_assert_eq!(ledger.balance(&LedgerAccount::held(account.client_id, None)), account.held);
+        }
+    }
+}
@@ -1,5 +1,8 @@
-use toy_payments_engine::engine::TransactionEngine;
-use toy_payments_engine::models::{TransactionType, Transaction, ClientAccount};
+use chrono::TimeDelta;
+use toy_payments_engine::engine::{AuthorizationState, EngineError, TransactionEngine};
+use toy_payments_engine::models::{TransactionType, Transaction, ClientAccount, ValidationError, ZeroAmountPolicy};
+use toy_payments_engine::ledger::{LedgerAccount, SystemBook};
+use toy_payments_engine::limits::{BreachAction, LimitBreach, LimitWindow, Limits};
 
 #[test]
 fn test_engine_initialization() {
@@ -11,12 +14,7 @@ fn test_engine_initialization() {
 #[test]
 fn test_handle_deposit() {
     let mut engine = TransactionEngine::new();
-    let transaction = Transaction {
-        tx_type: TransactionType::Deposit,
-        client_id: 1,
-        tx_id: 1,
-        amount: Some(100.0),
-    };
+    let transaction = Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0));
     assert!(engine.handle_transaction(transaction).is_ok());
 
     let account = engine.accounts.get(&1).unwrap();
@@ -29,12 +27,7 @@ fn test_handle_deposit() {
 #[test]
 fn test_deposit_to_new_account() {
     let mut engine = TransactionEngine::new();
-    let deposit = Transaction {
-        tx_id: 1,
-        client_id: 1,
-        tx_type: TransactionType::Deposit,
-        amount: Some(100.0),
-    };
+    let deposit = Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0));
 
     assert!(engine.handle_transaction(deposit).is_ok());
     let account = engine.accounts.get(&1).unwrap();
@@ -46,47 +39,27 @@ fn test_deposit_to_new_account() {
 #[test]
 fn test_handle_deposit_with_invalid_amount() {
     let mut engine = TransactionEngine::new();
-    let transaction = Transaction {
-        tx_type: TransactionType::Deposit,
-        client_id: 1,
-        tx_id: 1,
-        amount: None,
-    };
+    let transaction = Transaction::new(TransactionType::Deposit, 1, 1, None);
     assert!(engine.handle_transaction(transaction).is_err());
 }
 
 #[test]
 fn test_deposit_negative_amount() {
     let mut engine = TransactionEngine::new();
-    let deposit = Transaction {
-        tx_id: 1,
-        client_id: 1,
-        tx_type: TransactionType::Deposit,
-        amount: Some(-50.0),
-    };
+    let deposit = Transaction::new(TransactionType::Deposit, 1, 1, Some(-50.0));
 
     let result = engine.handle_transaction(deposit);
     assert!(result.is_err());
-    assert_eq!(result.unwrap_err(), "Deposit amount must be positive");
+    assert_eq!(result.unwrap_err().to_string(), "Deposit amount must be positive");
 }
 
 #[test]
 fn test_handle_withdrawal() {
     let mut engine = TransactionEngine::new();
-    let deposit = Transaction {
-        tx_type: TransactionType::Deposit,
-        client_id: 1,
-        tx_id: 1,
-        amount: Some(100.0),
-    };
+    let deposit = Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0));
     assert!(engine.handle_transaction(deposit).is_ok());
 
-    let withdrawal = Transaction {
-        tx_type: TransactionType::Withdrawal,
-        client_id: 1,
-        tx_id: 2,
-        amount: Some(50.0),
-    };
+    let withdrawal = Transaction::new(TransactionType::Withdrawal, 1, 2, Some(50.0));
     assert!(engine.handle_transaction(withdrawal).is_ok());
 
     let account = engine.accounts.get(&1).unwrap();
@@ -110,12 +83,7 @@ fn test_withdrawal_from_existing_account() {
         },
     );
 
-    let withdrawal = Transaction {
-        tx_id: 2,
-        client_id: 1,
-        tx_type: TransactionType::Withdrawal,
-        amount: Some(50.0),
-    };
+    let withdrawal = Transaction::new(TransactionType::Withdrawal, 1, 2, Some(50.0));
 
     assert!(engine.handle_transaction(withdrawal).is_ok());
     let account = engine.accounts.get(&1).unwrap();
@@ -137,35 +105,20 @@ fn test_withdrawal_insufficient_funds() {
         },
     );
 
-    let withdrawal = Transaction {
-        tx_id: 2,
-        client_id: 1,
-        tx_type: TransactionType::Withdrawal,
-        amount: Some(50.0),
-    };
+    let withdrawal = Transaction::new(TransactionType::Withdrawal, 1, 2, Some(50.0));
 
     let result = engine.handle_transaction(withdrawal);
     assert!(result.is_err());
-    assert_eq!(result.unwrap_err(), "Insufficient funds: Available = 30, Withdrawal amount = 50");
+    assert_eq!(result.unwrap_err().to_string(), "Insufficient funds: Available = 30, Withdrawal amount = 50");
 }
 
 #[test]
 fn test_dispute_transaction() {
     let mut engine = TransactionEngine::new();
-    let deposit = Transaction {
-        tx_id: 1,
-        client_id: 1,
-        tx_type: TransactionType::Deposit,
-        amount: Some(100.0),
-    };
+    let deposit = Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0));
     engine.handle_transaction(deposit).unwrap();
 
-    let dispute = Transaction {
-        tx_id: 1,
-        client_id: 1,
-        tx_type: TransactionType::Dispute,
-        amount: None,
-    };
+    let dispute = Transaction::new(TransactionType::Dispute, 1, 1, None);
 
     assert!(engine.handle_transaction(dispute).is_ok());
     let account = engine.accounts.get(&1).unwrap();
@@ -176,28 +129,13 @@ fn test_dispute_transaction() {
 #[test]
 fn test_resolve_dispute() {
     let mut engine = TransactionEngine::new();
-    let deposit = Transaction {
-        tx_id: 1,
-        client_id: 1,
-        tx_type: TransactionType::Deposit,
-        amount: Some(100.0),
-    };
+    let deposit = Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0));
     engine.handle_transaction(deposit).unwrap();
 
-    let dispute = Transaction {
-        tx_id: 1,
-        client_id: 1,
-        tx_type: TransactionType::Dispute,
-        amount: None,
-    };
+    let dispute = Transaction::new(TransactionType::Dispute, 1, 1, None);
     engine.handle_transaction(dispute).unwrap();
 
-    let resolve = Transaction {
-        tx_id: 1,
-        client_id: 1,
-        tx_type: TransactionType::Resolve,
-        amount: None,
-    };
+    let resolve = Transaction::new(TransactionType::Resolve, 1, 1, None);
 
     assert!(engine.handle_transaction(resolve).is_ok());
     let account = engine.accounts.get(&1).unwrap();
@@ -208,28 +146,13 @@ fn test_resolve_dispute() {
 #[test]
 fn test_chargeback() {
     let mut engine = TransactionEngine::new();
-    let deposit = Transaction {
-        tx_id: 1,
-        client_id: 1,
-        tx_type: TransactionType::Deposit,
-        amount: Some(100.0),
-    };
+    let deposit = Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0));
     engine.handle_transaction(deposit).unwrap();
 
-    let dispute = Transaction {
-        tx_id: 1,
-        client_id: 1,
-        tx_type: TransactionType::Dispute,
-        amount: None,
-    };
+    let dispute = Transaction::new(TransactionType::Dispute, 1, 1, None);
     engine.handle_transaction(dispute).unwrap();
 
-    let chargeback = Transaction {
-        tx_id: 1,
-        client_id: 1,
-        tx_type: TransactionType::Chargeback,
-        amount: None,
-    };
+    let chargeback = Transaction::new(TransactionType::Chargeback, 1, 1, None);
 
     assert!(engine.handle_transaction(chargeback).is_ok());
     let account = engine.accounts.get(&1).unwrap();
@@ -252,14 +175,868 @@ fn test_transaction_on_locked_account() {
         },
     );
 
-    let deposit = Transaction {
-        tx_id: 2,
-        client_id: 1,
-        tx_type: TransactionType::Deposit,
-        amount: Some(50.0),
-    };
+    let deposit = Transaction::new(TransactionType::Deposit, 1, 2, Some(50.0));
 
     let result = engine.handle_transaction(deposit);
     assert!(result.is_err());
-    assert_eq!(result.unwrap_err(), "Account is locked");
-}
\ No newline at end of file
+    assert_eq!(result.unwrap_err().to_string(), "Account is locked");
+}
+
+#[test]
+fn test_zero_amount_rejected_by_default() {
+    let mut engine = TransactionEngine::new();
+    let deposit = Transaction::new(TransactionType::Deposit, 1, 1, Some(0.0));
+
+    let result = engine.handle_transaction(deposit);
+    assert_eq!(
+        result,
+        Err(EngineError::Invalid(ValidationError::AmountNotPositive(TransactionType::Deposit)))
+    );
+    assert!(engine.accounts.is_empty());
+}
+
+#[test]
+fn test_zero_amount_allowed_by_policy() {
+    let mut engine = TransactionEngine::new();
+    engine.validation.zero_amounts = ZeroAmountPolicy::Allow;
+
+    let deposit = Transaction::new(TransactionType::Deposit, 1, 1, Some(0.0));
+    assert!(engine.handle_transaction(deposit).is_ok());
+
+    let withdrawal = Transaction::new(TransactionType::Withdrawal, 1, 2, Some(0.0));
+    assert!(engine.handle_transaction(withdrawal).is_ok());
+
+    let negative = Transaction::new(TransactionType::Deposit, 1, 3, Some(-1.0));
+    let result = engine.handle_transaction(negative);
+    assert_eq!(result.unwrap_err().to_string(), "Deposit amount must be non-negative");
+
+    let account = engine.accounts.get(&1).unwrap();
+    assert_eq!(account.total, 0.0);
+}
+
+#[test]
+fn test_engine_validates_direct_transactions() {
+    let mut engine = TransactionEngine::new();
+    let deposit = Transaction::new(TransactionType::Deposit, 1, 1, Some(f64::NAN));
+
+    let result = engine.handle_transaction(deposit);
+    assert_eq!(result, Err(EngineError::Invalid(ValidationError::NonFiniteAmount)));
+    assert!(result.unwrap_err().is_validation());
+    assert!(engine.accounts.is_empty());
+}
+
+#[test]
+fn test_business_rejections_are_not_validation_errors() {
+    let mut engine = TransactionEngine::new();
+    let withdrawal = Transaction::new(TransactionType::Withdrawal, 1, 1, Some(10.0));
+
+    let result = engine.handle_transaction(withdrawal);
+    assert!(matches!(result, Err(EngineError::AccountNotFound { client_id: 1, .. })));
+    assert!(!result.unwrap_err().is_validation());
+}
+
+
+fn in_currency(mut transaction: Transaction, currency: &str) -> Transaction {
+    transaction.currency = Some(currency.to_string());
+    transaction
+}
+
+#[test]
+fn test_balances_are_kept_per_currency() {
+    let mut engine = TransactionEngine::new();
+    engine.handle_transaction(in_currency(Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0)), "EUR")).unwrap();
+    engine.handle_transaction(in_currency(Transaction::new(TransactionType::Deposit, 1, 2, Some(50.0)), "USD")).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 3, Some(10.0))).unwrap();
+    engine.handle_transaction(in_currency(Transaction::new(TransactionType::Withdrawal, 1, 4, Some(30.0)), "EUR")).unwrap();
+
+    assert_eq!(engine.account(1, Some("EUR")).unwrap().available, 70.0);
+    assert_eq!(engine.account(1, Some("USD")).unwrap().available, 50.0);
+    assert_eq!(engine.account(1, None).unwrap().available, 10.0);
+    assert_eq!(engine.balances().count(), 3);
+    assert!(engine.is_multi_currency());
+
+    // No GBP balance to withdraw from
+    let result = engine.handle_transaction(in_currency(Transaction::new(TransactionType::Withdrawal, 1, 5, Some(1.0)), "GBP"));
+    assert!(matches!(result, Err(EngineError::AccountNotFound { client_id: 1, .. })));
+}
+
+#[test]
+fn test_dispute_applies_in_referenced_currency() {
+    let mut engine = TransactionEngine::new();
+    engine.handle_transaction(in_currency(Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0)), "EUR")).unwrap();
+    engine.handle_transaction(in_currency(Transaction::new(TransactionType::Deposit, 1, 2, Some(40.0)), "USD")).unwrap();
+
+    // The dispute row does not repeat the currency
+    engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 2, None)).unwrap();
+    assert_eq!(engine.account(1, Some("USD")).unwrap().held, 40.0);
+    assert_eq!(engine.account(1, Some("EUR")).unwrap().held, 0.0);
+
+    // A row naming another currency is refused
+    let result = engine.handle_transaction(in_currency(Transaction::new(TransactionType::Resolve, 1, 2, None), "EUR"));
+    assert!(matches!(result, Err(EngineError::CurrencyMismatch { tx_id: 2, .. })));
+
+    engine.handle_transaction(Transaction::new(TransactionType::Chargeback, 1, 2, None)).unwrap();
+    let usd = engine.account(1, Some("USD")).unwrap();
+    assert_eq!(usd.total, 0.0);
+    assert_eq!(usd.held, 0.0);
+
+    // A chargeback locks every balance of the client
+    assert!(engine.account(1, Some("EUR")).unwrap().locked);
+    let result = engine.handle_transaction(in_currency(Transaction::new(TransactionType::Deposit, 1, 3, Some(1.0)), "EUR"));
+    assert_eq!(result, Err(EngineError::AccountLocked(1)));
+}
+
+fn convert(client_id: u16, tx_id: u32, amount: f64, from: &str, to: &str) -> Transaction {
+    let mut transaction = in_currency(Transaction::new(TransactionType::Convert, client_id, tx_id, Some(amount)), from);
+    transaction.to_currency = Some(to.to_string());
+    transaction
+}
+
+fn engine_with_rates() -> TransactionEngine {
+    let mut engine = TransactionEngine::new();
+    engine.rates.insert("EUR", "USD", 1.0825).unwrap();
+    engine.rates.insert("USD", "EUR", 0.3333).unwrap();
+    engine
+}
+
+#[test]
+fn test_convert_between_currencies() {
+    let mut engine = engine_with_rates();
+    engine.handle_transaction(in_currency(Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0)), "EUR")).unwrap();
+
+    engine.handle_transaction(convert(1, 2, 40.0, "EUR", "USD")).unwrap();
+    assert_eq!(engine.account(1, Some("EUR")).unwrap().total, 60.0);
+    assert_eq!(engine.account(1, Some("USD")).unwrap().available, 43.3);
+
+    // The credited amount is rounded to four decimal places
+    engine.handle_transaction(convert(1, 3, 0.01, "USD", "EUR")).unwrap();
+    assert!((engine.account(1, Some("EUR")).unwrap().available - 60.0033).abs() < 1e-9);
+
+    // Missing rate, too small to credit anything, and insufficient funds
+    assert_eq!(
+        engine.handle_transaction(convert(1, 4, 1.0, "EUR", "GBP")),
+        Err(EngineError::NoRate { from: "EUR".to_string(), to: "GBP".to_string() })
+    );
+    assert!(matches!(
+        engine.handle_transaction(convert(1, 5, 0.0001, "USD", "EUR")),
+        Err(EngineError::ConversionTooSmall { .. })
+    ));
+    assert!(matches!(
+        engine.handle_transaction(convert(1, 6, 1000.0, "EUR", "USD")),
+        Err(EngineError::InsufficientFunds { operation: TransactionType::Convert, .. })
+    ));
+    assert!(matches!(
+        engine.handle_transaction(convert(2, 7, 1.0, "EUR", "USD")),
+        Err(EngineError::AccountNotFound { client_id: 2, .. })
+    ));
+}
+
+#[test]
+fn test_conversion_is_disputed_as_a_unit() {
+    let mut engine = engine_with_rates();
+    engine.handle_transaction(in_currency(Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0)), "EUR")).unwrap();
+    engine.handle_transaction(convert(1, 2, 40.0, "EUR", "USD")).unwrap();
+
+    // The dispute holds the credited amount in the target currency
+    engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 2, None)).unwrap();
+    let usd = engine.account(1, Some("USD")).unwrap();
+    assert_eq!((usd.available, usd.held), (0.0, 43.3));
+
+    // Naming the source currency is a mismatch: the funds are held in USD
+    let result = engine.handle_transaction(in_currency(Transaction::new(TransactionType::Chargeback, 1, 2, None), "EUR"));
+    assert!(matches!(result, Err(EngineError::CurrencyMismatch { tx_id: 2, .. })));
+
+    // The chargeback reverses the whole conversion and locks the client
+    engine.handle_transaction(Transaction::new(TransactionType::Chargeback, 1, 2, None)).unwrap();
+    let usd = engine.account(1, Some("USD")).unwrap();
+    assert_eq!((usd.held, usd.total), (0.0, 0.0));
+    let eur = engine.account(1, Some("EUR")).unwrap();
+    assert_eq!((eur.available, eur.total), (100.0, 100.0));
+    assert!(engine.is_locked(1));
+
+    // Each currency is settled against the exchange; nothing is a chargeback loss
+    let ledger = engine.ledger();
+    for currency in ["EUR", "USD"] {
+        assert_eq!(ledger.balance(&LedgerAccount::system(SystemBook::Exchange, Some(currency))), 0.0);
+        assert_eq!(ledger.balance(&LedgerAccount::system(SystemBook::ChargebackLoss, Some(currency))), 0.0);
+    }
+    assert!(ledger.unbalanced(4).is_empty());
+}
+
+fn transfer(client_id: u16, tx_id: u32, amount: f64, to_client: u16) -> Transaction {
+    let mut transaction = Transaction::new(TransactionType::Transfer, client_id, tx_id, Some(amount));
+    transaction.to_client = Some(to_client);
+    transaction
+}
+
+#[test]
+fn test_transfer_moves_funds_atomically() {
+    let mut engine = TransactionEngine::new();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0))).unwrap();
+
+    // The destination account is opened by the transfer
+    engine.handle_transaction(transfer(1, 2, 30.0, 2)).unwrap();
+    assert_eq!(engine.accounts.get(&1).unwrap().total, 70.0);
+    assert_eq!(engine.accounts.get(&2).unwrap().available, 30.0);
+
+    // A refused transfer changes neither side
+    let result = engine.handle_transaction(transfer(1, 3, 80.0, 2));
+    assert!(matches!(result, Err(EngineError::InsufficientFunds { operation: TransactionType::Transfer, .. })));
+    assert_eq!(engine.accounts.get(&1).unwrap().total, 70.0);
+    assert_eq!(engine.accounts.get(&2).unwrap().total, 30.0);
+
+    // Transfers stay within the currency they name
+    engine.handle_transaction(in_currency(Transaction::new(TransactionType::Deposit, 1, 4, Some(5.0)), "EUR")).unwrap();
+    engine.handle_transaction(in_currency(transfer(1, 5, 5.0, 3), "EUR")).unwrap();
+    assert_eq!(engine.account(3, Some("EUR")).unwrap().available, 5.0);
+    assert!(engine.account(3, None).is_none());
+}
+
+#[test]
+fn test_transfer_respects_locks_on_both_sides() {
+    let mut engine = TransactionEngine::new();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 2, 2, Some(10.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Dispute, 2, 2, None)).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Chargeback, 2, 2, None)).unwrap();
+
+    assert_eq!(engine.handle_transaction(transfer(1, 3, 10.0, 2)), Err(EngineError::AccountLocked(2)));
+    assert_eq!(engine.handle_transaction(transfer(2, 4, 10.0, 1)), Err(EngineError::AccountLocked(2)));
+    assert_eq!(engine.accounts.get(&1).unwrap().total, 100.0);
+}
+
+#[test]
+fn test_transfers_cannot_be_disputed() {
+    let mut engine = TransactionEngine::new();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0))).unwrap();
+    engine.handle_transaction(transfer(1, 2, 30.0, 2)).unwrap();
+
+    for tx_type in [TransactionType::Dispute, TransactionType::Resolve, TransactionType::Chargeback] {
+        assert_eq!(
+            engine.handle_transaction(Transaction::new(tx_type, 1, 2, None)),
+            Err(EngineError::NotDisputable { tx_id: 2, kind: TransactionType::Transfer, operation: tx_type })
+        );
+    }
+    assert_eq!(engine.accounts.get(&1).unwrap().held, 0.0);
+    assert!(!engine.is_locked(1));
+}
+
+#[test]
+fn test_partial_disputes_never_exceed_the_original_amount() {
+    let mut engine = TransactionEngine::new();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(0.3))).unwrap();
+
+    engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 1, Some(0.1))).unwrap();
+    assert_eq!(
+        engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 1, Some(0.25))),
+        Err(EngineError::DisputeExceedsRemaining { tx_id: 1, amount: 0.25, remaining: 0.2 })
+    );
+
+    // A dispute without an amount takes whatever is left
+    engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 1, None)).unwrap();
+    assert_eq!(engine.transactions.get(&1).unwrap().disputed, 0.3);
+    assert_eq!(
+        engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 1, Some(0.0001))),
+        Err(EngineError::AlreadyDisputed(1))
+    );
+
+    // Resolved funds are released but do not become disputable again
+    engine.handle_transaction(Transaction::new(TransactionType::Resolve, 1, 1, Some(0.1))).unwrap();
+    let account = engine.accounts.get(&1).unwrap();
+    assert!((account.available - 0.1).abs() < 1e-9);
+    assert!((account.held - 0.2).abs() < 1e-9);
+    assert_eq!(
+        engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 1, None)),
+        Err(EngineError::AlreadyDisputed(1))
+    );
+}
+
+#[test]
+fn test_partial_chargeback() {
+    let mut engine = TransactionEngine::new();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 2, Some(50.0))).unwrap();
+
+    // Resolving or charging back an undisputed transaction is refused even with funds held elsewhere
+    engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 2, None)).unwrap();
+    assert_eq!(
+        engine.handle_transaction(Transaction::new(TransactionType::Resolve, 1, 1, None)),
+        Err(EngineError::NotDisputed { tx_id: 1, operation: TransactionType::Resolve })
+    );
+
+    engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 1, Some(40.0))).unwrap();
+    assert!(matches!(
+        engine.handle_transaction(Transaction::new(TransactionType::Chargeback, 1, 1, Some(40.5))),
+        Err(EngineError::ExceedsDisputedAmount { tx_id: 1, .. })
+    ));
+
+    engine.handle_transaction(Transaction::new(TransactionType::Chargeback, 1, 1, Some(15.0))).unwrap();
+    let account = engine.accounts.get(&1).unwrap();
+    assert_eq!(account.available, 60.0);
+    assert_eq!(account.held, 75.0);
+    assert_eq!(account.total, 135.0);
+    assert!(account.locked);
+    assert_eq!(engine.transactions.get(&1).unwrap().held, 25.0);
+}
+
+#[test]
+fn test_conversions_are_only_disputed_whole() {
+    let mut engine = engine_with_rates();
+    engine.handle_transaction(in_currency(Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0)), "EUR")).unwrap();
+    engine.handle_transaction(convert(1, 2, 40.0, "EUR", "USD")).unwrap();
+
+    assert_eq!(
+        engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 2, Some(10.0))),
+        Err(EngineError::PartialConversionDispute(2))
+    );
+}
+
+#[test]
+fn test_authorize_then_capture_or_void() {
+    let mut engine = TransactionEngine::new();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0))).unwrap();
+
+    engine.handle_transaction(Transaction::new(TransactionType::Authorize, 1, 2, Some(30.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Authorize, 1, 3, Some(20.0))).unwrap();
+    let account = engine.accounts.get(&1).unwrap();
+    assert_eq!((account.available, account.held, account.total), (50.0, 50.0, 100.0));
+    assert_eq!(engine.outstanding_authorizations().len(), 2);
+
+    // A partial capture settles the captured amount and releases the rest
+    engine.handle_transaction(Transaction::new(TransactionType::Capture, 1, 2, Some(25.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Void, 1, 3, None)).unwrap();
+    let account = engine.accounts.get(&1).unwrap();
+    assert_eq!((account.available, account.held, account.total), (75.0, 0.0, 75.0));
+    assert!(engine.outstanding_authorizations().is_empty());
+
+    // Closed authorizations, deposits and disputes do not mix
+    assert_eq!(
+        engine.handle_transaction(Transaction::new(TransactionType::Void, 1, 2, None)),
+        Err(EngineError::AuthorizationClosed {
+            tx_id: 2,
+            state: AuthorizationState::Captured,
+            operation: TransactionType::Void
+        })
+    );
+    assert_eq!(
+        engine.handle_transaction(Transaction::new(TransactionType::Capture, 1, 1, None)),
+        Err(EngineError::NotAnAuthorization { tx_id: 1, operation: TransactionType::Capture })
+    );
+    assert!(matches!(
+        engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 3, None)),
+        Err(EngineError::NotDisputable { tx_id: 3, kind: TransactionType::Authorize, .. })
+    ));
+}
+
+#[test]
+fn test_authorization_cannot_replace_a_deposit() {
+    let mut engine = TransactionEngine::new();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Authorize, 1, 2, Some(10.0))).unwrap();
+
+    assert_eq!(
+        engine.handle_transaction(Transaction::new(TransactionType::Authorize, 1, 1, Some(30.0))),
+        Err(EngineError::DuplicateTransaction { tx_id: 1, existing: TransactionType::Deposit })
+    );
+    assert_eq!(
+        engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 2, Some(5.0))),
+        Err(EngineError::DuplicateTransaction { tx_id: 2, existing: TransactionType::Authorize })
+    );
+
+    // Capture and void still find the right records, and the deposit can still be disputed
+    assert_eq!(
+        engine.handle_transaction(Transaction::new(TransactionType::Capture, 1, 1, None)),
+        Err(EngineError::NotAnAuthorization { tx_id: 1, operation: TransactionType::Capture })
+    );
+    engine.handle_transaction(Transaction::new(TransactionType::Void, 1, 2, None)).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 1, None)).unwrap();
+    let account = engine.accounts.get(&1).unwrap();
+    assert_eq!((account.available, account.held, account.total), (0.0, 100.0, 100.0));
+
+    let error = EngineError::NotDisputable { tx_id: 2, kind: TransactionType::Authorize, operation: TransactionType::Dispute };
+    assert_eq!(error.to_string(), "Transaction with ID 2 has type authorize and cannot be the target of a dispute");
+}
+
+#[test]
+fn test_authorization_limits() {
+    let mut engine = TransactionEngine::new();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(10.0))).unwrap();
+
+    assert!(matches!(
+        engine.handle_transaction(Transaction::new(TransactionType::Authorize, 1, 2, Some(10.5))),
+        Err(EngineError::InsufficientFunds { operation: TransactionType::Authorize, .. })
+    ));
+
+    engine.handle_transaction(Transaction::new(TransactionType::Authorize, 1, 3, Some(10.0))).unwrap();
+    assert_eq!(
+        engine.handle_transaction(Transaction::new(TransactionType::Capture, 1, 3, Some(11.0))),
+        Err(EngineError::CaptureExceedsAuthorization { tx_id: 3, amount: 11.0, authorized: 10.0 })
+    );
+    assert!(matches!(
+        engine.handle_transaction(Transaction::new(TransactionType::Capture, 2, 3, None)),
+        Err(EngineError::ClientMismatch { expected: 1, got: 2, .. })
+    ));
+    assert_eq!(
+        engine.handle_transaction(Transaction::new(TransactionType::Void, 1, 3, Some(1.0))),
+        Err(EngineError::Invalid(ValidationError::UnexpectedAmount(TransactionType::Void)))
+    );
+}
+
+#[test]
+fn test_authorizations_expire() {
+    let mut engine = TransactionEngine::new();
+    engine.authorization_expiry = Some(2);
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Authorize, 1, 2, Some(40.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Authorize, 1, 3, Some(10.0))).unwrap();
+
+    // Still within two rows of the first authorization
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 4, Some(1.0))).unwrap();
+    assert_eq!(engine.accounts.get(&1).unwrap().held, 50.0);
+
+    // On the third row after it, the first authorization has expired and is released
+    engine.handle_transaction(Transaction::new(TransactionType::Capture, 1, 3, None)).unwrap();
+    assert_eq!(engine.transactions.get(&2).unwrap().authorization, Some(AuthorizationState::Expired));
+    let account = engine.accounts.get(&1).unwrap();
+    assert_eq!((account.available, account.held, account.total), (91.0, 0.0, 91.0));
+
+    assert!(matches!(
+        engine.handle_transaction(Transaction::new(TransactionType::Capture, 1, 2, None)),
+        Err(EngineError::AuthorizationClosed { state: AuthorizationState::Expired, .. })
+    ));
+}
+
+fn refund(client_id: u16, tx_id: u32, amount: f64, ref_tx: u32) -> Transaction {
+    let mut transaction = Transaction::new(TransactionType::Refund, client_id, tx_id, Some(amount));
+    transaction.ref_tx = Some(ref_tx);
+    transaction
+}
+
+#[test]
+fn test_refunds_never_exceed_the_deposit() {
+    let mut engine = TransactionEngine::new();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 1, 2, Some(10.0))).unwrap();
+
+    engine.handle_transaction(refund(1, 3, 60.0, 1)).unwrap();
+    engine.handle_transaction(refund(1, 4, 30.0, 1)).unwrap();
+    let account = engine.accounts.get(&1).unwrap();
+    assert_eq!((account.available, account.total), (0.0, 0.0));
+    assert_eq!(engine.transactions.get(&1).unwrap().refunded, 90.0);
+
+    assert_eq!(
+        engine.handle_transaction(refund(1, 5, 10.5, 1)),
+        Err(EngineError::RefundExceedsRemaining { tx_id: 1, amount: 10.5, remaining: 10.0 })
+    );
+    assert_eq!(
+        engine.handle_transaction(refund(1, 6, 1.0, 2)),
+        Err(EngineError::NotRefundable { tx_id: 2, kind: TransactionType::Withdrawal })
+    );
+    assert!(matches!(
+        engine.handle_transaction(refund(1, 7, 1.0, 99)),
+        Err(EngineError::TransactionNotFound { tx_id: 99, operation: TransactionType::Refund })
+    ));
+    assert!(matches!(
+        engine.handle_transaction(refund(2, 8, 1.0, 1)),
+        Err(EngineError::ClientMismatch { expected: 1, got: 2, .. })
+    ));
+}
+
+#[test]
+fn test_refunds_and_disputes_share_the_deposit() {
+    let mut engine = TransactionEngine::new();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0))).unwrap();
+
+    // Refunded money can no longer be disputed
+    engine.handle_transaction(refund(1, 2, 70.0, 1)).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 1, None)).unwrap();
+    assert_eq!(engine.accounts.get(&1).unwrap().held, 30.0);
+
+    // Disputed money can no longer be refunded
+    assert!(matches!(
+        engine.handle_transaction(refund(1, 3, 1.0, 1)),
+        Err(EngineError::RefundExceedsRemaining { remaining, .. }) if remaining == 0.0
+    ));
+
+    // The refund itself is not a payment that can be disputed
+    assert!(matches!(
+        engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 2, None)),
+        Err(EngineError::NotDisputable { kind: TransactionType::Refund, .. })
+    ));
+
+    // Resolved money is back with the client and may be refunded after all
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 4, Some(50.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 4, Some(20.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Resolve, 1, 4, None)).unwrap();
+    engine.handle_transaction(refund(1, 5, 50.0, 4)).unwrap();
+    assert_eq!(engine.transactions.get(&4).unwrap().refunded, 50.0);
+}
+
+#[test]
+fn test_duplicate_transaction_ids_are_rejected() {
+    let mut engine = engine_with_rates();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0))).unwrap();
+    engine.handle_transaction(in_currency(Transaction::new(TransactionType::Deposit, 1, 2, Some(50.0)), "EUR")).unwrap();
+
+    let duplicates = [
+        Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0)),
+        Transaction::new(TransactionType::Withdrawal, 1, 1, Some(1.0)),
+        refund(1, 1, 10.0, 1),
+        convert(1, 1, 10.0, "EUR", "USD"),
+        transfer(1, 1, 10.0, 2),
+        Transaction::new(TransactionType::Authorize, 1, 1, Some(10.0)),
+    ];
+    for duplicate in duplicates {
+        assert_eq!(
+            engine.handle_transaction(duplicate),
+            Err(EngineError::DuplicateTransaction { tx_id: 1, existing: TransactionType::Deposit })
+        );
+    }
+    let account = engine.accounts.get(&1).unwrap();
+    assert_eq!((account.available, account.held), (100.0, 0.0));
+
+    // The deposit is still there to be disputed, once
+    engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 1, None)).unwrap();
+    assert!(engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 1, None)).is_err());
+    assert_eq!(engine.accounts.get(&1).unwrap().held, 100.0);
+
+    // Rows acting on an id are not duplicates, and refused rows leave their id free
+    assert!(engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 1, 3, Some(1000.0))).is_err());
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 3, Some(1.0))).unwrap();
+}
+
+fn engine_with_fees() -> TransactionEngine {
+    let mut engine = engine_with_rates();
+    engine
+        .fees
+        .read_rules("type,tier,flat,percent\nwithdrawal,,1,0\nwithdrawal,vip,0,0\ntransfer,,0,1\nconvert,,0.5,0\nchargeback,,25,0\n".as_bytes())
+        .unwrap();
+    engine.fees.set_tier(2, "vip");
+    engine.fees.house_account = Some(100);
+    engine.keep_fee_lines();
+    engine
+}
+
+#[test]
+fn test_fees_are_credited_to_the_house_account() {
+    let mut engine = engine_with_fees();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 2, 2, Some(100.0))).unwrap();
+
+    engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 1, 3, Some(10.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 2, 4, Some(10.0))).unwrap();
+    engine.handle_transaction(transfer(1, 5, 50.0, 2)).unwrap();
+
+    assert_eq!(engine.accounts.get(&1).unwrap().total, 38.5);
+    assert_eq!(engine.accounts.get(&2).unwrap().total, 140.0);
+    let house = engine.accounts.get(&100).unwrap();
+    assert_eq!((house.available, house.total), (1.5, 1.5));
+
+    // Fee lines record who paid what on which transaction; the vip tier pays nothing
+    let lines: Vec<_> = engine.fee_lines().iter().map(|l| (l.tx_id, l.client_id, l.tx_type, l.amount)).collect();
+    assert_eq!(lines, vec![(3, 1, TransactionType::Withdrawal, 1.0), (5, 1, TransactionType::Transfer, 0.5)]);
+
+    // The fee has to be covered along with the amount
+    assert_eq!(
+        engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 1, 6, Some(38.0))),
+        Err(EngineError::InsufficientFunds { available: 38.5, amount: 39.0, operation: TransactionType::Withdrawal })
+    );
+}
+
+#[test]
+fn test_conversion_and_chargeback_fees() {
+    let mut engine = engine_with_fees();
+    engine.handle_transaction(in_currency(Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0)), "EUR")).unwrap();
+    engine.handle_transaction(in_currency(Transaction::new(TransactionType::Deposit, 1, 2, Some(10.0)), "EUR")).unwrap();
+
+    // Conversion fees are charged in the source currency
+    engine.handle_transaction(convert(1, 3, 40.0, "EUR", "USD")).unwrap();
+    assert_eq!(engine.account(1, Some("EUR")).unwrap().available, 69.5);
+    assert_eq!(engine.account(100, Some("EUR")).unwrap().total, 0.5);
+
+    // The chargeback fee takes no more than what is available
+    engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 2, None)).unwrap();
+    engine.handle_transaction(in_currency(Transaction::new(TransactionType::Withdrawal, 1, 4, Some(50.0)), "EUR")).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Chargeback, 1, 2, None)).unwrap();
+
+    let eur = engine.account(1, Some("EUR")).unwrap();
+    assert_eq!((eur.available, eur.held, eur.total), (0.0, 0.0, 0.0));
+    assert!(eur.locked);
+    assert_eq!(engine.account(100, Some("EUR")).unwrap().total, 10.0);
+    assert_eq!(engine.fee_lines().last().unwrap().amount, 8.5);
+}
+
+#[test]
+fn test_fees_stay_in_the_fees_book_while_the_house_account_is_locked() {
+    let mut engine = engine_with_fees();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 100, 2, Some(5.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Dispute, 100, 2, None)).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Chargeback, 100, 2, None)).unwrap();
+    assert!(engine.is_locked(100));
+
+    // The client still pays the fee, but the locked house account takes nothing
+    engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 1, 3, Some(10.0))).unwrap();
+    assert_eq!(engine.accounts.get(&1).unwrap().total, 89.0);
+    assert_eq!(engine.accounts.get(&100).unwrap().total, 0.0);
+    assert_eq!(engine.ledger().balance(&LedgerAccount::system(SystemBook::Fees, None)), 1.0);
+    assert_eq!(engine.fee_lines().last().unwrap().house_account, None);
+}
+
+#[test]
+fn test_fee_lines_are_only_kept_on_request() {
+    let mut engine = TransactionEngine::new();
+    engine.fees.read_rules("type,tier,flat,percent\nwithdrawal,,1,0\n".as_bytes()).unwrap();
+    engine.fees.house_account = Some(100);
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0))).unwrap();
+
+    engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 1, 2, Some(10.0))).unwrap();
+    assert!(engine.fee_lines().is_empty());
+
+    engine.keep_fee_lines();
+    engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 1, 3, Some(10.0))).unwrap();
+    assert_eq!(engine.fee_lines().len(), 1);
+    assert_eq!(engine.accounts.get(&100).unwrap().total, 2.0);
+}
+
+#[test]
+fn test_withdrawal_limits() {
+    let mut engine = TransactionEngine::new();
+    engine.limits.defaults = Limits { max_withdrawal: Some(50.0), max_withdrawals: Some(2), max_deposit_volume: None };
+    engine.limits.window = 4;
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(200.0))).unwrap();
+
+    assert_eq!(
+        engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 1, 2, Some(60.0))),
+        Err(EngineError::LimitExceeded {
+            client_id: 1,
+            breach: LimitBreach::SingleWithdrawal { amount: 60.0, max: 50.0 },
+            locked: false,
+        })
+    );
+
+    engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 1, 3, Some(10.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 1, 4, Some(10.0))).unwrap();
+    assert_eq!(
+        engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 1, 5, Some(10.0))),
+        Err(EngineError::LimitExceeded {
+            client_id: 1,
+            breach: LimitBreach::WithdrawalCount { max: 2, window: LimitWindow::Rows(4) },
+            locked: false,
+        })
+    );
+
+    // Rows of other clients do not move the window
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 2, 6, Some(1.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 2, 7, Some(1.0))).unwrap();
+    assert!(engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 1, 8, Some(10.0))).is_err());
+
+    // Once the first withdrawal leaves the client's window another one is accepted
+    engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 1, 9, Some(10.0))).unwrap();
+    assert_eq!(engine.accounts.get(&1).unwrap().total, 170.0);
+}
+
+#[test]
+fn test_velocity_limits_use_timestamps_when_a_period_is_set() {
+    let mut engine = TransactionEngine::new();
+    engine.limits.defaults = Limits { max_withdrawals: Some(1), ..Limits::default() };
+    engine.limits.window = 1;
+    engine.limits.period = Some(TimeDelta::hours(1));
+    let at = |tx_type, tx_id, amount, time: &str| Transaction {
+        timestamp: Some(time.parse().unwrap()),
+        ..Transaction::new(tx_type, 1, tx_id, Some(amount))
+    };
+
+    engine.handle_transaction(at(TransactionType::Deposit, 1, 100.0, "2024-01-01T10:00:00Z")).unwrap();
+    engine.handle_transaction(at(TransactionType::Withdrawal, 2, 10.0, "2024-01-01T10:10:00Z")).unwrap();
+    engine.handle_transaction(at(TransactionType::Deposit, 3, 1.0, "2024-01-01T10:20:00Z")).unwrap();
+
+    // The withdrawal left the row window, but not the hour
+    assert_eq!(
+        engine.handle_transaction(at(TransactionType::Withdrawal, 4, 10.0, "2024-01-01T11:00:00Z")),
+        Err(EngineError::LimitExceeded {
+            client_id: 1,
+            breach: LimitBreach::WithdrawalCount { max: 1, window: LimitWindow::Period(TimeDelta::hours(1)) },
+            locked: false,
+        })
+    );
+    engine.handle_transaction(at(TransactionType::Withdrawal, 5, 10.0, "2024-01-01T11:10:00Z")).unwrap();
+
+    // Without a timestamp the row window applies
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 6, Some(1.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 1, 7, Some(10.0))).unwrap();
+    assert_eq!(engine.accounts.get(&1).unwrap().total, 72.0);
+}
+
+#[test]
+fn test_deposit_volume_breach_locks_the_client() {
+    let mut engine = TransactionEngine::new();
+    engine.limits.set_limits(1, Limits { max_deposit_volume: Some(100.0), ..Limits::default() });
+    engine.limits.on_breach = BreachAction::Lock;
+
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(60.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 2, 2, Some(60.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 2, 3, Some(60.0))).unwrap();
+    assert_eq!(
+        engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 4, Some(50.0))),
+        Err(EngineError::LimitExceeded {
+            client_id: 1,
+            breach: LimitBreach::DepositVolume { volume: 110.0, max: 100.0, window: LimitWindow::Rows(1000) },
+            locked: true,
+        })
+    );
+
+    let account = engine.accounts.get(&1).unwrap();
+    assert_eq!((account.total, account.locked), (60.0, true));
+    assert_eq!(
+        engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 5, Some(1.0))),
+        Err(EngineError::AccountLocked(1))
+    );
+}
+
+#[test]
+fn test_breach_never_opens_an_account_to_lock_it() {
+    let mut engine = TransactionEngine::new();
+    engine.limits.defaults = Limits { max_deposit_volume: Some(100.0), ..Limits::default() };
+    engine.limits.on_breach = BreachAction::Lock;
+
+    assert_eq!(
+        engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(150.0))),
+        Err(EngineError::LimitExceeded {
+            client_id: 1,
+            breach: LimitBreach::DepositVolume { volume: 150.0, max: 100.0, window: LimitWindow::Rows(1000) },
+            locked: false,
+        })
+    );
+    assert!(engine.accounts.is_empty());
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 2, Some(50.0))).unwrap();
+    assert!(!engine.is_locked(1));
+}
+
+#[test]
+fn test_overdraft_down_to_the_credit_limit() {
+    let mut engine = TransactionEngine::new();
+    engine.credit.insert(1, None, 100.0).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(50.0))).unwrap();
+
+    engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 1, 2, Some(120.0))).unwrap();
+    let account = engine.accounts.get(&1).unwrap();
+    assert_eq!((account.available, account.total), (-70.0, -70.0));
+
+    assert_eq!(
+        engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 1, 3, Some(40.0))),
+        Err(EngineError::InsufficientFunds { available: 30.0, amount: 40.0, operation: TransactionType::Withdrawal })
+    );
+
+    // A dispute may draw on the rest of the overdraft
+    engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 1, Some(30.0))).unwrap();
+    let account = engine.accounts.get(&1).unwrap();
+    assert_eq!((account.available, account.held, account.total), (-100.0, 30.0, -70.0));
+    assert!(matches!(
+        engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 1, Some(10.0))),
+        Err(EngineError::InsufficientFunds { .. })
+    ));
+
+    // Clients without a credit limit cannot overdraw
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 2, 4, Some(10.0))).unwrap();
+    assert!(matches!(
+        engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 2, 5, Some(10.5))),
+        Err(EngineError::InsufficientFunds { .. })
+    ));
+}
+
+#[test]
+fn test_credit_line_without_a_prior_deposit() {
+    let mut engine = TransactionEngine::new();
+    engine.credit.insert(1, None, 100.0).unwrap();
+
+    // The withdrawal opens the balance and overdraws it straight away
+    engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 1, 1, Some(60.0))).unwrap();
+    let account = engine.accounts.get(&1).unwrap();
+    assert_eq!((account.available, account.total), (-60.0, -60.0));
+    assert_eq!(engine.ledger().balance(&LedgerAccount::system(SystemBook::ExternalFunding, None)), 60.0);
+
+    // Without a credit line there is still no balance to withdraw from
+    assert!(matches!(
+        engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 2, 2, Some(1.0))),
+        Err(EngineError::AccountNotFound { client_id: 2, .. })
+    ));
+}
+
+fn at(mut transaction: Transaction, timestamp: &str) -> Transaction {
+    transaction.timestamp = Some(timestamp.parse().unwrap());
+    transaction
+}
+
+#[test]
+fn test_timestamps_must_not_go_back_beyond_the_tolerance() {
+    let mut engine = TransactionEngine::new();
+    engine.time.tolerance = TimeDelta::minutes(5);
+
+    engine.handle_transaction(at(Transaction::new(TransactionType::Deposit, 1, 1, Some(10.0)), "2024-03-01T12:00:00Z")).unwrap();
+    engine.handle_transaction(at(Transaction::new(TransactionType::Deposit, 1, 2, Some(10.0)), "2024-03-01T11:56:00Z")).unwrap();
+    assert_eq!(engine.latest_timestamp(), Some("2024-03-01T12:00:00Z".parse().unwrap()));
+
+    assert_eq!(
+        engine.handle_transaction(at(Transaction::new(TransactionType::Deposit, 1, 3, Some(10.0)), "2024-03-01T11:54:59Z")),
+        Err(EngineError::OutOfOrder {
+            tx_id: 3,
+            timestamp: "2024-03-01T11:54:59Z".parse().unwrap(),
+            latest: "2024-03-01T12:00:00Z".parse().unwrap(),
+        })
+    );
+
+    // Rows without a timestamp are not ordered
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 4, Some(10.0))).unwrap();
+    assert_eq!(engine.accounts.get(&1).unwrap().total, 30.0);
+}
+
+#[test]
+fn test_refused_rows_do_not_move_the_timestamp_clock() {
+    let mut engine = TransactionEngine::new();
+
+    engine.handle_transaction(at(Transaction::new(TransactionType::Deposit, 1, 1, Some(10.0)), "2024-03-01T12:00:00Z")).unwrap();
+
+    // Refused for want of funds, so its far-future stamp is not kept
+    assert_eq!(
+        engine.handle_transaction(at(Transaction::new(TransactionType::Withdrawal, 1, 2, Some(50.0)), "2099-01-01T00:00:00Z")),
+        Err(EngineError::InsufficientFunds { available: 10.0, amount: 50.0, operation: TransactionType::Withdrawal })
+    );
+    assert_eq!(engine.latest_timestamp(), Some("2024-03-01T12:00:00Z".parse().unwrap()));
+
+    engine.handle_transaction(at(Transaction::new(TransactionType::Deposit, 1, 3, Some(10.0)), "2024-03-01T12:01:00Z")).unwrap();
+    assert_eq!(engine.latest_timestamp(), Some("2024-03-01T12:01:00Z".parse().unwrap()));
+    assert_eq!(engine.accounts.get(&1).unwrap().total, 20.0);
+}
+
+#[test]
+fn test_disputes_only_within_the_window() {
+    let mut engine = TransactionEngine::new();
+    engine.time.dispute_window = Some(TimeDelta::days(120));
+
+    engine.handle_transaction(at(Transaction::new(TransactionType::Deposit, 1, 1, Some(10.0)), "2024-01-01T00:00:00Z")).unwrap();
+    engine.handle_transaction(at(Transaction::new(TransactionType::Deposit, 1, 2, Some(10.0)), "2024-03-01T00:00:00Z")).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 3, Some(10.0))).unwrap();
+
+    // Within the window
+    engine.handle_transaction(at(Transaction::new(TransactionType::Dispute, 1, 2, None), "2024-05-01T00:00:00Z")).unwrap();
+
+    assert_eq!(
+        engine.handle_transaction(at(Transaction::new(TransactionType::Dispute, 1, 1, None), "2024-05-01T00:00:01Z")),
+        Err(EngineError::DisputeWindowClosed {
+            tx_id: 1,
+            occurred: "2024-01-01T00:00:00Z".parse().unwrap(),
+            disputed: "2024-05-01T00:00:01Z".parse().unwrap(),
+        })
+    );
+
+    // A dispute without a timestamp is timed by the latest one seen
+    assert!(matches!(
+        engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 1, None)),
+        Err(EngineError::DisputeWindowClosed { .. })
+    ));
+
+    // Transactions without a timestamp can always be disputed
+    engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 3, None)).unwrap();
+    assert_eq!(engine.accounts.get(&1).unwrap().held, 20.0);
+}
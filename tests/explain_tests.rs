@@ -0,0 +1,109 @@
+use indicatif::ProgressBar;
+use toy_payments_engine::engine::TransactionEngine;
+use toy_payments_engine::explain::{Explanation, Lifecycle};
+use toy_payments_engine::utils::process_reader_rows;
+
+async fn explain(engine: &mut TransactionEngine, input: &str, tx_id: u32) -> Explanation {
+    let mut explanation = Explanation::new(tx_id);
+    process_reader_rows(input.as_bytes(), &ProgressBar::hidden(), |report, transaction, line| {
+        let (client_id, tx_id) = (transaction.client_id, transaction.tx_id);
+        let result = explanation.handle(engine, transaction, line);
+        report.record(line, client_id, tx_id, &result);
+        Ok(())
+    })
+    .await
+    .unwrap();
+    explanation
+}
+
+#[tokio::test]
+async fn test_explain_follows_the_transaction_and_its_references() {
+    let input = "type,client,tx,amount,currency,to_currency,to_client,ref_tx\n\
+                 deposit,1,7,10.0,,,,\n\
+                 deposit,2,8,3.0,,,,\n\
+                 dispute,1,7,50.0,,,,\n\
+                 dispute,1,7,4.0,,,,\n\
+                 chargeback,1,7,,,,,\n\
+                 refund,1,9,2.0,,,,7\n";
+    let explanation = explain(&mut TransactionEngine::new(), input, 7).await;
+
+    let rows: Vec<_> = explanation
+        .rows
+        .iter()
+        .map(|row| (row.line, row.role, row.validation, row.decision, row.rule))
+        .collect();
+    assert_eq!(
+        rows,
+        vec![
+            (Some(2), "original", "passed", "accepted", None),
+            (Some(4), "reference", "passed", "rejected", Some("dispute_exceeds_remaining")),
+            (Some(5), "reference", "passed", "accepted", None),
+            (Some(6), "reference", "passed", "accepted", None),
+            (Some(7), "reference", "passed", "rejected", Some("account_locked")),
+        ]
+    );
+    assert_eq!(
+        explanation.lifecycle,
+        Some(Lifecycle { state: "charged_back", amount: 10.0, disputed: 4.0, held: 0.0, refunded: 0.0, credited: None })
+    );
+}
+
+#[tokio::test]
+async fn test_explain_a_refund_after_a_resolved_dispute() {
+    let input = "type,client,tx,amount,ref_tx\n\
+                 deposit,1,7,10.0,\n\
+                 dispute,1,7,4.0,\n\
+                 resolve,1,7,,\n\
+                 refund,1,9,3.0,7\n";
+    let mut engine = TransactionEngine::new();
+    let explanation = explain(&mut engine, input, 7).await;
+    assert_eq!(
+        explanation.lifecycle,
+        Some(Lifecycle { state: "partially_refunded", amount: 10.0, disputed: 4.0, held: 0.0, refunded: 3.0, credited: None })
+    );
+
+    let explanation = explain(&mut engine, "type,client,tx,amount,ref_tx\nrefund,1,10,7.0,7\n", 7).await;
+    assert_eq!(explanation.lifecycle.map(|lifecycle| lifecycle.state), Some("refunded"));
+}
+
+#[tokio::test]
+async fn test_explain_a_refused_transaction() {
+    let input = "type,client,tx,amount\n\
+                 deposit,1,1,1.00001\n\
+                 withdrawal,1,1,5.0\n";
+    let explanation = explain(&mut TransactionEngine::new(), input, 1).await;
+
+    assert_eq!(explanation.rows[0].validation, "excess_precision");
+    assert_eq!(explanation.rows[0].decision, "invalid");
+    assert_eq!(explanation.rows[1].rule, Some("account_not_found"));
+    assert_eq!(explanation.lifecycle, None);
+
+    let mut text = Vec::new();
+    explanation.write_text(&mut text).unwrap();
+    assert_eq!(
+        String::from_utf8(text).unwrap(),
+        "Transaction 1\n\
+         Line 2 (original): deposit client 1 tx 1 amount 1.00001\n  \
+         validation: excess_precision\n  \
+         decision: invalid by excess_precision: Amount 1.00001 has more than 4 decimal places\n\
+         Line 3 (original): withdrawal client 1 tx 1 amount 5\n  \
+         validation: passed\n  \
+         decision: rejected by account_not_found: Account for Client ID 1 not found. Cannot process withdrawal.\n\
+         State: never accepted\n"
+    );
+}
+
+#[tokio::test]
+async fn test_explain_an_expired_authorization() {
+    let mut engine = TransactionEngine::new();
+    engine.authorization_expiry = Some(1);
+    let input = "type,client,tx,amount\n\
+                 deposit,1,1,10.0\n\
+                 authorize,1,2,4.0\n\
+                 deposit,1,3,1.0\n\
+                 deposit,1,4,1.0\n";
+    let explanation = explain(&mut engine, input, 2).await;
+
+    assert_eq!(explanation.rows.len(), 1);
+    assert_eq!(explanation.lifecycle.unwrap().state, "expired");
+}
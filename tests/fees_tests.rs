@@ -0,0 +1,53 @@
+use toy_payments_engine::fees::{FeeError, FeeSchedule};
+use toy_payments_engine::models::TransactionType;
+
+fn schedule(rules: &str, tiers: &str) -> FeeSchedule {
+    let mut schedule = FeeSchedule::new();
+    schedule.read_rules(rules.as_bytes()).unwrap();
+    schedule.read_tiers(tiers.as_bytes()).unwrap();
+    schedule.house_account = Some(999);
+    schedule
+}
+
+#[test]
+fn test_fee_uses_the_client_tier_first() {
+    let schedule = schedule(
+        "type,tier,flat,percent\nwithdrawal,,1.0,0.5\nwithdrawal,gold,0,0.1\nchargeback,,15,0\n",
+        "client,tier\n1,gold\n2,silver\n",
+    );
+
+    assert_eq!(schedule.tier(1), Some("gold"));
+    assert!((schedule.fee(1, TransactionType::Withdrawal, 100.0) - 0.1).abs() < 1e-12);
+    // No silver rule, so the rule for every client applies
+    assert!((schedule.fee(2, TransactionType::Withdrawal, 100.0) - 1.5).abs() < 1e-12);
+    assert_eq!(schedule.fee(3, TransactionType::Chargeback, 100.0), 15.0);
+    assert_eq!(schedule.fee(3, TransactionType::Transfer, 100.0), 0.0);
+}
+
+#[test]
+fn test_no_fee_without_house_account() {
+    let mut schedule = schedule("type,tier,flat,percent\nwithdrawal,,1.0,0\n", "client,tier\n");
+    assert!(schedule.is_active());
+
+    schedule.house_account = None;
+    assert!(!schedule.is_active());
+    assert_eq!(schedule.fee(1, TransactionType::Withdrawal, 100.0), 0.0);
+}
+
+#[test]
+fn test_fee_schedule_rejects_bad_rules() {
+    let cases = [
+        "type,tier,flat,percent\ndeposit,,1,0\n",
+        "type,tier,flat,percent\nwithdrawal,,-1,0\n",
+        "type,tier,flat,percent\nwithdrawal,,0,101\n",
+        "type,tier,flat,percent\nwithdrawal,gold,1,0\nwithdrawal,gold,2,0\n",
+        "type,tier,flat,percent\nteleport,,1,0\n",
+    ];
+
+    for rules in cases {
+        assert!(FeeSchedule::new().read_rules(rules.as_bytes()).is_err(), "{}", rules);
+    }
+
+    let unsupported = FeeSchedule::new().read_rules(cases[0].as_bytes()).unwrap_err();
+    assert!(matches!(unsupported, FeeError::UnsupportedType(TransactionType::Deposit)));
+}
@@ -0,0 +1,149 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{oneshot, Mutex};
+use toy_payments_engine::engine::TransactionEngine;
+use toy_payments_engine::follow::{self, Export, Follower};
+use toy_payments_engine::server::SharedEngine;
+
+fn append(path: &std::path::Path, data: &str) {
+    let mut file = OpenOptions::new().create(true).append(true).open(path).unwrap();
+    file.write_all(data.as_bytes()).unwrap();
+}
+
+#[test]
+fn test_follower_waits_for_complete_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("feed.csv");
+    let mut follower = Follower::new(&path);
+    let mut engine = TransactionEngine::new();
+
+    // The file does not exist yet
+    follower.poll(&mut engine).unwrap();
+
+    append(&path, "type,client,tx,amount\ndeposit,1,1,10.0\ndeposit,1,2,5");
+    follower.poll(&mut engine).unwrap();
+    assert_eq!(follower.report.accepted, 1);
+    assert_eq!(engine.accounts.get(&1).unwrap().total, 10.0);
+
+    // The rest of the last line arrives
+    append(&path, ".5\nwithdrawal,1,3,100.0\n");
+    follower.poll(&mut engine).unwrap();
+    assert_eq!(follower.report.accepted, 2);
+    assert_eq!(follower.report.rejections.len(), 1);
+    assert_eq!(follower.report.rejections[0].line, Some(4));
+    assert_eq!(engine.accounts.get(&1).unwrap().total, 15.5);
+}
+
+#[test]
+fn test_follower_survives_rotation_and_truncation() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("feed.csv");
+    let mut follower = Follower::new(&path);
+    let mut engine = TransactionEngine::new();
+
+    append(&path, "type,client,tx,amount\ndeposit,1,1,10.0\n");
+    follower.poll(&mut engine).unwrap();
+
+    // Lines written just before the rotation are not lost
+    append(&path, "deposit,1,2,1.0\n");
+    fs::rename(&path, dir.path().join("feed.csv.1")).unwrap();
+    append(&path, "type,client,tx,amount\ndeposit,2,3,7.0\n");
+    follower.poll(&mut engine).unwrap();
+    assert_eq!(engine.accounts.get(&1).unwrap().total, 11.0);
+    assert_eq!(engine.accounts.get(&2).unwrap().total, 7.0);
+
+    // Truncated in place and started over
+    fs::write(&path, "").unwrap();
+    follower.poll(&mut engine).unwrap();
+    append(&path, "type,client,tx,amount\ndeposit,3,4,2.0\n");
+    follower.poll(&mut engine).unwrap();
+    assert_eq!(engine.accounts.get(&3).unwrap().total, 2.0);
+    assert_eq!(follower.report.accepted, 4);
+    assert!(follower.report.rejections.is_empty());
+}
+
+#[test]
+fn test_follower_numbers_every_physical_line() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("feed.csv");
+    let mut follower = Follower::new(&path);
+    let mut engine = TransactionEngine::new();
+
+    append(&path, "type,client,tx,amount\n\ndeposit,1,1,10.0\n\n\ndeposit,1,2,ten\n");
+    follower.poll(&mut engine).unwrap();
+    assert_eq!(follower.report.accepted, 1);
+    assert_eq!(follower.report.rejections[0].line, Some(6));
+}
+
+#[test]
+fn test_follower_skips_overlong_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("feed.csv");
+    let mut follower = Follower::new(&path);
+    let mut engine = TransactionEngine::new();
+
+    // The long line arrives over several polls; none of it is kept
+    append(&path, "type,client,tx,amount\ndeposit,1,1,10.0\ndeposit,1,2,");
+    follower.poll(&mut engine).unwrap();
+    append(&path, &"1".repeat(70 * 1024));
+    follower.poll(&mut engine).unwrap();
+    append(&path, &"1".repeat(1024));
+    append(&path, "\ndeposit,1,3,5.0\n");
+    follower.poll(&mut engine).unwrap();
+
+    assert_eq!(follower.report.accepted, 2);
+    assert_eq!(follower.rejected, 1);
+    assert_eq!(follower.report.rejections[0].line, Some(3));
+    assert_eq!(engine.accounts.get(&1).unwrap().total, 15.0);
+}
+
+#[test]
+fn test_follower_keeps_only_the_latest_rejections() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("feed.csv");
+    let mut follower = Follower::new(&path);
+    let mut engine = TransactionEngine::new();
+
+    let rejected = follow::KEPT_REJECTIONS + 5;
+    append(&path, "type,client,tx,amount\n");
+    append(&path, &"withdrawal,1,1,1.0\n".repeat(rejected));
+    follower.poll(&mut engine).unwrap();
+
+    assert_eq!(follower.rejected, rejected);
+    assert_eq!(follower.report.rejections.len(), follow::KEPT_REJECTIONS);
+    assert_eq!(follower.report.rejections.last().unwrap().line, Some(rejected as u64 + 1));
+}
+
+#[tokio::test]
+async fn test_follow_exports_accounts() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("feed.csv");
+    let export_path = dir.path().join("accounts.csv");
+    append(&path, "type,client,tx,amount\ndeposit,1,1,10.0\n");
+
+    let engine: SharedEngine = Arc::new(Mutex::new(TransactionEngine::new()));
+    let (stop, stopped) = oneshot::channel::<()>();
+    let export = Export { path: export_path.clone(), interval: Duration::from_millis(10) };
+    let following = tokio::spawn({
+        let engine = engine.clone();
+        async move {
+            let mut follower = Follower::new(&path);
+            follow::follow(&mut follower, engine, Duration::from_millis(10), Some(export), async {
+                let _ = stopped.await;
+            })
+            .await
+        }
+    });
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(fs::read_to_string(&export_path).unwrap(), "client,available,held,total,locked\n1,10.0000,0.0000,10.0000,false\n");
+
+    // Rows appended right before shutdown still make it into the final export
+    append(&dir.path().join("feed.csv"), "deposit,1,2,5.0\n");
+    stop.send(()).unwrap();
+    following.await.unwrap().unwrap();
+    assert!(fs::read_to_string(&export_path).unwrap().contains("1,15.0000,0.0000,15.0000,false"));
+    assert_eq!(engine.lock().await.accounts.get(&1).unwrap().total, 15.0);
+}
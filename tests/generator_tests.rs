@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use toy_payments_engine::engine::TransactionEngine;
+use toy_payments_engine::generator::{generate, AmountDistribution, GeneratorConfig, TransactionMix};
+use toy_payments_engine::models::{ValidationPolicy, ZeroAmountPolicy};
+use toy_payments_engine::utils::process_csv;
+
+fn generate_to_strings(config: &GeneratorConfig) -> (String, String) {
+    let mut transactions = Vec::new();
+    let mut expected = Vec::new();
+    generate(config, &mut transactions, &mut expected).unwrap();
+    (String::from_utf8(transactions).unwrap(), String::from_utf8(expected).unwrap())
+}
+
+// Parses an accounts CSV into client -> (available, held, total, locked)
+fn parse_accounts(data: &str) -> HashMap<u16, (f64, f64, f64, bool)> {
+    let mut reader = csv::Reader::from_reader(data.as_bytes());
+    reader
+        .records()
+        .map(|r| {
+            let r = r.unwrap();
+            (
+                r[0].parse().unwrap(),
+                (r[1].parse().unwrap(), r[2].parse().unwrap(), r[3].parse().unwrap(), r[4].parse().unwrap()),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_same_seed_produces_same_dataset() {
+    let config = GeneratorConfig { seed: 42, malformed_ratio: 0.05, ..GeneratorConfig::default() };
+
+    assert_eq!(generate_to_strings(&config), generate_to_strings(&config));
+}
+
+#[test]
+fn test_different_seeds_produce_different_datasets() {
+    let first = GeneratorConfig { seed: 1, ..GeneratorConfig::default() };
+    let second = GeneratorConfig { seed: 2, ..GeneratorConfig::default() };
+
+    assert_ne!(generate_to_strings(&first).0, generate_to_strings(&second).0);
+}
+
+#[test]
+fn test_summary_counts_rows() {
+    let config = GeneratorConfig {
+        seed: 7,
+        transactions: 500,
+        malformed_ratio: 0.1,
+        ..GeneratorConfig::default()
+    };
+    let summary = generate(&config, Vec::new(), Vec::new()).unwrap();
+
+    assert_eq!(summary.rows, 500);
+    assert!(summary.malformed > 0);
+    assert!(summary.deliberately_invalid > 0);
+    assert!(summary.accounts <= 10);
+}
+
+#[test]
+fn test_mix_and_amount_parsing() {
+    let mix = TransactionMix::from_str("deposit=5, withdrawal=3").unwrap();
+    assert_eq!(mix.deposit, 5);
+    assert_eq!(mix.withdrawal, 3);
+    assert_eq!(mix.chargeback, 0);
+    assert!(TransactionMix::from_str("withdrawal=3").is_err());
+    assert!(TransactionMix::from_str("refund=3").is_err());
+
+    assert_eq!(
+        AmountDistribution::from_str("uniform:1:10").unwrap(),
+        AmountDistribution::Uniform { min: 1.0, max: 10.0 }
+    );
+    assert_eq!(AmountDistribution::from_str("fixed:100").unwrap(), AmountDistribution::Fixed(100.0));
+    assert!(AmountDistribution::from_str("uniform:10:1").is_err());
+    assert!(AmountDistribution::from_str("normal:5").is_err());
+}
+
+#[tokio::test]
+async fn test_expected_output_matches_engine() {
+    for (seed, amounts) in [
+        (3, AmountDistribution::default()),
+        (11, AmountDistribution::Exponential { mean: 50.0 }),
+    ] {
+        let config = GeneratorConfig {
+            seed,
+            clients: 25,
+            transactions: 3_000,
+            amounts,
+            invalid_ratio: 0.2,
+            malformed_ratio: 0.02,
+            ..GeneratorConfig::default()
+        };
+        let (transactions, expected) = generate_to_strings(&config);
+
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(temp_file.path(), transactions).unwrap();
+
+        let mut engine = TransactionEngine::new();
+        process_csv(temp_file.path().to_str().unwrap(), &mut engine).await.unwrap();
+
+        let expected = parse_accounts(&expected);
+        assert_eq!(engine.accounts.len(), expected.len());
+
+        for (client_id, (available, held, total, locked)) in expected {
+            let account = engine.accounts.get(&client_id).unwrap();
+            assert!((account.available - available).abs() < 1e-6, "client {}", client_id);
+            assert!((account.held - held).abs() < 1e-6, "client {}", client_id);
+            assert!((account.total - total).abs() < 1e-6, "client {}", client_id);
+            assert_eq!(account.locked, locked, "client {}", client_id);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_expected_output_respects_the_maximum_amount() {
+    let config = GeneratorConfig {
+        seed: 5,
+        clients: 10,
+        transactions: 2_000,
+        max_amount: 500.0,
+        ..GeneratorConfig::default()
+    };
+    let (transactions, expected) = generate_to_strings(&config);
+
+    let temp_file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(temp_file.path(), transactions).unwrap();
+
+    let mut engine = TransactionEngine::new();
+    engine.validation = ValidationPolicy::new(500.0, 4, ZeroAmountPolicy::Reject).unwrap();
+    process_csv(temp_file.path().to_str().unwrap(), &mut engine).await.unwrap();
+
+    let expected = parse_accounts(&expected);
+    assert_eq!(engine.accounts.len(), expected.len());
+    for (client_id, (available, held, total, locked)) in expected {
+        let account = engine.accounts.get(&client_id).unwrap();
+        assert!((account.available - available).abs() < 1e-6, "client {}", client_id);
+        assert!((account.held - held).abs() < 1e-6, "client {}", client_id);
+        assert!((account.total - total).abs() < 1e-6, "client {}", client_id);
+        assert_eq!(account.locked, locked, "client {}", client_id);
+    }
+}
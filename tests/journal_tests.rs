@@ -0,0 +1,134 @@
+use indicatif::ProgressBar;
+use toy_payments_engine::engine::TransactionEngine;
+use toy_payments_engine::journal::{Event, EventKind};
+use toy_payments_engine::models::{Transaction, TransactionType};
+use toy_payments_engine::utils::{process_reader, write_journal};
+
+fn journaled_engine() -> TransactionEngine {
+    let mut engine = TransactionEngine::new();
+    engine.keep_journal();
+    engine
+}
+
+#[test]
+fn test_accepted_transactions_are_journaled() {
+    let mut engine = journaled_engine();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 1, 2, Some(30.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 1, Some(40.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Resolve, 1, 1, Some(10.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Chargeback, 1, 1, None)).unwrap();
+    // Refused rows leave no trace
+    assert!(engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 3, Some(5.0))).is_err());
+
+    let events = &engine.journal().unwrap().events;
+    assert_eq!(
+        events,
+        &vec![
+            Event::new(EventKind::Deposited, 1, None, 1, 100.0),
+            Event::new(EventKind::Withdrew, 1, None, 2, 30.0),
+            Event::new(EventKind::FundsHeld, 1, None, 1, 40.0),
+            Event::new(EventKind::FundsReleased, 1, None, 1, 10.0),
+            Event::new(EventKind::ChargedBack, 1, None, 1, 30.0),
+            Event::new(EventKind::AccountLocked, 1, None, 1, 0.0),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_rebuilt_balances_match_the_engine() {
+    let mut engine = journaled_engine();
+    engine.rates.insert("EUR", "USD", 1.0825).unwrap();
+    engine
+        .fees
+        .read_rules("type,tier,flat,percent\nwithdrawal,,0.25,0\ntransfer,,0,1\nchargeback,,5,0\n".as_bytes())
+        .unwrap();
+    engine.fees.house_account = Some(100);
+    engine.authorization_expiry = Some(3);
+
+    let input = "type,client,tx,amount,currency,to_currency,to_client,ref_tx\n\
+                 deposit,1,1,100.1234,,,,\n\
+                 deposit,1,2,50.0,EUR,,,\n\
+                 convert,1,3,20.0,EUR,USD,,\n\
+                 transfer,1,4,10.5,,,2,\n\
+                 withdrawal,2,5,3.3333,,,,\n\
+                 authorize,1,6,12.0,,,,\n\
+                 capture,1,6,7.5,,,,\n\
+                 authorize,1,7,4.0,,,,\n\
+                 refund,1,8,1.1,,,,1\n\
+                 deposit,3,9,0.0001,,,,\n\
+                 deposit,3,10,9.9999,,,,\n\
+                 dispute,3,10,,,,,\n\
+                 chargeback,3,10,,,,,\n\
+                 dispute,1,3,,,,,\n\
+                 resolve,1,3,,,,,\n";
+    let report = process_reader(input.as_bytes(), &mut engine, &ProgressBar::hidden()).await.unwrap();
+    assert_eq!(report.accepted, 15);
+
+    // The second authorization expired along the way
+    assert_eq!(engine.accounts.get(&1).unwrap().held, 0.0);
+    assert!(engine.is_locked(3));
+
+    assert_eq!(engine.journal().unwrap().verify(&engine), vec![]);
+
+    let rebuilt = engine.journal().unwrap().rebuild(engine.validation.max_decimal_places());
+    assert_eq!(rebuilt.balances().count(), engine.balances().count());
+    assert_eq!(rebuilt.account(1, Some("USD")), engine.account(1, Some("USD")));
+}
+
+#[test]
+fn test_verify_reports_balances_that_disagree() {
+    let mut engine = journaled_engine();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(10.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 2, 2, Some(10.0))).unwrap();
+
+    // Changes made behind the engine's back are not in the journal
+    engine.accounts.get_mut(&1).unwrap().available = 11.0;
+    engine.accounts.remove(&2);
+
+    let mismatches = engine.journal().unwrap().verify(&engine);
+    assert_eq!(mismatches.len(), 2);
+    assert_eq!(mismatches[0].client_id, 1);
+    assert_eq!(mismatches[0].live.as_ref().unwrap().available, 11.0);
+    assert_eq!(mismatches[0].rebuilt.as_ref().unwrap().available, 10.0);
+    assert_eq!((mismatches[1].client_id, mismatches[1].live.as_ref()), (2, None));
+}
+
+#[test]
+fn test_verify_checks_every_field_of_the_live_balances() {
+    let mut engine = journaled_engine();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(10.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 1, None)).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 2, 2, Some(10.0))).unwrap();
+    assert_eq!(engine.journal().unwrap().verify(&engine), vec![]);
+
+    // A total that no longer adds up, and a lock no event explains
+    engine.accounts.get_mut(&1).unwrap().total = 12.0;
+    engine.accounts.get_mut(&2).unwrap().locked = true;
+
+    let mismatches = engine.journal().unwrap().verify(&engine);
+    let found: Vec<_> = mismatches
+        .iter()
+        .map(|m| {
+            let (live, replayed) = (m.live.as_ref().unwrap(), m.rebuilt.as_ref().unwrap());
+            (m.client_id, live.total, replayed.total, live.locked, replayed.locked)
+        })
+        .collect();
+    assert_eq!(found, vec![(1, 12.0, 10.0, false, false), (2, 10.0, 10.0, true, false)]);
+}
+
+#[test]
+fn test_write_journal() {
+    let mut engine = journaled_engine();
+    let mut deposit = Transaction::new(TransactionType::Deposit, 1, 1, Some(2.5));
+    deposit.currency = Some("EUR".to_string());
+    engine.handle_transaction(deposit).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 2, 2, Some(1.0))).unwrap();
+
+    let mut output = Vec::new();
+    write_journal(&engine, &mut output).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "event,client,currency,tx,amount\nDeposited,1,EUR,1,2.5000\nDeposited,2,,2,1.0000\n"
+    );
+}
@@ -0,0 +1,120 @@
+use indicatif::ProgressBar;
+use toy_payments_engine::engine::TransactionEngine;
+use toy_payments_engine::journal::{Event, EventKind};
+use toy_payments_engine::ledger::{LedgerAccount, Posting, SystemBook};
+use toy_payments_engine::models::{ClientAccount, Transaction, TransactionType};
+use toy_payments_engine::utils::{process_reader, write_trial_balance};
+
+fn system(book: SystemBook) -> LedgerAccount {
+    LedgerAccount::system(book, None)
+}
+
+#[test]
+fn test_transactions_post_balanced_entries() {
+    let mut engine = TransactionEngine::new();
+    engine.fees.read_rules("type,tier,flat,percent\nwithdrawal,,1,0\n".as_bytes()).unwrap();
+    engine.fees.house_account = Some(100);
+
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 1, 2, Some(20.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 1, Some(30.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Chargeback, 1, 1, None)).unwrap();
+
+    let ledger = engine.ledger();
+    assert_eq!(ledger.balance(&LedgerAccount::available(1, None)), 49.0);
+    assert_eq!(ledger.balance(&LedgerAccount::held(1, None)), 0.0);
+    assert_eq!(ledger.balance(&LedgerAccount::available(100, None)), 1.0);
+    assert_eq!(ledger.balance(&system(SystemBook::ExternalFunding)), -80.0);
+    assert_eq!(ledger.balance(&system(SystemBook::ChargebackLoss)), 30.0);
+    // Fees pass straight through to the house account
+    assert_eq!(ledger.balance(&system(SystemBook::Fees)), 0.0);
+    assert!(ledger.unbalanced(4).is_empty());
+}
+
+#[tokio::test]
+async fn test_ledger_nets_to_zero_and_backs_every_balance() {
+    let mut engine = TransactionEngine::new();
+    engine.rates.insert("EUR", "USD", 1.0825).unwrap();
+    engine
+        .fees
+        .read_rules("type,tier,flat,percent\nwithdrawal,,0.25,0\ntransfer,,0,1\nconvert,,0.5,0\n".as_bytes())
+        .unwrap();
+    engine.fees.house_account = Some(100);
+
+    let input = "type,client,tx,amount,currency,to_currency,to_client,ref_tx\n\
+                 deposit,1,1,100.1234,,,,\n\
+                 deposit,1,2,50.0,EUR,,,\n\
+                 convert,1,3,20.0,EUR,USD,,\n\
+                 transfer,1,4,10.5,,,2,\n\
+                 withdrawal,2,5,3.3333,,,,\n\
+                 authorize,1,6,12.0,,,,\n\
+                 capture,1,6,7.5,,,,\n\
+                 refund,1,7,1.1,,,,1\n\
+                 dispute,1,3,,,,,\n\
+                 chargeback,1,3,,,,,\n";
+    let report = process_reader(input.as_bytes(), &mut engine, &ProgressBar::hidden()).await.unwrap();
+    assert_eq!(report.accepted, 10);
+
+    let ledger = engine.ledger();
+    assert!(ledger.unbalanced(4).is_empty(), "{:?}", ledger.net(4));
+    assert_eq!(ledger.net(4).len(), 3);
+
+    for (currency, account) in engine.balances() {
+        let client_id = account.client_id;
+        assert_eq!(ledger.balance(&LedgerAccount::available(client_id, currency)), account.available);
+        assert_eq!(ledger.balance(&LedgerAccount::held(client_id, currency)), account.held);
+    }
+}
+
+#[test]
+fn test_postings_describe_client_events() {
+    let posting = Posting::new(LedgerAccount::available(2, None), LedgerAccount::available(1, None), 5.0);
+    assert_eq!(
+        posting.events(7),
+        vec![
+            Event::new(EventKind::Withdrew, 1, None, 7, 5.0),
+            Event::new(EventKind::Deposited, 2, None, 7, 5.0),
+        ]
+    );
+
+    let posting = Posting::new(system(SystemBook::ChargebackLoss), LedgerAccount::held(1, Some("EUR")), 5.0);
+    assert_eq!(posting.events(7), vec![Event::new(EventKind::ChargedBack, 1, Some("EUR"), 7, 5.0)]);
+
+    // Postings between the engine's own accounts leave clients alone
+    let posting = Posting::new(system(SystemBook::Fees), system(SystemBook::ExternalFunding), 5.0);
+    assert!(posting.events(7).is_empty());
+}
+
+#[test]
+fn test_restored_balances_are_posted_against_opening_balances() {
+    let mut engine = TransactionEngine::new();
+    engine.restore_account(
+        None,
+        ClientAccount { client_id: 1, available: 40.0, held: 10.0, total: 50.0, locked: false },
+    );
+    engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 1, 1, Some(15.0))).unwrap();
+
+    let ledger = engine.ledger();
+    assert_eq!(ledger.balance(&system(SystemBook::OpeningBalances)), -50.0);
+    assert_eq!(ledger.balance(&LedgerAccount::available(1, None)), 25.0);
+    assert_eq!(engine.accounts.get(&1).unwrap().available, 25.0);
+    assert!(ledger.unbalanced(4).is_empty());
+}
+
+#[test]
+fn test_write_trial_balance() {
+    let mut engine = TransactionEngine::new();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(10.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Dispute, 1, 1, Some(4.0))).unwrap();
+
+    let mut output = Vec::new();
+    write_trial_balance(&engine, &mut output).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "account,client,currency,balance\n\
+         available,1,,6.0000\n\
+         held,1,,4.0000\n\
+         external_funding,,,-10.0000\n\
+         net,,,0.0000\n"
+    );
+}
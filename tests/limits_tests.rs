@@ -0,0 +1,44 @@
+use toy_payments_engine::limits::{BreachAction, LimitPolicy, Limits, LimitsError};
+
+#[test]
+fn test_client_limits_override_the_defaults() {
+    let mut policy = LimitPolicy::new();
+    assert!(!policy.is_active());
+
+    policy
+        .read("client,max_withdrawal,max_withdrawals,max_deposit_volume\n,500,10,\n1,1000,,5000\n".as_bytes())
+        .unwrap();
+
+    assert!(policy.is_active());
+    assert_eq!(
+        policy.limits(1),
+        Limits { max_withdrawal: Some(1000.0), max_withdrawals: Some(10), max_deposit_volume: Some(5000.0) }
+    );
+    assert_eq!(
+        policy.limits(2),
+        Limits { max_withdrawal: Some(500.0), max_withdrawals: Some(10), max_deposit_volume: None }
+    );
+}
+
+#[test]
+fn test_limits_reject_bad_rows() {
+    let mut policy = LimitPolicy::new();
+    let result = policy.read("client,max_withdrawal,max_withdrawals,max_deposit_volume\n3,-5,,\n".as_bytes());
+    assert!(matches!(result, Err(LimitsError::InvalidLimit { client: Some(3) })));
+
+    let result = policy.read("client,max_withdrawal,max_withdrawals,max_deposit_volume\n,,,0\n".as_bytes());
+    assert!(matches!(result, Err(LimitsError::InvalidLimit { client: None })));
+
+    let result = policy.read("client,max_withdrawal,max_withdrawals,max_deposit_volume\n4,,0,\n".as_bytes());
+    assert!(matches!(result, Err(LimitsError::InvalidLimit { client: Some(4) })));
+
+    let result = policy.read("client,max_withdrawal,max_withdrawals,max_deposit_volume\n1,ten,,\n".as_bytes());
+    assert!(matches!(result, Err(LimitsError::Csv(_))));
+}
+
+#[test]
+fn test_breach_action_from_str() {
+    assert_eq!("reject".parse::<BreachAction>(), Ok(BreachAction::Reject));
+    assert_eq!("LOCK".parse::<BreachAction>(), Ok(BreachAction::Lock));
+    assert!("freeze".parse::<BreachAction>().is_err());
+}
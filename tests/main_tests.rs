@@ -80,4 +80,58 @@ async fn test_export_accounts_to_stdout() {
     });
 
     assert!(output.is_ok());
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_max_decimals_out_of_range_is_a_usage_error() {
+    let input = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(input.path(), "type,client,tx,amount\ndeposit,1,1,2\ndeposit,1,2,1.5\n").unwrap();
+
+    for places in ["5", "400"] {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_toy_payments_engine"))
+            .args([input.path().to_str().unwrap(), "--max-decimals", places])
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        assert!(output.stdout.is_empty());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("Usage:"), "{}", stderr);
+        assert!(stderr.contains("Invalid maximum decimal places"), "{}", stderr);
+    }
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_toy_payments_engine"))
+        .args([input.path().to_str().unwrap(), "--max-decimals", "0"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "client,available,held,total,locked\n1,2.0000,0.0000,2.0000,false\n"
+    );
+}
+
+#[test]
+fn test_house_account_zero_is_a_usage_error() {
+    let input = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(input.path(), "type,client,tx,amount\ndeposit,1,1,2\n").unwrap();
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_toy_payments_engine"))
+        .args([input.path().to_str().unwrap(), "--house-account", "0"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Usage:"), "{}", stderr);
+    assert!(stderr.contains("Invalid house account: 0"), "{}", stderr);
+}
+
+#[test]
+fn test_database_options_are_only_listed_with_sqlite() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_toy_payments_engine")).output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Usage:"), "{}", stderr);
+    assert_eq!(stderr.contains("--database <sqlite_file>"), cfg!(feature = "sqlite"), "{}", stderr);
+    assert_eq!(stderr.contains("--commit-every <rows>"), cfg!(feature = "sqlite"), "{}", stderr);
+}
@@ -1,6 +1,5 @@
-use toy_payments_engine::models::{TransactionType, Transaction, ClientAccount};
+use toy_payments_engine::models::{TransactionType, Transaction, ClientAccount, ValidationError, ValidationPolicy, PolicyError, ZeroAmountPolicy, MAX_DECIMAL_PLACES};
 use std::str::FromStr;
-use serde_json;
 
 // Utility function for floating-point comparison with precision
 fn assert_float_eq(a: f64, b: f64, precision: usize) {
@@ -100,4 +99,217 @@ fn test_client_account_precision() {
     assert_float_eq(account.available, 1.1235, 4);
     assert_float_eq(account.held, 0.9877, 4);
     assert_float_eq(account.total, 2.1111, 4);
-}
\ No newline at end of file
+}
+
+fn deserialize_csv_row(row: &str) -> Transaction {
+    let data = format!("type,client,tx,amount\n{}\n", row);
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data.as_bytes());
+    reader.deserialize().next().unwrap().unwrap()
+}
+
+#[test]
+fn test_validate_rejects_non_finite_amounts() {
+    let policy = ValidationPolicy::default();
+
+    for row in ["deposit,1,1,NaN", "deposit,1,1,inf", "withdrawal,1,1,  -inf  "] {
+        let transaction = deserialize_csv_row(row);
+        assert_eq!(transaction.validate(&policy), Err(ValidationError::NonFiniteAmount), "{}", row);
+    }
+}
+
+#[test]
+fn test_validate_rejects_out_of_range_amounts() {
+    let policy = ValidationPolicy::default();
+
+    let huge = deserialize_csv_row("deposit,1,1,1e308");
+    assert!(matches!(huge.validate(&policy), Err(ValidationError::AmountTooLarge { .. })));
+
+    let too_precise = deserialize_csv_row("deposit,1,1,1.12345");
+    assert!(matches!(too_precise.validate(&policy), Err(ValidationError::ExcessPrecision { .. })));
+
+    let at_limit = deserialize_csv_row("deposit,1,1,100000000000.0000");
+    assert_eq!(at_limit.validate(&policy), Ok(()));
+
+    let four_decimals = deserialize_csv_row("withdrawal,1,1,0.0001");
+    assert_eq!(four_decimals.validate(&policy), Ok(()));
+}
+
+#[test]
+fn test_validate_with_custom_policy() {
+    let policy = ValidationPolicy::new(500.0, 2, ZeroAmountPolicy::Reject).unwrap();
+
+    assert_eq!(deserialize_csv_row("deposit,1,1,499.99").validate(&policy), Ok(()));
+    assert!(matches!(
+        deserialize_csv_row("deposit,1,1,500.01").validate(&policy),
+        Err(ValidationError::AmountTooLarge { .. })
+    ));
+    assert_eq!(
+        deserialize_csv_row("deposit,1,1,1.005").validate(&policy),
+        Err(ValidationError::ExcessPrecision { amount: 1.005, max_decimal_places: 2 })
+    );
+}
+
+#[test]
+fn test_validation_policy_rejects_invalid_limits() {
+    assert_eq!(
+        ValidationPolicy::new(100.0, 5, ZeroAmountPolicy::Reject),
+        Err(PolicyError::InvalidMaxDecimalPlaces(5))
+    );
+    assert_eq!(
+        ValidationPolicy::new(0.0, 2, ZeroAmountPolicy::Reject),
+        Err(PolicyError::InvalidMaxAmount(0.0))
+    );
+    assert!(ValidationPolicy::new(f64::INFINITY, 2, ZeroAmountPolicy::Reject).is_err());
+    assert!(ValidationPolicy::new(f64::NAN, 2, ZeroAmountPolicy::Reject).is_err());
+
+    let policy = ValidationPolicy::new(100.0, MAX_DECIMAL_PLACES, ZeroAmountPolicy::Allow).unwrap();
+    assert_eq!((policy.max_amount(), policy.max_decimal_places()), (100.0, 4));
+}
+
+#[test]
+fn test_transaction_currency_column() {
+    let data = "type,client,tx,amount,currency\ndeposit,1,1,1.0,EUR\ndeposit,1,2,1.0,\n";
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data.as_bytes());
+    let transactions: Vec<Transaction> = reader.deserialize().map(|t| t.unwrap()).collect();
+
+    assert_eq!(transactions[0].currency.as_deref(), Some("EUR"));
+    assert_eq!(transactions[1].currency, None);
+
+    // Files without the column still deserialize
+    assert_eq!(deserialize_csv_row("deposit,1,1,1.0").currency, None);
+}
+
+#[test]
+fn test_validate_rejects_invalid_currency_codes() {
+    let policy = ValidationPolicy::default();
+
+    for code in ["eur", "EURO", "E1R", ""] {
+        let mut transaction = Transaction::new(TransactionType::Deposit, 1, 1, Some(1.0));
+        transaction.currency = Some(code.to_string());
+        assert_eq!(
+            transaction.validate(&policy),
+            Err(ValidationError::InvalidCurrency(code.to_string())),
+            "{}",
+            code
+        );
+    }
+}
+
+#[test]
+fn test_validate_convert_rows() {
+    let policy = ValidationPolicy::default();
+    let data = "type,client,tx,amount,currency,to_currency
+convert,1,1,10.0,EUR,USD
+convert,1,2,10.0,,USD
+convert,1,3,10.0,EUR,
+convert,1,4,10.0,EUR,EUR
+deposit,1,5,10.0,EUR,USD
+convert,1,6,,EUR,USD
+";
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data.as_bytes());
+    let results: Vec<_> = reader
+        .deserialize::<Transaction>()
+        .map(|t| t.unwrap().validate(&policy))
+        .collect();
+
+    assert_eq!(
+        results,
+        vec![
+            Ok(()),
+            Err(ValidationError::MissingCurrency(TransactionType::Convert)),
+            Err(ValidationError::MissingTargetCurrency),
+            Err(ValidationError::SameCurrencyConversion("EUR".to_string())),
+            Err(ValidationError::UnexpectedTargetCurrency(TransactionType::Deposit)),
+            Err(ValidationError::MissingAmount(TransactionType::Convert)),
+        ]
+    );
+}
+
+#[test]
+fn test_validate_transfer_rows() {
+    let policy = ValidationPolicy::default();
+    let data = "type,client,tx,amount,to_client
+transfer,1,1,10.0,2
+transfer,1,2,10.0,
+transfer,1,3,10.0,0
+transfer,1,4,10.0,1
+withdrawal,1,5,10.0,2
+transfer,1,6,-1.0,2
+";
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data.as_bytes());
+    let results: Vec<_> = reader
+        .deserialize::<Transaction>()
+        .map(|t| t.unwrap().validate(&policy))
+        .collect();
+
+    assert_eq!(
+        results,
+        vec![
+            Ok(()),
+            Err(ValidationError::MissingDestinationClient),
+            Err(ValidationError::InvalidDestinationClient),
+            Err(ValidationError::SelfTransfer),
+            Err(ValidationError::UnexpectedDestinationClient(TransactionType::Withdrawal)),
+            Err(ValidationError::AmountNotPositive(TransactionType::Transfer)),
+        ]
+    );
+}
+
+#[test]
+fn test_validate_refund_rows() {
+    let policy = ValidationPolicy::default();
+    let data = "type,client,tx,amount,ref_tx
+refund,1,2,10.0,1
+refund,1,3,10.0,
+refund,1,4,10.0,0
+refund,1,5,,1
+deposit,1,6,10.0,1
+";
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data.as_bytes());
+    let results: Vec<_> = reader
+        .deserialize::<Transaction>()
+        .map(|t| t.unwrap().validate(&policy))
+        .collect();
+
+    assert_eq!(
+        results,
+        vec![
+            Ok(()),
+            Err(ValidationError::MissingReferencedTransaction),
+            Err(ValidationError::InvalidReferencedTransaction),
+            Err(ValidationError::MissingAmount(TransactionType::Refund)),
+            Err(ValidationError::UnexpectedReferencedTransaction(TransactionType::Deposit)),
+        ]
+    );
+}
+
+#[test]
+fn test_transaction_timestamp_column() {
+    let data = "type,client,tx,amount,timestamp
+deposit,1,1,1.0,2024-03-01T12:00:00Z
+deposit,1,2,1.0,2024-03-01T14:00:00+02:00
+deposit,1,3,1.0,
+deposit,1,4,1.0,yesterday
+";
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data.as_bytes());
+    let rows: Vec<_> = reader.deserialize::<Transaction>().collect();
+
+    // Offsets are normalised to UTC
+    let first = rows[0].as_ref().unwrap().timestamp.unwrap();
+    assert_eq!(first.to_rfc3339(), "2024-03-01T12:00:00+00:00");
+    assert_eq!(rows[1].as_ref().unwrap().timestamp, Some(first));
+    assert_eq!(rows[2].as_ref().unwrap().timestamp, None);
+    assert!(rows[3].is_err());
+}
@@ -0,0 +1,40 @@
+use toy_payments_engine::rates::{convert_amount, RateError, RateTable};
+
+#[test]
+fn test_rate_table_from_csv() {
+    let data = "from, to, rate\nEUR, USD, 1.0825\nUSD, EUR, 0.9238\n";
+    let table = RateTable::from_reader(data.as_bytes()).unwrap();
+
+    assert_eq!(table.rate("EUR", "USD"), Some(1.0825));
+    assert_eq!(table.rate("USD", "EUR"), Some(0.9238));
+    // Inverse and cross rates are not derived
+    assert_eq!(table.rate("EUR", "GBP"), None);
+}
+
+#[test]
+fn test_rate_table_rejects_bad_rows() {
+    let cases = [
+        "from,to,rate\nEUR,USD,0\n",
+        "from,to,rate\nEUR,USD,-1.5\n",
+        "from,to,rate\neur,USD,1.1\n",
+        "from,to,rate\nEUR,USD,1.1\nEUR,USD,1.2\n",
+        "from,to,rate\nEUR,USD,abc\n",
+    ];
+
+    for data in cases {
+        assert!(RateTable::from_reader(data.as_bytes()).is_err(), "{}", data);
+    }
+
+    let duplicate = RateTable::from_reader(cases[3].as_bytes()).unwrap_err();
+    assert!(matches!(duplicate, RateError::DuplicateRate { .. }));
+}
+
+#[test]
+fn test_convert_amount_rounds_to_four_decimals() {
+    assert_eq!(convert_amount(100.0, 1.0825), 108.25);
+    assert_eq!(convert_amount(1.0, 0.33333), 0.3333);
+    assert_eq!(convert_amount(1.0, 0.66666), 0.6667);
+    // Half a unit of the last place rounds away from zero
+    assert_eq!(convert_amount(1.0, 0.00005), 0.0001);
+    assert_eq!(convert_amount(0.0001, 0.4), 0.0);
+}
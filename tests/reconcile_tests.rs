@@ -0,0 +1,98 @@
+use toy_payments_engine::engine::TransactionEngine;
+use toy_payments_engine::models::{Transaction, TransactionType};
+use toy_payments_engine::reconcile::{read_expected, reconcile, write_discrepancies, DiscrepancyKind};
+use toy_payments_engine::utils::write_accounts;
+
+fn engine() -> TransactionEngine {
+    let mut engine = TransactionEngine::new();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(50.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 2, 2, Some(3.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Dispute, 2, 2, None)).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Chargeback, 2, 2, None)).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 3, 3, Some(1.0))).unwrap();
+    engine
+}
+
+#[test]
+fn test_own_output_reconciles() {
+    let engine = engine();
+    let mut output = Vec::new();
+    write_accounts(&engine, &mut output).unwrap();
+
+    let expected = read_expected(output.as_slice()).unwrap();
+    assert_eq!(expected.len(), 3);
+    assert!(reconcile(&engine, &expected, 0.0).is_empty());
+}
+
+#[test]
+fn test_house_account_is_not_reconciled() {
+    let mut engine = TransactionEngine::new();
+    engine.fees.read_rules("type,tier,flat,percent\nwithdrawal,,1,0\n".as_bytes()).unwrap();
+    engine.fees.house_account = Some(100);
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(50.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 1, 2, Some(10.0))).unwrap();
+
+    // The partner's view has the client's side of the fee, not the house account
+    let expected = read_expected("client,available,held,total,locked\n1,39.0000,0.0000,39.0000,false\n".as_bytes()).unwrap();
+    assert!(reconcile(&engine, &expected, 0.0).is_empty());
+
+    // Nor is a house account row expected by mistake
+    let expected = read_expected(
+        "client,available,held,total,locked\n1,39.0000,0.0000,39.0000,false\n100,0.0000,0.0000,0.0000,false\n".as_bytes(),
+    )
+    .unwrap();
+    assert!(reconcile(&engine, &expected, 0.0).is_empty());
+}
+
+#[test]
+fn test_reconcile_reports_every_discrepancy() {
+    let expected = "client,available,held,total,locked\n\
+                    1,49.9990,0.0000,50.0000,false\n\
+                    2,0.0000,0.0000,0.0000,false\n\
+                    4,1.0000,0.0000,1.0000,false\n";
+    let expected = read_expected(expected.as_bytes()).unwrap();
+    let discrepancies = reconcile(&engine(), &expected, 0.0);
+
+    let found: Vec<_> = discrepancies
+        .iter()
+        .map(|d| (d.kind, d.client_id, d.field, d.expected.as_str(), d.actual.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            (DiscrepancyKind::Balance, 1, "available", "49.9990", "50.0000"),
+            (DiscrepancyKind::Lock, 2, "locked", "false", "true"),
+            (DiscrepancyKind::Extra, 3, "", "", ""),
+            (DiscrepancyKind::Missing, 4, "", "", ""),
+        ]
+    );
+
+    // Within the tolerance, only the other discrepancies are left
+    assert_eq!(reconcile(&engine(), &expected, 0.001).len(), 3);
+
+    let mut output = Vec::new();
+    write_discrepancies(&discrepancies[..1], &mut output).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "kind,client,currency,field,expected,actual\nbalance,1,,available,49.9990,50.0000\n"
+    );
+}
+
+#[test]
+fn test_reconcile_by_currency() {
+    let mut engine = TransactionEngine::new();
+    let mut deposit = Transaction::new(TransactionType::Deposit, 1, 1, Some(2.0));
+    deposit.currency = Some("EUR".to_string());
+    engine.handle_transaction(deposit).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 2, Some(1.0))).unwrap();
+
+    let expected = "client,currency,available,held,total,locked\n\
+                    1,,1.0000,0.0000,1.0000,false\n\
+                    1,USD,2.0000,0.0000,2.0000,false\n";
+    let expected = read_expected(expected.as_bytes()).unwrap();
+    let found: Vec<_> = reconcile(&engine, &expected, 0.0).into_iter().map(|d| (d.kind, d.currency)).collect();
+    assert_eq!(
+        found,
+        vec![(DiscrepancyKind::Extra, "EUR".to_string()), (DiscrepancyKind::Missing, "USD".to_string())]
+    );
+}
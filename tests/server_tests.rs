@@ -0,0 +1,160 @@
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+use toy_payments_engine::engine::TransactionEngine;
+use toy_payments_engine::models::{Transaction, TransactionType};
+use toy_payments_engine::server::{self, SharedEngine};
+
+// Starts the API on a free localhost port
+async fn start() -> (String, SharedEngine) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let engine: SharedEngine = Arc::new(Mutex::new(TransactionEngine::new()));
+    tokio::spawn(server::serve(listener, engine.clone(), std::future::pending()));
+    (address, engine)
+}
+
+// Sends one HTTP/1.1 request and returns the status code and body
+async fn request(address: &str, method: &str, path: &str, content_type: &str, body: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let request = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        path,
+        address,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let status = response[9..12].parse().unwrap();
+    let body = response.split_once("\r\n\r\n").unwrap().1.to_string();
+    (status, body)
+}
+
+#[tokio::test]
+async fn test_post_json_and_get_accounts() {
+    let (address, engine) = start().await;
+
+    let (status, body) = request(
+        &address,
+        "POST",
+        "/transactions",
+        "application/json",
+        r#"{"type":"deposit","client":1,"tx":1,"amount":10.5}"#,
+    )
+    .await;
+    assert_eq!((status, body.as_str()), (200, r#"{"accepted":1,"rejections":[]}"#));
+
+    // A batch reports every row that was not accepted
+    let (status, body) = request(
+        &address,
+        "POST",
+        "/transactions",
+        "application/json",
+        r#"[{"type":"withdrawal","client":1,"tx":2,"amount":20.0},{"type":"deposit","client":2,"tx":3,"amount":0}]"#,
+    )
+    .await;
+    assert_eq!(status, 422);
+    let report: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(report["accepted"], 0);
+    assert_eq!(report["rejections"][0]["kind"], "rejected");
+    assert_eq!(report["rejections"][0]["tx"], 2);
+    assert_eq!(report["rejections"][1]["kind"], "invalid");
+
+    let (status, body) = request(&address, "GET", "/accounts/1", "text/plain", "").await;
+    assert_eq!(status, 200);
+    assert_eq!(
+        body,
+        r#"[{"client":1,"available":"10.5000","held":"0.0000","total":"10.5000","locked":false,"currency":null}]"#
+    );
+    assert_eq!(engine.lock().await.accounts.get(&1).unwrap().total, 10.5);
+}
+
+#[tokio::test]
+async fn test_post_csv_batch() {
+    let (address, _engine) = start().await;
+
+    let csv = "type,client,tx,amount,currency\ndeposit,1,1,5.0,EUR\ndeposit,2,2,7.0,\n";
+    let (status, _) = request(&address, "POST", "/transactions", "text/csv", csv).await;
+    assert_eq!(status, 200);
+
+    let (status, body) = request(&address, "GET", "/accounts", "text/plain", "").await;
+    assert_eq!(status, 200);
+    let accounts: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+    assert_eq!(accounts.len(), 2);
+    assert!(accounts.iter().any(|a| a["client"] == 1 && a["currency"] == "EUR"));
+}
+
+#[tokio::test]
+async fn test_typed_error_responses() {
+    let (address, _engine) = start().await;
+
+    let (status, body) = request(&address, "GET", "/accounts/7", "text/plain", "").await;
+    assert_eq!(status, 404);
+    let error: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(error["error"], "account_not_found");
+
+    let (status, body) = request(&address, "POST", "/transactions", "application/json", "{not json").await;
+    assert_eq!(status, 400);
+    assert!(body.contains(r#""error":"malformed""#));
+
+    let (status, body) = request(&address, "POST", "/transactions", "text/plain", "deposit").await;
+    assert_eq!(status, 415);
+    assert!(body.contains(r#""error":"unsupported_media_type""#));
+}
+
+#[tokio::test]
+async fn test_unreadable_csv_rows_are_reported() {
+    let (address, engine) = start().await;
+
+    let csv = "type,client,tx,amount\ndeposit,1,1,5.0\ndeposit,1,2,five\ndeposit,1,3,2.0\n";
+    let (status, body) = request(&address, "POST", "/transactions", "text/csv", csv).await;
+    assert_eq!(status, 422);
+    let report: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(report["accepted"], 2);
+    assert_eq!(report["rejections"][0]["kind"], "malformed");
+    assert_eq!(report["rejections"][0]["line"], 3);
+
+    // The report matches what was applied
+    assert_eq!(engine.lock().await.accounts.get(&1).unwrap().total, 7.0);
+}
+
+#[tokio::test]
+async fn test_unreadable_json_batches_apply_nothing() {
+    let (address, engine) = start().await;
+
+    let json = r#"[{"type":"deposit","client":1,"tx":1,"amount":5.0},{"type":"deposit","client":1,"tx":2,"amount":"five"}]"#;
+    let (status, body) = request(&address, "POST", "/transactions", "application/json", json).await;
+    assert_eq!(status, 400);
+    assert!(body.contains(r#""error":"malformed""#));
+    assert!(engine.lock().await.accounts.is_empty());
+}
+
+#[tokio::test]
+async fn test_read_only_api_takes_no_transactions() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let engine: SharedEngine = Arc::new(Mutex::new(TransactionEngine::new()));
+    engine.lock().await.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(2.0))).unwrap();
+    tokio::spawn(server::serve_read_only(listener, engine.clone(), std::future::pending()));
+
+    let (status, _) = request(
+        &address,
+        "POST",
+        "/transactions",
+        "application/json",
+        r#"{"type":"deposit","client":1,"tx":2,"amount":10.0}"#,
+    )
+    .await;
+    assert_eq!(status, 404);
+    assert_eq!(engine.lock().await.accounts.get(&1).unwrap().total, 2.0);
+
+    let (status, body) = request(&address, "GET", "/accounts/1", "text/plain", "").await;
+    assert_eq!(status, 200);
+    assert!(body.contains(r#""total":"2.0000""#));
+}
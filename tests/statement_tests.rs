@@ -0,0 +1,174 @@
+use indicatif::ProgressBar;
+use toy_payments_engine::engine::TransactionEngine;
+use toy_payments_engine::models::TransactionType;
+use toy_payments_engine::statement::{Statement, StatementEntry, StatementRange};
+use toy_payments_engine::utils::{process_reader_rows, ProcessingReport};
+
+async fn statement_of(input: &str, client_id: u16, range: StatementRange) -> (Statement, ProcessingReport) {
+    let mut engine = TransactionEngine::new();
+    let mut statement = Statement::new(client_id, range);
+    let report = process_reader_rows(input.as_bytes(), &ProgressBar::hidden(), |report, transaction, line| {
+        let (client_id, tx_id) = (transaction.client_id, transaction.tx_id);
+        let result = statement.handle(&mut engine, transaction, line);
+        report.record(line, client_id, tx_id, &result);
+        Ok(())
+    })
+    .await
+    .unwrap();
+    (statement, report)
+}
+
+#[tokio::test]
+async fn test_statement_lists_every_row_of_the_client() {
+    let input = "type,client,tx,amount,currency,to_currency,to_client,ref_tx\n\
+                 deposit,42,1,100.0,,,,\n\
+                 deposit,7,2,50.0,,,,\n\
+                 withdrawal,42,3,500.0,,,,\n\
+                 transfer,7,4,5.0,,,42,\n\
+                 dispute,42,1,30.0,,,,\n\
+                 chargeback,42,1,,,,,\n\
+                 deposit,42,5,1.0,,,,\n";
+    let (statement, report) = statement_of(input, 42, StatementRange::default()).await;
+    assert_eq!(report.accepted, 5);
+
+    let rows: Vec<_> = statement
+        .lines
+        .iter()
+        .map(|line| (line.tx_id, line.status, line.available, line.held, line.total, line.locked))
+        .collect();
+    assert_eq!(
+        rows,
+        vec![
+            (1, "accepted", 100.0, 0.0, 100.0, false),
+            (3, "rejected", 100.0, 0.0, 100.0, false),
+            (4, "accepted", 105.0, 0.0, 105.0, false),
+            (1, "accepted", 75.0, 30.0, 105.0, false),
+            (1, "accepted", 75.0, 0.0, 75.0, true),
+            (5, "rejected", 75.0, 0.0, 75.0, true),
+        ]
+    );
+    assert_eq!((statement.lines[2].client_id, statement.lines[5].reason.as_str()), (7, "Account is locked"));
+    assert_eq!(statement.lock_reason.as_deref(), Some("Locked by the chargeback of transaction 1"));
+}
+
+#[tokio::test]
+async fn test_statement_range_still_explains_the_lock() {
+    let input = "type,client,tx,amount,timestamp\n\
+                 deposit,1,1,10.0,2024-01-01T00:00:00Z\n\
+                 dispute,1,1,,2024-01-02T00:00:00Z\n\
+                 chargeback,1,1,,2024-01-03T00:00:00Z\n\
+                 deposit,1,2,5.0,2024-01-04T00:00:00Z\n\
+                 deposit,1,3,5.0,\n";
+    let range = StatementRange {
+        to_tx: Some(2),
+        from_time: Some("2024-01-04T00:00:00Z".parse().unwrap()),
+        ..Default::default()
+    };
+    let (statement, _) = statement_of(input, 1, range).await;
+
+    let txs: Vec<_> = statement.lines.iter().map(|line| (line.tx_id, line.status)).collect();
+    assert_eq!(txs, vec![(2, "rejected")]);
+    assert_eq!(statement.lock_reason.as_deref(), Some("Locked by the chargeback of transaction 1"));
+}
+
+#[tokio::test]
+async fn test_statement_lists_fees_and_expiries_under_their_own_transactions() {
+    let input = "type,client,tx,amount\n\
+                 deposit,1,1,100.0\n\
+                 authorize,1,2,30.0\n\
+                 deposit,2,3,5.0\n\
+                 deposit,2,4,5.0\n\
+                 withdrawal,1,5,10.0\n";
+    let mut engine = TransactionEngine::new();
+    engine.fees.read_rules("type,tier,flat,percent\nwithdrawal,,0.5,0\n".as_bytes()).unwrap();
+    engine.fees.house_account = Some(100);
+    engine.authorization_expiry = Some(1);
+
+    let mut statement = Statement::new(1, StatementRange::default());
+    let report = process_reader_rows(input.as_bytes(), &ProgressBar::hidden(), |report, transaction, line| {
+        let (client_id, tx_id) = (transaction.client_id, transaction.tx_id);
+        let result = statement.handle(&mut engine, transaction, line);
+        report.record(line, client_id, tx_id, &result);
+        Ok(())
+    })
+    .await
+    .unwrap();
+    assert_eq!(report.accepted, 5);
+
+    // The authorization expires while client 2 deposits, and is listed as itself
+    let rows: Vec<_> = statement
+        .lines
+        .iter()
+        .map(|line| (line.line, line.tx_id, line.entry, line.amount, line.available, line.held))
+        .collect();
+    assert_eq!(
+        rows,
+        vec![
+            (Some(2), 1, StatementEntry::Row, Some(100.0), 100.0, 0.0),
+            (Some(3), 2, StatementEntry::Row, Some(30.0), 70.0, 30.0),
+            (Some(5), 2, StatementEntry::Expiry, Some(30.0), 100.0, 0.0),
+            (Some(6), 5, StatementEntry::Row, Some(10.0), 90.0, 0.0),
+            (Some(6), 5, StatementEntry::Fee, Some(0.5), 89.5, 0.0),
+        ]
+    );
+    assert_eq!(statement.lines[2].tx_type, TransactionType::Authorize);
+    assert_eq!(statement.lines[4].tx_type, TransactionType::Withdrawal);
+}
+
+#[tokio::test]
+async fn test_house_statement_lists_the_fees_it_receives() {
+    let input = "type,client,tx,amount\n\
+                 deposit,1,1,100.0\n\
+                 withdrawal,1,2,10.0\n";
+    let mut engine = TransactionEngine::new();
+    engine.fees.read_rules("type,tier,flat,percent\nwithdrawal,,0.5,0\n".as_bytes()).unwrap();
+    engine.fees.house_account = Some(100);
+
+    let mut statement = Statement::new(100, StatementRange::default());
+    process_reader_rows(input.as_bytes(), &ProgressBar::hidden(), |report, transaction, line| {
+        let (client_id, tx_id) = (transaction.client_id, transaction.tx_id);
+        let result = statement.handle(&mut engine, transaction, line);
+        report.record(line, client_id, tx_id, &result);
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    let rows: Vec<_> = statement.lines.iter().map(|line| (line.tx_id, line.client_id, line.entry, line.total)).collect();
+    assert_eq!(rows, vec![(2, 1, StatementEntry::Fee, 0.5)]);
+}
+
+#[tokio::test]
+async fn test_write_statement() {
+    let input = "type,client,tx,amount,currency\n\
+                 deposit,1,1,2.5,EUR\n\
+                 withdrawal,1,2,,EUR\n";
+    let (statement, _) = statement_of(input, 1, StatementRange::default()).await;
+
+    let mut csv = Vec::new();
+    statement.write_csv(&mut csv).unwrap();
+    assert_eq!(
+        String::from_utf8(csv).unwrap(),
+        "line,tx,type,entry,client,currency,amount,timestamp,status,reason,available,held,total,locked\n\
+         2,1,deposit,row,1,EUR,2.5000,,accepted,,2.5000,0.0000,2.5000,false\n\
+         3,2,withdrawal,row,1,EUR,,,invalid,Withdrawal transaction requires an amount,2.5000,0.0000,2.5000,false\n"
+    );
+
+    let mut text = Vec::new();
+    statement.write_text(&mut text).unwrap();
+    assert_eq!(
+        String::from_utf8(text).unwrap(),
+        "Statement for client 1\n\
+         line  tx  type        entry  currency  amount  status    available  held    total   locked  reason\n\
+         2     1   deposit     row    EUR       2.5000  accepted  2.5000     0.0000  2.5000  false\n\
+         3     2   withdrawal  row    EUR               invalid   2.5000     0.0000  2.5000  false   Withdrawal transaction requires an amount\n\
+         Not locked\n"
+    );
+
+    let mut json = Vec::new();
+    statement.write_json(&mut json).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    assert_eq!(json["client"], 1);
+    assert_eq!(json["lines"][0]["available"], "2.5000");
+    assert!(json["lock_reason"].is_null());
+}
@@ -0,0 +1,90 @@
+#![cfg(feature = "sqlite")]
+
+use std::fs;
+use toy_payments_engine::engine::TransactionEngine;
+use toy_payments_engine::models::{Transaction, TransactionType};
+use toy_payments_engine::store::SqliteStore;
+use toy_payments_engine::utils::process_csv_with;
+
+async fn run(database: &std::path::Path, input: &str, batch_size: u64) -> TransactionEngine {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("input.csv");
+    fs::write(&path, input).unwrap();
+
+    let mut store = SqliteStore::open(database).unwrap();
+    store.batch_size = batch_size;
+    let mut engine = TransactionEngine::new();
+    store.load(&mut engine).unwrap();
+    process_csv_with(path.to_str().unwrap(), &mut engine, |engine| Ok(store.row_handled(engine)?))
+        .await
+        .unwrap();
+    store.commit(&mut engine).unwrap();
+    engine
+}
+
+#[tokio::test]
+async fn test_later_runs_continue_from_the_database() {
+    let dir = tempfile::tempdir().unwrap();
+    let database = dir.path().join("engine.db");
+
+    run(
+        &database,
+        "type,client,tx,amount,currency\n\
+         deposit,1,1,100.0,\n\
+         deposit,1,2,50.0,EUR\n\
+         deposit,2,3,20.0,\n\
+         dispute,1,1,,\n",
+        2,
+    )
+    .await;
+
+    // The dispute opened in the first run is settled in the second
+    let mut engine = run(&database, "type,client,tx,amount\nchargeback,1,1,\ndeposit,2,4,5.0\n", 2).await;
+    let account = engine.accounts.get(&1).unwrap();
+    assert_eq!((account.total, account.held, account.locked), (0.0, 0.0, true));
+    assert_eq!(engine.account(1, Some("EUR")).unwrap().available, 50.0);
+    assert_eq!(engine.accounts.get(&2).unwrap().available, 25.0);
+
+    // Deposits from earlier runs can still be disputed
+    engine.handle_transaction(Transaction::new(TransactionType::Dispute, 2, 3, None)).unwrap();
+    assert_eq!(engine.accounts.get(&2).unwrap().held, 20.0);
+
+    let store = SqliteStore::open(&database).unwrap();
+    assert!(store.account(1, None).unwrap().unwrap().locked);
+    assert_eq!(store.account(1, Some("EUR")).unwrap().unwrap().total, 50.0);
+    assert_eq!(store.account(2, None).unwrap().unwrap().total, 25.0);
+    assert!(store.account(3, None).unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_rows_are_committed_in_batches() {
+    let dir = tempfile::tempdir().unwrap();
+    let database = dir.path().join("engine.db");
+    let mut store = SqliteStore::open(&database).unwrap();
+    store.batch_size = 2;
+    let mut engine = TransactionEngine::new();
+    store.load(&mut engine).unwrap();
+
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(10.0))).unwrap();
+    store.row_handled(&mut engine).unwrap();
+    assert!(store.account(1, None).unwrap().is_none());
+
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 2, Some(5.0))).unwrap();
+    store.row_handled(&mut engine).unwrap();
+    assert_eq!(store.account(1, None).unwrap().unwrap().total, 15.0);
+}
+
+#[test]
+fn test_schema_is_migrated_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let database = dir.path().join("engine.db");
+
+    assert_eq!(SqliteStore::open(&database).unwrap().version().unwrap(), 2);
+    // Reopening leaves an up-to-date schema alone
+    assert_eq!(SqliteStore::open(&database).unwrap().version().unwrap(), 2);
+
+    let connection = rusqlite::Connection::open(&database).unwrap();
+    connection.pragma_update(None, "user_version", 99).unwrap();
+    drop(connection);
+    assert!(SqliteStore::open(&database).is_err());
+}
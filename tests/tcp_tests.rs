@@ -0,0 +1,109 @@
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{oneshot, Mutex};
+use toy_payments_engine::engine::TransactionEngine;
+use toy_payments_engine::server::SharedEngine;
+use toy_payments_engine::tcp;
+
+// Starts the ingestion server on a free localhost port; the sender shuts it down
+async fn start() -> (String, SharedEngine, oneshot::Sender<()>, tokio::task::JoinHandle<std::io::Result<()>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let engine: SharedEngine = Arc::new(Mutex::new(TransactionEngine::new()));
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(tcp::serve(listener, engine.clone(), async {
+        let _ = stopped.await;
+    }));
+    (address, engine, stop, server)
+}
+
+#[tokio::test]
+async fn test_stream_acknowledges_every_row() {
+    let (address, engine, _stop, _server) = start().await;
+
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    stream
+        .write_all(b"type,client,tx,amount\ndeposit,1,1,10.0\nwithdrawal,1,2,50.0\n\ndeposit,0,3,1.0\ndeposit,1,four,1.0\ndeposit,1,5,2.5\n")
+        .await
+        .unwrap();
+    stream.shutdown().await.unwrap();
+
+    let mut acks = String::new();
+    stream.read_to_string(&mut acks).await.unwrap();
+    let acks: Vec<&str> = acks.lines().collect();
+
+    assert_eq!(acks.len(), 5);
+    assert_eq!(acks[0], "2,accepted,1,");
+    assert!(acks[1].starts_with("3,rejected,2,"));
+    assert!(acks[2].starts_with("5,invalid,3,"));
+    assert!(acks[3].starts_with("6,malformed,,"));
+    assert_eq!(acks[4], "7,accepted,5,");
+
+    assert_eq!(engine.lock().await.accounts.get(&1).unwrap().total, 12.5);
+}
+
+#[tokio::test]
+async fn test_connections_share_the_engine() {
+    let (address, engine, _stop, _server) = start().await;
+
+    let mut first = TcpStream::connect(&address).await.unwrap();
+    let mut second = TcpStream::connect(&address).await.unwrap();
+    first.write_all(b"type,client,tx,amount\ndeposit,1,1,10.0\n").await.unwrap();
+    let mut acks = BufReader::new(&mut first);
+    let mut ack = String::new();
+    acks.read_line(&mut ack).await.unwrap();
+    assert_eq!(ack, "2,accepted,1,\n");
+
+    // A deposit from one connection can be withdrawn from another
+    second.write_all(b"type,client,tx,amount\nwithdrawal,1,2,4.0\n").await.unwrap();
+    let mut acks = BufReader::new(&mut second);
+    ack.clear();
+    acks.read_line(&mut ack).await.unwrap();
+    assert_eq!(ack, "2,accepted,2,\n");
+
+    assert_eq!(engine.lock().await.accounts.get(&1).unwrap().total, 6.0);
+}
+
+#[tokio::test]
+async fn test_shutdown_closes_open_connections() {
+    let (address, engine, stop, server) = start().await;
+
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    stream.write_all(b"type,client,tx,amount\ndeposit,1,1,10.0\n").await.unwrap();
+    let mut ack = String::new();
+    BufReader::new(&mut stream).read_line(&mut ack).await.unwrap();
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+
+    // The connection was closed without the producer hanging up first
+    let mut rest = String::new();
+    stream.read_to_string(&mut rest).await.unwrap();
+    assert_eq!(rest, "");
+    assert_eq!(engine.lock().await.accounts.get(&1).unwrap().total, 10.0);
+}
+
+#[tokio::test]
+async fn test_overlong_line_is_malformed_and_closes_the_connection() {
+    let (address, engine, _stop, _server) = start().await;
+
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    let mut rows = b"type,client,tx,amount\ndeposit,1,1,10.0\ndeposit,1,2,".to_vec();
+    rows.extend(std::iter::repeat_n(b'1', 70 * 1024));
+    rows.extend(b"\ndeposit,1,3,1.0\n");
+    stream.write_all(&rows).await.unwrap();
+
+    let mut acks = BufReader::new(&mut stream);
+    let mut ack = String::new();
+    acks.read_line(&mut ack).await.unwrap();
+    assert_eq!(ack, "2,accepted,1,\n");
+    ack.clear();
+    acks.read_line(&mut ack).await.unwrap();
+    assert!(ack.starts_with("3,malformed,,"));
+
+    // Nothing after the long line is read
+    ack.clear();
+    assert!(matches!(acks.read_line(&mut ack).await, Ok(0) | Err(_)));
+    assert_eq!(engine.lock().await.accounts.get(&1).unwrap().total, 10.0);
+}
@@ -3,7 +3,7 @@ use std::fs::File;
 use std::io::Write;
 use tokio::fs;
 use std::io::{stdout, BufWriter};
-use toy_payments_engine::utils::{process_csv, validate_transaction, process_file};
+use toy_payments_engine::utils::{process_csv, validate_transaction, process_file, write_accounts, write_authorizations, write_fees, RejectionKind};
 use toy_payments_engine::engine::TransactionEngine;
 use toy_payments_engine::models::{Transaction, TransactionType, ClientAccount};
 
@@ -56,60 +56,92 @@ chargeback,1,3,1.0
     assert!(engine.accounts.is_empty(), "No accounts should have been created");
 }
 
+#[tokio::test]
+async fn test_process_csv_skips_unusable_amounts() {
+    let mut engine = TransactionEngine::new();
+    let csv_data = r#"
+type,client,tx,amount
+deposit,1,1,NaN
+deposit,1,2,inf
+deposit,1,3,1e308
+deposit,1,4,   1.123456   
+deposit,1,5,2.5
+"#;
+
+    let mut temp_file = NamedTempFile::new().unwrap();
+    writeln!(temp_file, "{}", csv_data.trim()).unwrap();
+    let file_path = temp_file.into_temp_path();
+
+    let result = process_csv(file_path.to_str().unwrap(), &mut engine).await;
+    assert!(result.is_ok(), "Unusable amounts should be skipped, not abort processing");
+
+    // Only the last deposit is usable
+    let account = engine.accounts.get(&1).unwrap();
+    assert_eq!(account.available, 2.5);
+    assert_eq!(account.total, 2.5);
+}
+
+#[tokio::test]
+async fn test_process_csv_classifies_rejections() {
+    let mut engine = TransactionEngine::new();
+    let csv_data = r#"
+type,client,tx,amount
+deposit,1,1,0.0
+deposit,1,2,5.0
+withdrawal,1,3,50.0
+deposit,1
+dispute,1,2,
+"#;
+
+    let mut temp_file = NamedTempFile::new().unwrap();
+    writeln!(temp_file, "{}", csv_data.trim()).unwrap();
+    let file_path = temp_file.into_temp_path();
+
+    let report = process_csv(file_path.to_str().unwrap(), &mut engine).await.unwrap();
+
+    assert_eq!(report.accepted, 2);
+    assert_eq!(report.count(RejectionKind::Invalid), 1);
+    assert_eq!(report.count(RejectionKind::Rejected), 1);
+    assert_eq!(report.count(RejectionKind::Malformed), 1);
+
+    // The zero deposit is a validation failure, not a business rejection
+    let zero = &report.rejections[0];
+    assert_eq!(zero.kind, RejectionKind::Invalid);
+    assert_eq!(zero.tx_id, Some(1));
+    assert_eq!(zero.line, Some(2));
+    assert_eq!(zero.reason, "Deposit amount must be positive");
+}
+
 #[tokio::test]
 async fn test_validate_transaction_valid_cases() {
-    let valid_deposit = Transaction {
-        tx_type: TransactionType::Deposit,
-        client_id: 1,
-        tx_id: 1,
-        amount: Some(1.5),
-    };
+    let valid_deposit = Transaction::new(TransactionType::Deposit, 1, 1, Some(1.5));
 
-    let valid_withdrawal = Transaction {
-        tx_type: TransactionType::Withdrawal,
-        client_id: 1,
-        tx_id: 2,
-        amount: Some(0.5),
-    };
+    let valid_withdrawal = Transaction::new(TransactionType::Withdrawal, 1, 2, Some(0.5));
+
+    let partial_dispute = Transaction::new(TransactionType::Dispute, 1, 1, Some(1.0));
 
     assert!(validate_transaction(&valid_deposit).is_ok(), "Valid deposit should pass validation");
     assert!(validate_transaction(&valid_withdrawal).is_ok(), "Valid withdrawal should pass validation");
+    assert!(validate_transaction(&partial_dispute).is_ok(), "Partial dispute should pass validation");
 }
 
 #[tokio::test]
 async fn test_validate_transaction_invalid_cases() {
-    let invalid_client = Transaction {
-        tx_type: TransactionType::Deposit,
-        client_id: 0,
-        tx_id: 1,
-        amount: Some(1.5),
-    };
+    let invalid_client = Transaction::new(TransactionType::Deposit, 0, 1, Some(1.5));
 
-    let invalid_tx_id = Transaction {
-        tx_type: TransactionType::Withdrawal,
-        client_id: 1,
-        tx_id: 0,
-        amount: Some(0.5),
-    };
+    let invalid_tx_id = Transaction::new(TransactionType::Withdrawal, 1, 0, Some(0.5));
 
-    let negative_amount = Transaction {
-        tx_type: TransactionType::Deposit,
-        client_id: 1,
-        tx_id: 2,
-        amount: Some(-1.0),
-    };
+    let negative_amount = Transaction::new(TransactionType::Deposit, 1, 2, Some(-1.0));
 
-    let dispute_with_amount = Transaction {
-        tx_type: TransactionType::Dispute,
-        client_id: 1,
-        tx_id: 3,
-        amount: Some(1.0),
-    };
+    let dispute_with_negative_amount = Transaction::new(TransactionType::Dispute, 1, 3, Some(-1.0));
 
     assert!(validate_transaction(&invalid_client).is_err(), "Invalid client ID should fail validation");
     assert!(validate_transaction(&invalid_tx_id).is_err(), "Invalid transaction ID should fail validation");
     assert!(validate_transaction(&negative_amount).is_err(), "Negative amount should fail validation");
-    assert!(validate_transaction(&dispute_with_amount).is_err(), "Dispute with amount should fail validation");
+    assert!(
+        validate_transaction(&dispute_with_negative_amount).is_err(),
+        "Dispute with a negative amount should fail validation"
+    );
 }
 
 #[tokio::test]
@@ -154,10 +186,11 @@ async fn test_process_file_invalid_csv() {
     let mut engine = TransactionEngine::new();
 
     // Process the CSV file
-    let result = process_file(temp_file_path, &mut engine).await;
+    let report = process_file(temp_file_path, &mut engine).await.unwrap();
 
-    // Assertions
-    assert!(result.is_err()); // Expect an error due to invalid transaction type
+    // Assertions: the row with an invalid transaction type is malformed, the file goes on
+    assert_eq!(report.count(RejectionKind::Malformed), 1);
+    assert!(engine.accounts.is_empty());
 
     // Cleanup
     fs::remove_file(temp_file_path)
@@ -215,4 +248,133 @@ fn test_export_accounts_to_stdout() {
     assert!(output.contains("client,available,held,total,locked"));
     assert!(output.contains("1,100.0000,0.0000,100.0000,false"));
     assert!(output.contains("2,200.0000,50.0000,250.0000,true"));
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_export_multi_currency_accounts() {
+    let mut engine = TransactionEngine::new();
+    let csv_data = r#"
+type,client,tx,amount,currency
+deposit,1,1,10.0,EUR
+deposit,1,2,5.0,USD
+deposit,2,3,7.5,
+"#;
+
+    let mut temp_file = NamedTempFile::new().unwrap();
+    writeln!(temp_file, "{}", csv_data.trim()).unwrap();
+    let file_path = temp_file.into_temp_path();
+    process_csv(file_path.to_str().unwrap(), &mut engine).await.unwrap();
+
+    let mut buffer = Vec::new();
+    write_accounts(&engine, &mut buffer).unwrap();
+    let output = String::from_utf8(buffer).unwrap();
+
+    assert!(output.starts_with("client,currency,available,held,total,locked"));
+    assert!(output.contains("1,EUR,10.0000,0.0000,10.0000,false"));
+    assert!(output.contains("1,USD,5.0000,0.0000,5.0000,false"));
+    assert!(output.contains("2,,7.5000,0.0000,7.5000,false"));
+}
+
+#[test]
+fn test_export_single_currency_accounts_unchanged() {
+    let mut engine = TransactionEngine::new();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(2.0))).unwrap();
+
+    let mut buffer = Vec::new();
+    write_accounts(&engine, &mut buffer).unwrap();
+
+    assert_eq!(String::from_utf8(buffer).unwrap(), "client,available,held,total,locked\n1,2.0000,0.0000,2.0000,false\n");
+}
+
+#[test]
+fn test_export_accounts_with_credit_limits() {
+    let mut engine = TransactionEngine::new();
+    engine.credit.insert(1, None, 50.0).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(10.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 1, 2, Some(30.0))).unwrap();
+
+    let mut buffer = Vec::new();
+    write_accounts(&engine, &mut buffer).unwrap();
+
+    assert_eq!(
+        String::from_utf8(buffer).unwrap(),
+        "client,available,held,total,locked,credit_limit,headroom\n1,-20.0000,0.0000,-20.0000,false,50.0000,30.0000\n"
+    );
+}
+
+#[test]
+fn test_write_outstanding_authorizations() {
+    let mut engine = TransactionEngine::new();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0))).unwrap();
+    for tx_id in [4, 2, 3] {
+        engine.handle_transaction(Transaction::new(TransactionType::Authorize, 1, tx_id, Some(tx_id as f64))).unwrap();
+    }
+    engine.handle_transaction(Transaction::new(TransactionType::Void, 1, 3, None)).unwrap();
+
+    let mut buffer = Vec::new();
+    write_authorizations(&engine, &mut buffer).unwrap();
+
+    assert_eq!(
+        String::from_utf8(buffer).unwrap(),
+        "tx,client,currency,amount\n2,1,,2.0000\n4,1,,4.0000\n"
+    );
+}
+
+#[test]
+fn test_write_fees() {
+    let mut engine = TransactionEngine::new();
+    engine.fees.read_rules("type,tier,flat,percent\nwithdrawal,,0.25,0\n".as_bytes()).unwrap();
+    engine.fees.house_account = Some(9);
+    engine.keep_fee_lines();
+    engine.handle_transaction(Transaction::new(TransactionType::Deposit, 1, 1, Some(100.0))).unwrap();
+    engine.handle_transaction(Transaction::new(TransactionType::Withdrawal, 1, 2, Some(10.0))).unwrap();
+
+    let mut buffer = Vec::new();
+    write_fees(&engine, &mut buffer).unwrap();
+
+    assert_eq!(String::from_utf8(buffer).unwrap(), "tx,client,type,currency,amount\n2,1,withdrawal,,0.2500\n");
+}
+
+#[tokio::test]
+async fn test_process_csv_matches_columns_by_name() {
+    let mut engine = TransactionEngine::new();
+    let csv_data = r#"
+client,type,tx,amount,to_client,timestamp
+1,deposit,1,10.0,,2024-03-01T12:00:00Z
+1,transfer,2,4.0,2,2024-03-01T12:01:00Z
+"#;
+
+    let mut temp_file = NamedTempFile::new().unwrap();
+    writeln!(temp_file, "{}", csv_data.trim()).unwrap();
+    let file_path = temp_file.into_temp_path();
+
+    let report = process_csv(file_path.to_str().unwrap(), &mut engine).await.unwrap();
+
+    assert_eq!(report.accepted, 2);
+    assert_eq!(engine.accounts.get(&2).unwrap().total, 4.0);
+    assert_eq!(engine.latest_timestamp(), Some("2024-03-01T12:01:00Z".parse().unwrap()));
+}
+
+#[tokio::test]
+async fn test_process_csv_skips_rows_with_unreadable_fields() {
+    let mut engine = TransactionEngine::new();
+    let csv_data = r#"
+type,client,tx,amount,timestamp
+deposit,1,1,10.0,2024-03-01T12:00:00Z
+deposit,1,2,5.0,yesterday
+deposit,1,3,ten,
+withdrawal,1,4,3.0,2024-03-01T12:02:00Z
+"#;
+
+    let mut temp_file = NamedTempFile::new().unwrap();
+    writeln!(temp_file, "{}", csv_data.trim()).unwrap();
+    let file_path = temp_file.into_temp_path();
+
+    let report = process_csv(file_path.to_str().unwrap(), &mut engine).await.unwrap();
+
+    assert_eq!(report.accepted, 2);
+    assert_eq!(report.count(RejectionKind::Malformed), 2);
+    assert_eq!(report.rejections[0].line, Some(3));
+    assert_eq!(report.rejections[1].line, Some(4));
+    assert_eq!(engine.accounts.get(&1).unwrap().total, 7.0);
+}
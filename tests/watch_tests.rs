@@ -0,0 +1,123 @@
+use std::fs;
+use toy_payments_engine::engine::TransactionEngine;
+use toy_payments_engine::watch::{FileOutcome, Inbox};
+
+#[tokio::test]
+async fn test_inbox_processes_files_once_they_are_complete() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut inbox = Inbox::open(dir.path()).unwrap();
+    let mut engine = TransactionEngine::new();
+
+    fs::write(dir.path().join("first.csv"), "type,client,tx,amount\ndeposit,1,1,10.0\nwithdrawal,1,2,50.0\n").unwrap();
+    fs::write(dir.path().join("notes.txt"), "not a transaction file").unwrap();
+
+    // A file is only taken once it has not changed between two polls
+    assert!(inbox.poll(&mut engine).await.unwrap().is_empty());
+    let outcomes = inbox.poll(&mut engine).await.unwrap();
+    assert_eq!(outcomes.len(), 1);
+    assert!(matches!(&outcomes[0], FileOutcome::Processed { report, .. } if report.accepted == 1));
+
+    assert!(!dir.path().join("first.csv").exists());
+    assert!(dir.path().join("processed/first.csv").exists());
+    assert!(dir.path().join("notes.txt").exists());
+    let rejections = fs::read_to_string(dir.path().join("processed/first.csv.rejections.csv")).unwrap();
+    assert!(rejections.starts_with("line,client,tx,kind,reason\n3,1,2,rejected,"));
+    assert_eq!(engine.accounts.get(&1).unwrap().total, 10.0);
+}
+
+#[tokio::test]
+async fn test_inbox_never_processes_the_same_content_twice() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut engine = TransactionEngine::new();
+    let contents = "type,client,tx,amount\ndeposit,1,1,10.0\n";
+
+    let mut inbox = Inbox::open(dir.path()).unwrap();
+    fs::write(dir.path().join("a.csv"), contents).unwrap();
+    inbox.poll(&mut engine).await.unwrap();
+    inbox.poll(&mut engine).await.unwrap();
+
+    // The same content under another name, seen by a new run
+    let mut inbox = Inbox::open(dir.path()).unwrap();
+    fs::write(dir.path().join("b.csv"), contents).unwrap();
+    inbox.poll(&mut engine).await.unwrap();
+    let outcomes = inbox.poll(&mut engine).await.unwrap();
+
+    assert!(matches!(&outcomes[..], [FileOutcome::Duplicate { .. }]));
+    assert!(dir.path().join("failed/b.csv").exists());
+    assert_eq!(engine.accounts.get(&1).unwrap().total, 10.0);
+}
+
+#[tokio::test]
+async fn test_inbox_moves_unreadable_files_to_failed() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut inbox = Inbox::open(dir.path()).unwrap();
+    let mut engine = TransactionEngine::new();
+
+    // A header that is not UTF-8 cannot be matched to any column
+    fs::write(dir.path().join("bad.csv"), b"type,cli\xffent,tx,amount\ndeposit,1,1,10.0\n").unwrap();
+    inbox.poll(&mut engine).await.unwrap();
+    let outcomes = inbox.poll(&mut engine).await.unwrap();
+
+    assert!(matches!(&outcomes[..], [FileOutcome::Failed { .. }]));
+    assert!(dir.path().join("failed/bad.csv").exists());
+    assert!(dir.path().join("failed/bad.csv.error").exists());
+}
+
+#[tokio::test]
+async fn test_inbox_takes_failed_content_again() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut inbox = Inbox::open(dir.path()).unwrap();
+    let mut engine = TransactionEngine::new();
+    let contents = b"type,cli\xffent,tx,amount\ndeposit,1,1,10.0\n";
+
+    fs::write(dir.path().join("bad.csv"), contents).unwrap();
+    inbox.poll(&mut engine).await.unwrap();
+    inbox.poll(&mut engine).await.unwrap();
+
+    // Delivered again, it is not mistaken for content already processed
+    fs::write(dir.path().join("bad.csv"), contents).unwrap();
+    inbox.poll(&mut engine).await.unwrap();
+    let outcomes = inbox.poll(&mut engine).await.unwrap();
+    assert!(matches!(&outcomes[..], [FileOutcome::Failed { .. }]));
+    assert!(dir.path().join("failed/bad.csv.1").exists());
+}
+
+#[tokio::test]
+async fn test_inbox_records_a_file_only_once_it_is_saved() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut engine = TransactionEngine::new();
+    fs::write(dir.path().join("a.csv"), "type,client,tx,amount\ndeposit,1,1,10.0\n").unwrap();
+
+    let mut inbox = Inbox::open(dir.path()).unwrap();
+    let failing_save = |_: &mut TransactionEngine| Err(std::io::Error::other("disk full"));
+    inbox.poll_with(&mut engine, failing_save).await.unwrap();
+    assert!(inbox.poll_with(&mut engine, failing_save).await.is_err());
+    assert!(dir.path().join("a.csv").exists());
+
+    // A new run with a fresh engine processes the file instead of skipping it
+    let mut engine = TransactionEngine::new();
+    let mut inbox = Inbox::open(dir.path()).unwrap();
+    inbox.poll(&mut engine).await.unwrap();
+    let outcomes = inbox.poll(&mut engine).await.unwrap();
+    assert!(matches!(&outcomes[..], [FileOutcome::Processed { .. }]));
+    assert_eq!(engine.accounts.get(&1).unwrap().total, 10.0);
+}
+
+#[tokio::test]
+async fn test_inbox_does_not_apply_rows_twice_when_retaken() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut engine = TransactionEngine::new();
+    fs::write(dir.path().join("a.csv"), "type,client,tx,amount\ndeposit,1,1,10.0\ndispute,1,1,4.0\n").unwrap();
+
+    let mut inbox = Inbox::open(dir.path()).unwrap();
+    let failing_save = |_: &mut TransactionEngine| Err(std::io::Error::other("disk full"));
+    inbox.poll_with(&mut engine, failing_save).await.unwrap();
+    assert!(inbox.poll_with(&mut engine, failing_save).await.is_err());
+
+    // Taken again by the same run, the partial dispute is not held a second time
+    inbox.poll(&mut engine).await.unwrap();
+    let outcomes = inbox.poll(&mut engine).await.unwrap();
+    assert!(matches!(&outcomes[..], [FileOutcome::Processed { report, .. }] if report.rejections.is_empty()));
+    let account = engine.accounts.get(&1).unwrap();
+    assert_eq!((account.available, account.held), (6.0, 4.0));
+}